@@ -1,7 +1,28 @@
-use pixel::Pixel;
+use pixel::{Pixel, PixelOrder};
+use rect::Rect;
+use point::Point;
+use view::{BitmapView, BitmapViewMut};
 use ppm::PPMImage;
+use format::ImageFormat;
+use bmp;
+use qoi;
+use tga;
+use ppm;
+use png;
 
 use std::path::Path;
+use std::io;
+use std::slice;
+use std::cmp::min;
+
+// How far apart two equally sized bitmaps are
+#[derive(Copy, Clone, Debug)]
+pub struct DiffStats {
+    pub max_delta: u8,            // Largest difference in any channel
+    pub differing: usize,         // Pixels with any difference at all
+    pub psnr:      f64,           // In dB, infinite when identical
+    pub bounds:    Option<Rect>,  // Covers every differing pixel
+}
 
 #[derive(Clone)]
 pub struct Bitmap {
@@ -28,7 +49,307 @@ impl Bitmap {
         self.pixels[x + y*self.width]
     }
 
+    // The pixels as premultiplied bytes in Pixel::ORDER, rows packed top to
+    // bottom, ready to hand to a window or video library without a copy
+    pub fn as_bytes(&self) -> &[u8] {
+        // Pixel is four u8s with repr(C), so any pixel slice is a byte slice
+        unsafe { slice::from_raw_parts(self.pixels.as_ptr() as *const u8, self.pixels.len() * 4) }
+    }
+
+    // Writes through this skip pack_argb's premultiplication check, and the
+    // drawing code assumes r, g and b never exceed a
+    pub fn as_bytes_mut(&mut self) -> &mut [u8] {
+        unsafe { slice::from_raw_parts_mut(self.pixels.as_mut_ptr() as *mut u8, self.pixels.len() * 4) }
+    }
+
+    // The whole bitmap as a view, for code that also takes borrowed pixels
+    pub fn view(&self) -> BitmapView<'_> {
+        BitmapView::new(&self.pixels, self.width, self.height, self.width)
+    }
+
+    pub fn view_mut(&mut self) -> BitmapViewMut<'_> {
+        BitmapViewMut::new(&mut self.pixels, self.width, self.height, self.width)
+    }
+
+    // A region of the bitmap, without copying; see BitmapView::subset
+    pub fn subset(&self, rect: &Rect) -> BitmapView<'_> {
+        self.view().subset(rect)
+    }
+
+    // A region to draw into, e.g. with Canvas::new, or to copy into
+    pub fn subset_mut(&mut self, rect: &Rect) -> BitmapViewMut<'_> {
+        self.view_mut().into_subset(rect)
+    }
+
+    // A copy of the pixels under rect as a bitmap of their own
+    pub fn extract(&self, rect: &Rect) -> Bitmap {
+        self.subset(rect).to_bitmap()
+    }
+
+    // See BitmapViewMut::copy_from
+    pub fn copy_from(&mut self, src: &BitmapView, src_rect: &Rect, dst: &Point) {
+        self.view_mut().copy_from(src, src_rect, dst);
+    }
+
+    // A copy in the given order, for consumers that want the other one
+    pub fn to_bytes(&self, order: PixelOrder) -> Vec<u8> {
+        if order == Pixel::ORDER {
+            return self.as_bytes().to_vec();
+        }
+
+        let mut bytes = Vec::with_capacity(self.pixels.len() * 4);
+        for px in &self.pixels {
+            let [c0, g, c2, a] = px.to_bytes();
+            bytes.extend_from_slice(&[c2, g, c0, a]);
+        }
+        bytes
+    }
+
+    pub fn diff(&self, other: &Bitmap) -> DiffStats {
+        self.assert_same_size(other);
+
+        let mut max_delta = 0;
+        let mut differing = 0;
+        let mut sum_sq = 0u64;
+        let (mut l, mut t, mut r, mut b) = (self.width, self.height, 0, 0);
+
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let (p, q) = (self.get(x, y), other.get(x, y));
+                let deltas = channel_deltas(&p, &q);
+
+                for &d in &deltas {
+                    sum_sq += d as u64 * d as u64;
+                }
+                let delta = *deltas.iter().max().unwrap();
+                if delta > 0 {
+                    max_delta = max_delta.max(delta);
+                    differing += 1;
+                    l = l.min(x);
+                    t = t.min(y);
+                    r = r.max(x + 1);
+                    b = b.max(y + 1);
+                }
+            }
+        }
+
+        let psnr = if sum_sq == 0 {
+            f64::INFINITY
+        } else {
+            let mse = sum_sq as f64 / (self.pixels.len() * 4) as f64;
+            10.0 * (255.0 * 255.0 / mse).log10()
+        };
+        let bounds = if differing > 0 {
+            Some(Rect::make_ltrb(l as f32, t as f32, r as f32, b as f32))
+        } else {
+            None
+        };
+
+        DiffStats {
+            max_delta: max_delta,
+            differing: differing,
+            psnr:      psnr,
+            bounds:    bounds,
+        }
+    }
+
+    // Pixels differing by more than tolerance in red, smaller differences in
+    // yellow, over a faded greyscale copy of self
+    pub fn diff_image(&self, other: &Bitmap, tolerance: u8) -> Bitmap {
+        self.assert_same_size(other);
+
+        let mut diff = Bitmap::new(self.width, self.height);
+        for i in 0..self.pixels.len() {
+            let (p, q) = (self.pixels[i], other.pixels[i]);
+            let delta = *channel_deltas(&p, &q).iter().max().unwrap();
+
+            diff.pixels[i] = if delta > tolerance {
+                Pixel::pack_rgb(255, 0, 0)
+            } else if delta > 0 {
+                Pixel::pack_rgb(255, 255, 0)
+            } else {
+                let grey = (luma(&p) / 4.0 + 191.0) as u8;
+                Pixel::pack_rgb(grey, grey, grey)
+            };
+        }
+
+        diff
+    }
+
+    // Mean structural similarity of the luma, over 8x8 windows every 4
+    // pixels: 1 for identical images, lower as structure differs
+    pub fn ssim(&self, other: &Bitmap) -> f64 {
+        self.assert_same_size(other);
+        if self.pixels.is_empty() {
+            return 1.0;
+        }
+
+        let c1 = (0.01 * 255.0) * (0.01 * 255.0);
+        let c2 = (0.03 * 255.0) * (0.03 * 255.0);
+
+        // Images smaller than a window are one window
+        let (win_w, win_h) = (self.width.min(8), self.height.min(8));
+
+        let mut total = 0.0;
+        let mut windows = 0;
+        let mut y = 0;
+        while y + win_h <= self.height {
+            let mut x = 0;
+            while x + win_w <= self.width {
+                let n = (win_w * win_h) as f64;
+                let (mut sum_a, mut sum_b, mut sum_aa, mut sum_bb, mut sum_ab) = (0.0, 0.0, 0.0, 0.0, 0.0);
+                for wy in y..y + win_h {
+                    for wx in x..x + win_w {
+                        let a = luma(&self.get(wx, wy));
+                        let b = luma(&other.get(wx, wy));
+                        sum_a  += a;
+                        sum_b  += b;
+                        sum_aa += a*a;
+                        sum_bb += b*b;
+                        sum_ab += a*b;
+                    }
+                }
+
+                let (mean_a, mean_b) = (sum_a / n, sum_b / n);
+                let var_a  = sum_aa / n - mean_a*mean_a;
+                let var_b  = sum_bb / n - mean_b*mean_b;
+                let covar  = sum_ab / n - mean_a*mean_b;
+
+                total += ((2.0*mean_a*mean_b + c1) * (2.0*covar + c2)) /
+                         ((mean_a*mean_a + mean_b*mean_b + c1) * (var_a + var_b + c2));
+                windows += 1;
+
+                x += 4;
+            }
+            y += 4;
+        }
+
+        total / windows as f64
+    }
+
+    // Mirrors left to right, in place
+    pub fn flip_horizontal(&mut self) {
+        if self.width == 0 {
+            return;
+        }
+        for row in self.pixels.chunks_mut(self.width) {
+            row.reverse();
+        }
+    }
+
+    // Mirrors top to bottom, in place
+    pub fn flip_vertical(&mut self) {
+        let (w, h) = (self.width, self.height);
+        for y in 0..h/2 {
+            let (top, bottom) = self.pixels.split_at_mut((h - 1 - y)*w);
+            top[y*w .. (y + 1)*w].swap_with_slice(&mut bottom[..w]);
+        }
+    }
+
+    // Half a turn is both flips, which is the whole buffer reversed
+    pub fn rotate_180(&mut self) {
+        self.pixels.reverse();
+    }
+
+    // A quarter turn clockwise, so the top row becomes the right column
+    pub fn rotate_90(&mut self) {
+        let h = self.height;
+        self.swap_axes(|x, y| (y, h - 1 - x));
+    }
+
+    // A quarter turn counterclockwise
+    pub fn rotate_270(&mut self) {
+        let w = self.width;
+        self.swap_axes(|x, y| (w - 1 - y, x));
+    }
+
+    // Mirrors across the diagonal from the top-left
+    pub fn transpose(&mut self) {
+        self.swap_axes(|x, y| (y, x));
+    }
+
+    // Rebuilds the pixels with width and height swapped, each new (x, y)
+    // read from src(x, y) in the old layout. Works in square blocks so reads
+    // and writes both stay within a few cache lines.
+    fn swap_axes<F: Fn(usize, usize) -> (usize, usize)>(&mut self, src: F) {
+        const BLOCK: usize = 32;
+        let (w, h) = (self.height, self.width);
+        let mut pixels = vec![Pixel::pack_argb(0, 0, 0, 0); w*h];
+
+        for by in (0..h).step_by(BLOCK) {
+            for bx in (0..w).step_by(BLOCK) {
+                for y in by..min(by + BLOCK, h) {
+                    for x in bx..min(bx + BLOCK, w) {
+                        let (sx, sy) = src(x, y);
+                        pixels[y*w + x] = self.pixels[sy*self.width + sx];
+                    }
+                }
+            }
+        }
+
+        self.width  = w;
+        self.height = h;
+        self.pixels = pixels;
+    }
+
+    fn assert_same_size(&self, other: &Bitmap) {
+        assert!(self.width == other.width && self.height == other.height,
+                "bitmaps differ in size: {}x{} vs {}x{}", self.width, self.height, other.width, other.height);
+    }
+
+    // Format is picked from the file extension, defaulting to PPM
     pub fn read(&mut self, path: &Path) {
+        match ImageFormat::from_path(path) {
+            Some(ImageFormat::Bmp) => *self = bmp::read(path).unwrap(),
+            Some(ImageFormat::Qoi) => *self = qoi::read(path).unwrap(),
+            Some(ImageFormat::Tga) => *self = tga::read(path).unwrap(),
+            Some(ImageFormat::Png) => panic!("PNG decoding is not supported"),
+            _                      => self.read_ppm(path),
+        }
+    }
+
+    pub fn write(&self, path: &Path) {
+        match ImageFormat::from_path(path) {
+            Some(ImageFormat::Bmp) => bmp::write(self, path).unwrap(),
+            Some(ImageFormat::Qoi) => qoi::write(self, path).unwrap(),
+            Some(ImageFormat::Tga) => tga::write(self, path).unwrap(),
+            Some(ImageFormat::Png) => png::write(self, path).unwrap(),
+            _                      => self.write_ppm(path),
+        }
+    }
+
+    // Like read/write, but reporting failures instead of panicking
+    pub fn load(path: &Path) -> io::Result<Bitmap> {
+        match ImageFormat::from_path(path) {
+            Some(ImageFormat::Bmp) => bmp::read(path),
+            Some(ImageFormat::Qoi) => qoi::read(path),
+            Some(ImageFormat::Tga) => tga::read(path),
+            Some(ImageFormat::Png) => Err(io::Error::new(io::ErrorKind::InvalidInput, "PNG decoding is not supported")),
+            _                      => ppm::read(path).map(|bitmap| bitmap.through_color()),
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        match ImageFormat::from_path(path) {
+            Some(ImageFormat::Bmp) => bmp::write(self, path),
+            Some(ImageFormat::Qoi) => qoi::write(self, path),
+            Some(ImageFormat::Tga) => tga::write(self, path),
+            Some(ImageFormat::Png) => png::write(self, path),
+            _                      => ppm::write(&self.through_color(), path),
+        }
+    }
+
+    // PPMImage stores Colors, which don't round-trip every pixel exactly.
+    // Apply the same conversion so load/save agree with read/write.
+    fn through_color(&self) -> Bitmap {
+        let mut bitmap = self.clone();
+        for px in bitmap.pixels.iter_mut() {
+            *px = px.to_color().to_pixel();
+        }
+        bitmap
+    }
+
+    fn read_ppm(&mut self, path: &Path) {
         let mut image = PPMImage::new(self.width, self.height);
 
         image.read(path);
@@ -44,7 +365,7 @@ impl Bitmap {
         }
     }
 
-    pub fn write(&self, path: &Path) {
+    fn write_ppm(&self, path: &Path) {
         let mut image = PPMImage::new(self.width, self.height);
 
         for x in 0..self.width {
@@ -56,3 +377,12 @@ impl Bitmap {
         image.write(path);
     }
 }
+
+fn channel_deltas(p: &Pixel, q: &Pixel) -> [u8; 4] {
+    [p.a.abs_diff(q.a), p.r.abs_diff(q.r), p.g.abs_diff(q.g), p.b.abs_diff(q.b)]
+}
+
+// Of the premultiplied color, i.e. as composited over black
+fn luma(p: &Pixel) -> f64 {
+    0.299*p.r as f64 + 0.587*p.g as f64 + 0.114*p.b as f64
+}
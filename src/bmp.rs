@@ -0,0 +1,222 @@
+use bitmap::Bitmap;
+use pixel::Pixel;
+
+use std::path::Path;
+use std::fs::File;
+use std::io;
+use std::io::prelude::*;
+use std::io::BufWriter;
+
+const FILE_HEADER_SIZE: usize = 14;
+const V4_HEADER_SIZE:   usize = 108;
+
+// Compression types
+const BI_RGB:            u32 = 0;
+const BI_BITFIELDS:      u32 = 3;
+const BI_ALPHABITFIELDS: u32 = 6;
+
+// Channel masks, in (r, g, b, a) order
+type Masks = [u32; 4];
+
+pub fn read(path: &Path) -> io::Result<Bitmap> {
+    let mut bytes = Vec::new();
+    File::open(path)?.read_to_end(&mut bytes)?;
+
+    decode(&bytes)
+}
+
+pub fn write(bitmap: &Bitmap, path: &Path) -> io::Result<()> {
+    let mut file = BufWriter::new(File::create(path)?);
+    file.write_all(&encode(bitmap))?;
+    file.flush()
+}
+
+pub fn decode(bytes: &[u8]) -> io::Result<Bitmap> {
+    if bytes.len() < FILE_HEADER_SIZE + 4 || &bytes[0..2] != b"BM" {
+        return Err(invalid("not a BMP file"));
+    }
+
+    let data_offset = get_u32(bytes, 10) as usize;
+
+    // BITMAPINFOHEADER (40), its Adobe extensions (52, 56), V4 (108) and V5 (124)
+    let header_size = get_u32(bytes, FILE_HEADER_SIZE) as usize;
+    match header_size {
+        40 | 52 | 56 | 108 | 124 => {},
+        _ => return Err(invalid("unsupported BMP header")),
+    }
+    if bytes.len() < FILE_HEADER_SIZE + header_size {
+        return Err(invalid("truncated BMP header"));
+    }
+
+    let header = &bytes[FILE_HEADER_SIZE..];
+    let width       = get_u32(header, 4) as i32;
+    let height      = get_u32(header, 8) as i32;
+    let bpp         = get_u16(header, 14);
+    let compression = get_u32(header, 16);
+    let colors_used = get_u32(header, 32) as usize;
+
+    if width <= 0 || height == 0 || height == i32::MIN {
+        return Err(invalid("invalid BMP dimensions"));
+    }
+    if bpp != 8 && bpp != 24 && bpp != 32 {
+        return Err(invalid("unsupported BMP bit depth"));
+    }
+
+    // Negative height means rows are stored top-down
+    let top_down = height < 0;
+    let (w, h) = (width as usize, height.unsigned_abs() as usize);
+
+    // Masks live inside V4/V5 headers, but directly after a plain info header
+    let mut tables = FILE_HEADER_SIZE + header_size;
+    let masks = match (compression, bpp) {
+        (BI_RGB, 32) => [0x00ff0000, 0x0000ff00, 0x000000ff, 0],
+        (BI_RGB, _)  => [0; 4],
+        (BI_BITFIELDS, 32) | (BI_ALPHABITFIELDS, 32) => {
+            let count = if compression == BI_ALPHABITFIELDS {4} else {3};
+            let start = if header_size >= 52 {
+                FILE_HEADER_SIZE + 40
+            } else {
+                tables += 4*count;
+                FILE_HEADER_SIZE + header_size
+            };
+            if bytes.len() < start + 4*count {
+                return Err(invalid("truncated BMP masks"));
+            }
+
+            let mut masks = [0; 4];
+            for i in 0..count {
+                masks[i] = get_u32(bytes, start + 4*i);
+            }
+            if header_size >= 56 {
+                masks[3] = get_u32(bytes, FILE_HEADER_SIZE + 52);
+            }
+            masks
+        },
+        _ => return Err(invalid("unsupported BMP compression")),
+    };
+
+    // Palette entries are stored as BGRx
+    let mut palette = Vec::new();
+    if bpp == 8 {
+        let count = if colors_used == 0 {256} else {colors_used};
+        if count > 256 || bytes.len() < tables + 4*count {
+            return Err(invalid("invalid BMP palette"));
+        }
+
+        for i in 0..count {
+            let entry = &bytes[tables + 4*i..];
+            palette.push(Pixel::pack_rgb(entry[2], entry[1], entry[0]));
+        }
+    }
+
+    let stride = (w * bpp as usize).div_ceil(32) * 4;
+    match stride.checked_mul(h).and_then(|size| size.checked_add(data_offset)) {
+        Some(end) if end <= bytes.len() => {},
+        _ => return Err(invalid("truncated BMP pixel data")),
+    }
+
+    let mut bitmap = Bitmap::new(w, h);
+    for row in 0..h {
+        let y = if top_down {row} else {h - 1 - row};
+        let src = &bytes[data_offset + row*stride..];
+
+        for x in 0..w {
+            let px = match bpp {
+                8 => match palette.get(src[x] as usize) {
+                    Some(px) => *px,
+                    None     => return Err(invalid("BMP palette index out of range")),
+                },
+                24 => Pixel::pack_rgb(src[3*x + 2], src[3*x + 1], src[3*x]),
+                _  => unpack_masked(get_u32(src, 4*x), &masks),
+            };
+
+            bitmap.set(x, y, &px);
+        }
+    }
+
+    Ok(bitmap)
+}
+
+pub fn encode(bitmap: &Bitmap) -> Vec<u8> {
+    let (w, h) = (bitmap.width, bitmap.height);
+
+    let data_offset = FILE_HEADER_SIZE + V4_HEADER_SIZE;
+    let data_size   = w*h*4;
+
+    let mut out = Vec::with_capacity(data_offset + data_size);
+
+    // BITMAPFILEHEADER
+    out.extend_from_slice(b"BM");
+    put_u32(&mut out, (data_offset + data_size) as u32);
+    put_u32(&mut out, 0);
+    put_u32(&mut out, data_offset as u32);
+
+    // BITMAPV4HEADER
+    put_u32(&mut out, V4_HEADER_SIZE as u32);
+    put_u32(&mut out, w as u32);
+    put_u32(&mut out, h as u32);
+    put_u16(&mut out, 1);
+    put_u16(&mut out, 32);
+    put_u32(&mut out, BI_BITFIELDS);
+    put_u32(&mut out, data_size as u32);
+    put_u32(&mut out, 2835);  // 72 DPI
+    put_u32(&mut out, 2835);
+    put_u32(&mut out, 0);
+    put_u32(&mut out, 0);
+    put_u32(&mut out, 0x00ff0000);
+    put_u32(&mut out, 0x0000ff00);
+    put_u32(&mut out, 0x000000ff);
+    put_u32(&mut out, 0xff000000);
+    out.extend_from_slice(b"BGRs");  // LCS_sRGB
+    out.extend_from_slice(&[0; 48]); // Endpoints and gamma (unused for sRGB)
+
+    // Bottom-up BGRA rows, straight alpha
+    for y in (0..h).rev() {
+        for x in 0..w {
+            let (a, r, g, b) = bitmap.get(x, y).unpremul_argb();
+            out.extend_from_slice(&[b, g, r, a]);
+        }
+    }
+
+    out
+}
+
+fn unpack_masked(value: u32, masks: &Masks) -> Pixel {
+    let channel = |mask: u32, default: u8| -> u8 {
+        if mask == 0 {
+            return default;
+        }
+        let bits = (mask >> mask.trailing_zeros()).count_ones();
+        let max  = (1u64 << bits) - 1;
+        let v    = ((value & mask) >> mask.trailing_zeros()) as u64;
+        ((v * 255 + max/2) / max) as u8
+    };
+
+    let a = channel(masks[3], 255);
+    let r = channel(masks[0], 0);
+    let g = channel(masks[1], 0);
+    let b = channel(masks[2], 0);
+
+    Pixel::pack_unpremul_argb(a, r, g, b)
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+fn get_u16(bytes: &[u8], i: usize) -> u16 {
+    bytes[i] as u16 | (bytes[i+1] as u16) << 8
+}
+
+fn get_u32(bytes: &[u8], i: usize) -> u32 {
+    get_u16(bytes, i) as u32 | (get_u16(bytes, i+2) as u32) << 16
+}
+
+fn put_u16(out: &mut Vec<u8>, v: u16) {
+    out.extend_from_slice(&[v as u8, (v >> 8) as u8]);
+}
+
+fn put_u32(out: &mut Vec<u8>, v: u32) {
+    put_u16(out, v as u16);
+    put_u16(out, (v >> 16) as u16);
+}
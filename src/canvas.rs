@@ -1,123 +1,281 @@
 use bitmap::Bitmap;
+use view::Surface;
 use color::Color;
 use pixel::Pixel;
 use rect::Rect;
 use point::Point;
-use matrix::Matrix;
+use matrix::{Matrix, MatrixType};
 use triangle::Triangle;
-use shader::{Shader, Shaders};
-use util::{blend_row, map_rect_to_rect_mat};
+use shader::Shader;
+use context::DrawContext;
+use util::{blend_row, blend_color_row, clamp, clip_convex_polygon, in_guard_band, map_polygon};
 
 use std::path::Path;
 use std::cmp::{min, max};
 
-pub struct Canvas {
-    bitmap: Bitmap,
+// Draws into a Bitmap it owns, or any other Surface such as a BitmapViewMut
+pub struct Canvas<S: Surface = Bitmap> {
+    bitmap: S,
     ctms:   Vec<Matrix>,
+    clips:  Vec<Option<Vec<Point>>>,  // Convex, in device space
+    row:    Vec<Pixel>,               // Shaded pixels for the row being drawn
+    left:   usize,                    // Device position of the bitmap's top-left pixel
+    top:    usize,
 }
 
-impl Canvas {
-    pub fn new(bitmap: Bitmap) -> Canvas {
-        let ctms = vec![Matrix::identity()];
+// Shades the pixels starting at (x, y) into the given row
+type ShadeFn<'a> = dyn FnMut(usize, usize, &mut [Pixel]) + 'a;
+
+// Puts a row of shaded pixels onto the bitmap: blend_row, or a plain copy
+type WriteFn = fn(&[Pixel], &mut [Pixel]);
+
+impl<S: Surface> Canvas<S> {
+    pub fn new(bitmap: S) -> Canvas<S> {
+        Canvas::with_origin(bitmap, 0, 0)
+    }
+
+    // A canvas onto part of a larger device, with the bitmap standing for
+    // the device pixels from (left, top). Everything is computed in device
+    // space, so each pixel comes out exactly as on a canvas for the whole.
+    pub fn with_origin(bitmap: S, left: usize, top: usize) -> Canvas<S> {
+        let ctms  = vec![Matrix::identity()];
+        let clips = vec![None];
+        let row   = vec![Pixel::pack_argb(0, 0, 0, 0); bitmap.width()];
         Canvas {
             bitmap: bitmap,
             ctms:   ctms,
+            clips:  clips,
+            row:    row,
+            left:   left,
+            top:    top,
         }
     }
 
-    pub fn clear(&mut self, color: &Color) {
-        let srcpx = color.to_pixel();
+    fn shade_tri(&mut self, tri: &Triangle, shader: &mut dyn Shader) {
+        // Apply CTM, which under perspective may leave more than a triangle
+        let ctm = self.get_ctm();
+        let mapped = map_polygon(&ctm, &[tri.a, tri.b, tri.c]);
+        if !shader.set_context(&ctm) {
+            return;
+        }
 
-        let w = self.bitmap.width;
-        let h = self.bitmap.height;
+        for i in 1..mapped.len().saturating_sub(1) {
+            let piece = Triangle::new(mapped[0], mapped[i], mapped[i+1]);
+            self.fill_device_tri(&piece, &mut |x, y, row| shader.shade_row(x, y, row));
+        }
+    }
+
+    // Clips a device-space triangle and rasterizes what is left, with rows
+    // of source pixels from shade
+    fn fill_device_tri(&mut self, tri: &Triangle, shade: &mut ShadeFn) {
+        let clip = match self.clips[self.clips.len()-1] {
+            Some(ref clip) => clip.clone(),
+            None           => return self.raster_tri(tri, shade, blend_row),
+        };
 
-        for i in 0..w*h {
-            self.bitmap.pixels[i] = srcpx;
+        let clipped = clip_convex_polygon(&[tri.a, tri.b, tri.c], &clip);
+        for i in 1..clipped.len().saturating_sub(1) {
+            let piece = Triangle::new(clipped[0], clipped[i], clipped[i+1]);
+            self.raster_tri(&piece, shade, blend_row);
         }
     }
 
-    pub fn fill_bitmap_rect(&mut self, src: &Bitmap, dst: &Rect) {
-        let (w, h) = (self.bitmap.width, self.bitmap.height);
-        let mut roi = Rect::make_wh(w as f32, h as f32);
-        if !roi.intersect(&dst.round()) {
-            return;
+    // The pixels [x0, x1) x [y0, y1) a local rect covers, when the CTM only
+    // scales and translates and any clip is a device-space rect. These are
+    // exactly the pixels the two triangles of the rect would rasterize.
+    fn device_span_rect(&self, rect: &Rect) -> Option<(usize, usize, usize, usize)> {
+        let ctm = self.get_ctm();
+        if ctm.get_type() > MatrixType::ScaleTranslate {
+            return None;
         }
 
-        let srcrect = Rect::make_wh(src.width as f32, src.height as f32);
+        // Non-finite rects take the triangle path, which drops them
+        let mut bounds = ctm.map_rect(rect);
+        if ![bounds.left(), bounds.top(), bounds.right(), bounds.bottom()].iter().all(|v| v.is_finite()) {
+            return None;
+        }
+        if let Some(ref clip) = self.clips[self.clips.len()-1] {
+            if !bounds.intersect(&axis_aligned_rect(clip)?) {
+                return Some((0, 0, 0, 0));
+            }
+        }
+
+        // Same 28.4 rounding and pixel sampling as raster_tri, on coordinates
+        // first brought near the canvas so the fixed-point math can't overflow
+        let pixel = |v: f32, lo: usize, hi: usize| {
+            let v = clamp(lo as f32 - 1.0, v, hi as f32 + 1.0);
+            clamp(lo as f32, (((v * 16.0).round() as i64 + 0xf) >> 4) as f32, hi as f32) as usize
+        };
+        let (right, bottom) = (self.left + self.bitmap.width(), self.top + self.bitmap.height());
+        let (x0, x1) = (pixel(bounds.left(), self.left, right),  pixel(bounds.right(),  self.left, right));
+        let (y0, y1) = (pixel(bounds.top(),  self.top,  bottom), pixel(bounds.bottom(), self.top,  bottom));
+
+        Some((x0, y0, max(x0, x1), max(y0, y1)))
+    }
 
-        let r2r = map_rect_to_rect_mat(&srcrect, dst);
-        let r2r_floats = [
-            r2r.at(0), r2r.at(1), r2r.at(2),
-            r2r.at(3), r2r.at(4), r2r.at(5),
-        ];
+    // Shades a rect of device pixels a row at a time, with no edge tests
+    fn blit_rect(&mut self, x0: usize, y0: usize, x1: usize, y1: usize, shader: &dyn Shader) {
+        let count = x1 - x0;
+        let (left, top) = (self.left, self.top);
+
+        let solid  = shader.solid_pixel();
+        let offset = shader.offset_bitmap();
+
+        for y in y0..y1 {
+            let dst = &mut self.bitmap.row_mut(y - top)[x0 - left .. x1 - left];
+
+            // Opaque colors just overwrite
+            match solid {
+                Some(src) if src.a == 255 => {
+                    dst.fill(src);
+                    continue;
+                },
+                Some(src) => {
+                    blend_color_row(&src, dst);
+                    continue;
+                },
+                None => {},
+            }
 
-        let mut shader = Shaders::from_bitmap(src, r2r_floats);
+            // An unscaled bitmap row can be read in place, and copied outright
+            // where opaque. Rows that would clamp at the bitmap's edges are
+            // left to the shader.
+            if let Some((bitmap, dx, dy)) = offset {
+                let (sx, sy) = (x0 as i64 + dx, y as i64 + dy);
+                if sx >= 0 && sy >= 0 && sx + count as i64 <= bitmap.width as i64 && sy < bitmap.height as i64 {
+                    let start = sy as usize * bitmap.width + sx as usize;
+                    let src = &bitmap.pixels[start .. start + count];
+                    if src.iter().all(|px| px.a == 255) {
+                        dst.copy_from_slice(src);
+                    } else {
+                        blend_row(src, dst);
+                    }
+                    continue;
+                }
+            }
 
-        self.shade_rect(&roi, &mut shader);
+            let src = &mut self.row[..count];
+            shader.shade_row(x0, y, src);
+            blend_row(src, dst);
+        }
     }
 
-    pub fn fill_rect(&mut self, rect: &Rect, color: &Color) {
-        let mut color_shader = Shaders::from_color(*color);
-        self.shade_rect(rect, &mut color_shader);
+    // Draws a triangle mesh, each triple of indices naming one triangle, with
+    // the vertex colors blended across it
+    pub fn draw_vertices(&mut self, points: &[Point], colors: &[Color], indices: &[usize]) {
+        self.draw_mesh(points, Some(colors), None, indices);
     }
 
-    pub fn shade_rect<S: Shader>(&mut self, rect: &Rect, shader: &mut S) {
-        if !rect.empty() {
-            let (w, h) = (self.bitmap.width, self.bitmap.height);
+    // Like draw_vertices, but maps the bitmap across each triangle from the
+    // texture coordinates (in bitmap pixels) given per point. Vertex colors,
+    // if any, modulate the bitmap.
+    pub fn draw_textured_vertices(&mut self, points: &[Point], tex_coords: &[Point], bitmap: &Bitmap,
+                                  colors: Option<&[Color]>, indices: &[usize]) {
+        self.draw_mesh(points, colors, Some((bitmap, tex_coords)), indices);
+    }
 
-            // Clip rectangle with canvas
-            let mut roi = Rect::make_wh(w as f32, h as f32).round();
-            if !roi.intersect(&rect.round()) {
-                return;
+    fn draw_mesh(&mut self, points: &[Point], colors: Option<&[Color]>, texture: Option<(&Bitmap, &[Point])>,
+                 indices: &[usize]) {
+        if let Some(colors) = colors {
+            assert_eq!(points.len(), colors.len(), "need one color per point");
+        }
+        if let Some((_, tex_coords)) = texture {
+            assert_eq!(points.len(), tex_coords.len(), "need one texture coordinate per point");
+        }
+        assert!(indices.len() % 3 == 0, "indices must come in triples");
+
+        let ctm = self.get_ctm();
+        let device: Vec<Point> = points.iter().map(|p| ctm.apply(p)).collect();
+        let usable: Vec<bool> = points.iter().zip(&device)
+                                      .map(|(p, d)| ctm.apply_homogeneous(p)[2] > 0.0 && in_guard_band(d))
+                                      .collect();
+
+        for tri in indices.chunks(3) {
+            let (mut i, j, mut k) = (tri[0], tri[1], tri[2]);
+
+            // Interpolation is affine in device space, so there is no
+            // sensible way to draw triangles reaching behind the eye, or to
+            // clip ones reaching past the guard band
+            if !(usable[i] && usable[j] && usable[k]) {
+                continue;
             }
 
-            // Split into two triangles and draw each
-            let tri1 = Triangle {
-                // CW
-                a: Point::new(roi.left(),  roi.top()),
-                b: Point::new(roi.right(), roi.top()),
-                c: Point::new(roi.left(),  roi.bottom()),
-            };
-            let tri2 = Triangle {
-                // CW
-                a: Point::new(roi.right(), roi.top()),
-                b: Point::new(roi.right(), roi.bottom()),
-                c: Point::new(roi.left(),  roi.bottom()),
+            // The rasterizer wants CW, i.e. a positive cross product in y-down space
+            let (a, b, c) = (device[i], device[j], device[k]);
+            let cross = (b.x - a.x)*(c.y - a.y) - (b.y - a.y)*(c.x - a.x);
+            if cross < 0.0 {
+                std::mem::swap(&mut i, &mut k);
+            }
+
+            let tri = Triangle::new(device[i], device[j], device[k]);
+            let weights = match Barycentric::new(&tri) {
+                Some(weights) => weights,
+                None          => continue,
             };
-            self.shade_tri(&tri1, shader);
-            self.shade_tri(&tri2, shader);
+
+            let vertex_colors = colors.map(|colors| (colors[i], colors[j], colors[k]));
+            let texture = texture.map(|(bitmap, uvs)| (bitmap, (uvs[i], uvs[j], uvs[k])));
+
+            self.fill_device_tri(&tri, &mut |x, y, row| {
+                for (n, px) in row.iter_mut().enumerate() {
+                    let (wa, wb, wc) = weights.at(x + n, y);
+
+                    let color = vertex_colors.map(|(ca, cb, cc)| {
+                        Color::make_argb(wa*ca.a + wb*cb.a + wc*cc.a,
+                                         wa*ca.r + wb*cb.r + wc*cc.r,
+                                         wa*ca.g + wb*cb.g + wc*cc.g,
+                                         wa*ca.b + wb*cb.b + wc*cc.b)
+                    });
+
+                    let (bitmap, (ua, ub, uc)) = match texture {
+                        Some(texture) => texture,
+                        None          => {
+                            *px = color.unwrap().to_pixel();
+                            continue;
+                        },
+                    };
+                    let u = wa*ua.x + wb*ub.x + wc*uc.x;
+                    let v = wa*ua.y + wb*ub.y + wc*uc.y;
+                    let texel = bitmap.get(clamp(0.0, u, bitmap.width  as f32 - 1.0) as usize,
+                                           clamp(0.0, v, bitmap.height as f32 - 1.0) as usize);
+
+                    *px = match color {
+                        Some(color) => modulate(&texel, &color.to_pixel()),
+                        None        => texel,
+                    };
+                }
+            });
         }
     }
 
     // Courtesy of http://forum.devmaster.net/t/advanced-rasterization/6145
-    // TODO: Currently requires CW vertex ordering
-    fn shade_tri<S: Shader>(&mut self, tri: &Triangle, shader: &mut S) {
-        let (w,h) = (self.bitmap.width, self.bitmap.height);
-
-        // Apply CTM
-        let ctm = self.get_ctm();
-        let tri = Triangle {
-            a: ctm.apply(&tri.a),
-            b: ctm.apply(&tri.b),
-            c: ctm.apply(&tri.c),
+    fn raster_tri(&mut self, tri: &Triangle, shade: &mut ShadeFn, write: WriteFn) {
+        let (w,h) = (self.bitmap.width(), self.bitmap.height());
+        let (left, top) = (self.left, self.top);
+
+        // Mirroring transforms flip the winding, but the edge math wants CW
+        let (a, b, c) = (tri.a, tri.b, tri.c);
+        let tri = if (b.x - a.x)*(c.y - a.y) - (b.y - a.y)*(c.x - a.x) < 0.0 {
+            Triangle::new(a, c, b)
+        } else {
+            Triangle::new(a, b, c)
         };
-        shader.set_context(ctm.get_floats());
 
-        // Clip bounding box with canvas
+        // Reject triangles entirely off the canvas
         let bounds = tri.bounds();
-        let mut roi = Rect::make_wh(w as f32, h as f32).round();
+        let mut roi = Rect::make_xywh(left as f32, top as f32, w as f32, h as f32);
         if !roi.intersect(&bounds) {
             return;
         }
 
         // 28.4 fixed-point coordinates
-        let x1 = (tri.a.x * 16.0).round() as i32;
-        let x2 = (tri.b.x * 16.0).round() as i32;
-        let x3 = (tri.c.x * 16.0).round() as i32;
+        let x1 = (tri.a.x * 16.0).round() as i64;
+        let x2 = (tri.b.x * 16.0).round() as i64;
+        let x3 = (tri.c.x * 16.0).round() as i64;
 
-        let y1 = (tri.a.y * 16.0).round() as i32;
-        let y2 = (tri.b.y * 16.0).round() as i32;
-        let y3 = (tri.c.y * 16.0).round() as i32;
+        let y1 = (tri.a.y * 16.0).round() as i64;
+        let y2 = (tri.b.y * 16.0).round() as i64;
+        let y3 = (tri.c.y * 16.0).round() as i64;
 
         // Deltas
         let dx12 = x1-x2;
@@ -138,14 +296,17 @@ impl Canvas {
         let fdy31 = dy31 << 4;
 
         // Apply clipping
-        let mut xmin_i32 = (min(x1, min(x2, x3)) + 0xf) >> 4;
-        let mut xmax_i32 = (max(x1, max(x2, x3)) + 0xf) >> 4;
-        let mut ymin_i32 = (min(y1, min(y2, y3)) + 0xf) >> 4;
-        let mut ymax_i32 = (max(y1, max(y2, y3)) + 0xf) >> 4;
-        xmin_i32 = max(xmin_i32, roi.left()   as i32);
-        xmax_i32 = min(xmax_i32, roi.right()  as i32);
-        ymin_i32 = max(ymin_i32, roi.top()    as i32);
-        ymax_i32 = min(ymax_i32, roi.bottom() as i32);
+        let mut xmin_i64 = (min(x1, min(x2, x3)) + 0xf) >> 4;
+        let mut xmax_i64 = (max(x1, max(x2, x3)) + 0xf) >> 4;
+        let mut ymin_i64 = (min(y1, min(y2, y3)) + 0xf) >> 4;
+        let mut ymax_i64 = (max(y1, max(y2, y3)) + 0xf) >> 4;
+        xmin_i64 = max(xmin_i64, left as i64);
+        xmax_i64 = min(xmax_i64, (left + w) as i64);
+        ymin_i64 = max(ymin_i64, top as i64);
+        ymax_i64 = min(ymax_i64, (top + h) as i64);
+        if xmin_i64 >= xmax_i64 || ymin_i64 >= ymax_i64 {
+            return;
+        }
 
         // Half-edge constants
         let mut c1 = dy12*x1 - dx12*y1;
@@ -157,37 +318,35 @@ impl Canvas {
         if dy23 > 0 || (dy23 == 0 && dx23 < 0) {c2 -= 1;}
         if dy31 > 0 || (dy31 == 0 && dx31 < 0) {c3 -= 1;}
 
-        let mut cy1 = c1 + dx12*(ymin_i32 << 4) - dy12*(xmin_i32 << 4);
-        let mut cy2 = c2 + dx23*(ymin_i32 << 4) - dy23*(xmin_i32 << 4);
-        let mut cy3 = c3 + dx31*(ymin_i32 << 4) - dy31*(xmin_i32 << 4);
+        let mut cy1 = c1 + dx12*(ymin_i64 << 4) - dy12*(xmin_i64 << 4);
+        let mut cy2 = c2 + dx23*(ymin_i64 << 4) - dy23*(xmin_i64 << 4);
+        let mut cy3 = c3 + dx31*(ymin_i64 << 4) - dy31*(xmin_i64 << 4);
 
-        let xmin = xmin_i32 as usize;
-        let xmax = xmax_i32 as usize;
-        let ymin = ymin_i32 as usize;
-        let ymax = ymax_i32 as usize;
+        let xmin = xmin_i64 as usize;
+        let xmax = xmax_i64 as usize;
+        let ymin = ymin_i64 as usize;
+        let ymax = ymax_i64 as usize;
 
-        // Rasterize
+        // Rasterize a span per row. Along a row each edge function steps by
+        // -fdy per pixel, so the pixels where all three are negative (the
+        // inside test) can be solved for exactly instead of tested one by one.
         for y in ymin..ymax {
-            let mut cx1 = cy1;
-            let mut cx2 = cy2;
-            let mut cx3 = cy3;
-
-            let count = xmax - xmin;
-            let shaded_row = shader.shade_row(xmin, y, count);
-            let mut dst_row: Vec<Pixel> = Vec::with_capacity(count);
-            for i in 0..count {
-                dst_row.push(self.bitmap.get(xmin + i, y));
-            }
-            let blended_row = blend_row(&shaded_row, &dst_row);
-
-            for x in xmin..xmax {
-                if cx1 < 0 && cx2 < 0 && cx3 < 0 {
-                    self.bitmap.set(x, y, &blended_row[x-xmin]);
+            let (mut start, mut end) = (0i64, (xmax - xmin) as i64);
+            for &(cy, fdy) in [(cy1, fdy12), (cy2, fdy23), (cy3, fdy31)].iter() {
+                if fdy > 0 {
+                    start = max(start, cy.div_euclid(fdy) + 1);
+                } else if fdy < 0 {
+                    end = min(end, -cy.div_euclid(-fdy));
+                } else if cy >= 0 {
+                    end = 0;
                 }
+            }
 
-                cx1 -= fdy12;
-                cx2 -= fdy23;
-                cx3 -= fdy31;
+            if start < end {
+                let (x0, x1) = (xmin + start as usize, xmin + end as usize);
+                let shaded = &mut self.row[..x1 - x0];
+                shade(x0, y, shaded);
+                write(shaded, &mut self.bitmap.row_mut(y - top)[x0 - left .. x1 - left]);
             }
 
             cy1 += fdx12;
@@ -196,12 +355,72 @@ impl Canvas {
         }
     }
 
-    pub fn fill_convex_polygon(&mut self, points: &[Point], color: &Color) {
-        let mut color_shader = Shaders::from_color(*color);
-        self.shade_convex_polygon(points, &mut color_shader);
+    fn get_ctm(&self) -> Matrix {
+        self.ctms[self.ctms.len()-1]
     }
 
-    pub fn shade_convex_polygon<S: Shader>(&mut self, points: &[Point], shader: &mut S) {
+    pub fn bitmap(&self) -> &S {
+        &self.bitmap
+    }
+}
+
+impl Canvas<Bitmap> {
+    pub fn write(&self, path: &Path) {
+        self.bitmap.write(path);
+    }
+}
+
+impl<S: Surface> DrawContext for Canvas<S> {
+    fn clear(&mut self, color: &Color) {
+        let srcpx = color.to_pixel();
+
+        // Within a clip, its pixels are replaced as a fill would cover them
+        if let Some(clip) = self.clips[self.clips.len()-1].clone() {
+            for i in 1..clip.len().saturating_sub(1) {
+                let piece = Triangle::new(clip[0], clip[i], clip[i+1]);
+                self.raster_tri(&piece, &mut |_, _, row| row.fill(srcpx), |src, dst| dst.copy_from_slice(src));
+            }
+            return;
+        }
+
+        for y in 0..self.bitmap.height() {
+            for px in self.bitmap.row_mut(y) {
+                *px = srcpx;
+            }
+        }
+    }
+
+    fn shade_rect(&mut self, rect: &Rect, shader: &mut dyn Shader) {
+        // Clipping happens per triangle, once they are in device space
+        let roi = rect.round();
+        if !roi.empty() {
+            // Axis-aligned draws can skip the triangles and their edge tests
+            if let Some((x0, y0, x1, y1)) = self.device_span_rect(&roi) {
+                if shader.set_context(&self.get_ctm()) {
+                    self.blit_rect(x0, y0, x1, y1, shader);
+                }
+                return;
+            }
+
+            // Split into two triangles and draw each
+            let tri1 = Triangle {
+                // CW
+                a: Point::new(roi.left(),  roi.top()),
+                b: Point::new(roi.right(), roi.top()),
+                c: Point::new(roi.left(),  roi.bottom()),
+            };
+            let tri2 = Triangle {
+                // CW
+                a: Point::new(roi.right(), roi.top()),
+                b: Point::new(roi.right(), roi.bottom()),
+                c: Point::new(roi.left(),  roi.bottom()),
+            };
+            self.shade_tri(&tri1, shader);
+            self.shade_tri(&tri2, shader);
+        }
+    }
+
+    fn shade_convex_polygon(&mut self, points: &[Point], shader: &mut dyn Shader) {
         // Reject lines and points
         if points.len() <= 2 {
             return
@@ -220,55 +439,100 @@ impl Canvas {
         }
     }
 
-    fn get_ctm(&mut self) -> Matrix {
-        self.ctms[self.ctms.len()-1]
-    }
-
-    pub fn save(&mut self) {
-        let ctm = self.get_ctm();
+    fn save(&mut self) {
+        let ctm  = self.get_ctm();
+        let clip = self.clips[self.clips.len()-1].clone();
         self.ctms.push(ctm);
+        self.clips.push(clip);
     }
 
-    pub fn restore(&mut self) {
+    fn restore(&mut self) {
         self.ctms.pop().unwrap();
+        self.clips.pop().unwrap();
     }
 
-    pub fn concat(&mut self, mat: [f32; 6]) {
+    fn concat_matrix(&mut self, mat: &Matrix) {
         let ctm = self.get_ctm();
         let len = self.ctms.len();
-        self.ctms[len-1] = ctm.mul(&Matrix::new(mat));
+        self.ctms[len-1] = ctm.mul(mat);
     }
 
-    pub fn scale(&mut self, sx: f32, sy: f32) {
-        self.concat([
-             sx, 0.0, 0.0,
-            0.0,  sy, 0.0,
+    fn clip_rect(&mut self, rect: &Rect) {
+        let ctm = self.get_ctm();
+        let corners = map_polygon(&ctm, &[
+            Point::new(rect.left(),  rect.top()),
+            Point::new(rect.right(), rect.top()),
+            Point::new(rect.right(), rect.bottom()),
+            Point::new(rect.left(),  rect.bottom()),
         ]);
-    }
 
-    pub fn translate(&mut self, tx: f32, ty: f32) {
-        self.concat([
-            1.0, 0.0, tx,
-            0.0, 1.0, ty,
-        ]);
+        let len = self.clips.len();
+        let clip = match self.clips[len-1] {
+            Some(ref clip) => clip_convex_polygon(&corners, clip),
+            None           => corners,
+        };
+        self.clips[len-1] = Some(clip);
     }
+}
 
-    pub fn rotate(&mut self, radians: f32) {
-        let c = radians.cos();
-        let s = radians.sin();
-        self.concat([
-            c, -s, 0.0,
-            s,  c, 0.0,
-        ]);
+// The rect a polygon outlines, if it is an axis-aligned one
+fn axis_aligned_rect(points: &[Point]) -> Option<Rect> {
+    if points.len() != 4 {
+        return None;
+    }
+    for i in 0..4 {
+        let (p, q) = (points[i], points[(i + 1) % 4]);
+        if (p.x == q.x) == (p.y == q.y) {
+            return None;
+        }
     }
 
-    pub fn rotate_about(&mut self, radians: f32, point: &Point) {
-        self.translate(-point.x, -point.y);
-        self.rotate(radians);
-        self.translate(point.x, point.y);
+    let (p, q) = (points[0], points[2]);
+    Some(Rect::make_ltrb(p.x.min(q.x), p.y.min(q.y), p.x.max(q.x), p.y.max(q.y)))
+}
+
+// Barycentric weights of a device-space triangle, from the same 28.4
+// fixed-point edge functions raster_tri uses, taken at pixel centers like
+// the shaders sample. Pixels along the edges may be slightly outside.
+struct Barycentric {
+    edges: [(i64, i64, i64); 3],  // Constant, x and y steps of each edge
+    area:  f32,                   // Sum of the three edge functions
+}
+
+impl Barycentric {
+    fn new(tri: &Triangle) -> Option<Barycentric> {
+        let fixed = |p: &Point| ((p.x * 16.0).round() as i64, (p.y * 16.0).round() as i64);
+        let (a, b, c) = (fixed(&tri.a), fixed(&tri.b), fixed(&tri.c));
+
+        // Edge opposite each vertex, so its function weighs that vertex
+        let edge = |p: (i64, i64), q: (i64, i64)| {
+            let (dx, dy) = (p.0 - q.0, p.1 - q.1);
+            (dy*p.0 - dx*p.1, -dy << 4, dx << 4)
+        };
+        let edges = [edge(b, c), edge(c, a), edge(a, b)];
+
+        let area = (edges[0].0 + edges[1].0 + edges[2].0) as f32;
+        if area == 0.0 {
+            return None;
+        }
+
+        Some(Barycentric {
+            edges: edges,
+            area:  area,
+        })
     }
 
-    pub fn write(&self, path: &Path) {
-        self.bitmap.write(path);
+    fn at(&self, x: usize, y: usize) -> (f32, f32, f32) {
+        // The steps are per pixel, so half of one reaches the center
+        let (x, y) = (x as i64, y as i64);
+        let weight = |e: &(i64, i64, i64)| (e.0 + e.1*x + e.2*y + (e.1 + e.2)/2) as f32 / self.area;
+
+        (weight(&self.edges[0]), weight(&self.edges[1]), weight(&self.edges[2]))
     }
 }
+
+// Componentwise product of two premultiplied pixels
+fn modulate(p: &Pixel, q: &Pixel) -> Pixel {
+    let mul = |a: u8, b: u8| ((a as u32 * b as u32 + 127) / 255) as u8;
+    Pixel::pack_argb(mul(p.a, q.a), mul(p.r, q.r), mul(p.g, q.g), mul(p.b, q.b))
+}
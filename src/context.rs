@@ -0,0 +1,76 @@
+use bitmap::Bitmap;
+use color::Color;
+use rect::Rect;
+use point::Point;
+use matrix::Matrix;
+use shader::{Shader, Shaders};
+use util::map_rect_to_rect_mat;
+
+// The drawing API shared by every backend (raster, vector, recorders, ...)
+pub trait DrawContext {
+    // Replaces everything inside the clip with the color, without blending
+    fn clear(&mut self, color: &Color);
+
+    fn shade_rect(&mut self, rect: &Rect, shader: &mut dyn Shader);
+    fn shade_convex_polygon(&mut self, points: &[Point], shader: &mut dyn Shader);
+
+    fn fill_rect(&mut self, rect: &Rect, color: &Color) {
+        let mut color_shader = Shaders::from_color(*color);
+        self.shade_rect(rect, &mut color_shader);
+    }
+
+    fn fill_convex_polygon(&mut self, points: &[Point], color: &Color) {
+        let mut color_shader = Shaders::from_color(*color);
+        self.shade_convex_polygon(points, &mut color_shader);
+    }
+
+    fn fill_bitmap_rect(&mut self, src: &Bitmap, dst: &Rect) {
+        let srcrect = Rect::make_wh(src.width as f32, src.height as f32);
+
+        let r2r = map_rect_to_rect_mat(&srcrect, dst);
+        let mut shader = Shaders::from_bitmap(src, r2r.get_floats());
+
+        self.shade_rect(dst, &mut shader);
+    }
+
+    fn save(&mut self);
+    fn restore(&mut self);
+    fn concat(&mut self, mat: [f32; 6]) {
+        self.concat_matrix(&Matrix::new(mat));
+    }
+
+    // Like concat, but takes a full 3x3 matrix that may add perspective
+    fn concat_matrix(&mut self, mat: &Matrix);
+
+    // Intersects the current clip with the rect, mapped by the CTM
+    fn clip_rect(&mut self, rect: &Rect);
+
+    fn scale(&mut self, sx: f32, sy: f32) {
+        self.concat([
+             sx, 0.0, 0.0,
+            0.0,  sy, 0.0,
+        ]);
+    }
+
+    fn translate(&mut self, tx: f32, ty: f32) {
+        self.concat([
+            1.0, 0.0, tx,
+            0.0, 1.0, ty,
+        ]);
+    }
+
+    fn rotate(&mut self, radians: f32) {
+        let c = radians.cos();
+        let s = radians.sin();
+        self.concat([
+            c, -s, 0.0,
+            s,  c, 0.0,
+        ]);
+    }
+
+    fn rotate_about(&mut self, radians: f32, point: &Point) {
+        self.translate(-point.x, -point.y);
+        self.rotate(radians);
+        self.translate(point.x, point.y);
+    }
+}
@@ -0,0 +1,39 @@
+use std::path::Path;
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum ImageFormat {
+    Ppm,
+    Bmp,
+    Qoi,
+    Tga,
+    Png,
+}
+
+impl ImageFormat {
+    pub fn from_extension(ext: &str) -> Option<ImageFormat> {
+        match ext.to_lowercase().as_str() {
+            "ppm" => Some(ImageFormat::Ppm),
+            "bmp" => Some(ImageFormat::Bmp),
+            "qoi" => Some(ImageFormat::Qoi),
+            "tga" => Some(ImageFormat::Tga),
+            "png" => Some(ImageFormat::Png),
+            _     => None,
+        }
+    }
+
+    pub fn from_path(path: &Path) -> Option<ImageFormat> {
+        path.extension()
+            .and_then(|ext| ext.to_str())
+            .and_then(ImageFormat::from_extension)
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match *self {
+            ImageFormat::Ppm => "ppm",
+            ImageFormat::Bmp => "bmp",
+            ImageFormat::Qoi => "qoi",
+            ImageFormat::Tga => "tga",
+            ImageFormat::Png => "png",
+        }
+    }
+}
@@ -0,0 +1,331 @@
+use bitmap::Bitmap;
+use canvas::Canvas;
+use color::Color;
+use pixel::Pixel;
+use rect::Rect;
+use point::Point;
+use context::DrawContext;
+use ppm;
+
+// The built-in test images, drawn through DrawContext so any backend can
+// reproduce them
+pub struct Image {
+    pub name:   &'static str,
+    pub width:  usize,
+    pub height: usize,
+    pub draw:   fn(&mut dyn DrawContext),
+}
+
+impl Image {
+    pub fn render(&self) -> Bitmap {
+        let mut canvas = Canvas::new(Bitmap::new(self.width, self.height));
+        (self.draw)(&mut canvas);
+        canvas.bitmap().clone()
+    }
+}
+
+pub static IMAGES: [Image; 13] = [
+    Image { name: "solid_ramp",     width: 256, height: 196, draw: draw_solid_ramp     },
+    Image { name: "blend_black",    width: 200, height: 200, draw: draw_blend_black    },
+    Image { name: "blend_white",    width: 200, height: 200, draw: draw_blend_white    },
+    Image { name: "spocks_quad",    width: 300, height: 300, draw: draw_spocks_quad    },
+    Image { name: "spocks_zoom",    width: 300, height: 300, draw: draw_spocks_zoom    },
+    Image { name: "circles_blend",  width: 300, height: 300, draw: draw_bm_circles     },
+    Image { name: "circles_fat",    width: 400, height: 300, draw: draw_circle_big     },
+    Image { name: "tri",            width: 256, height: 256, draw: draw_tri            },
+    Image { name: "tri_clipped",    width: 256, height: 256, draw: draw_tri_clipped    },
+    Image { name: "poly",           width: 512, height: 512, draw: draw_poly           },
+    Image { name: "poly_center",    width: 256, height: 256, draw: draw_poly_center    },
+    Image { name: "poly_rotate",    width: 230, height: 230, draw: draw_poly_rotate    },
+    Image { name: "translate_rect", width: 100, height: 100, draw: draw_translate_rect },
+];
+
+pub fn find(name: &str) -> Option<&'static Image> {
+    IMAGES.iter().find(|image| image.name == name)
+}
+
+// Same pixels Bitmap::read gives for spock.ppm, which round-trips through Color
+fn spock() -> Bitmap {
+    let mut bitmap = ppm::decode(include_bytes!("../spock.ppm")).unwrap();
+    for px in bitmap.pixels.iter_mut() {
+        *px = px.to_color().to_pixel();
+    }
+    bitmap
+}
+
+fn draw_solid_ramp(canvas: &mut dyn DrawContext) {
+    let (ramp_w, ramp_h) = (1, 28);
+
+    let c = 1.0/512.0;
+    let d = 1.0/256.0;
+
+    let recs = [
+        (Color::make_argb(1.0,     c,     c,     c), Color::make_argb(0.0,   d,   d,   d)),  // Grey
+        (Color::make_argb(1.0, 1.0-c,   0.0,   0.0), Color::make_argb(0.0,  -d, 0.0, 0.0)),  // Red
+        (Color::make_argb(1.0,   0.0,     c,     c), Color::make_argb(0.0, 0.0,   d,   d)),  // Cyan
+        (Color::make_argb(1.0,   0.0, 1.0-c,   0.0), Color::make_argb(0.0, 0.0,  -d, 0.0)),  // Green
+        (Color::make_argb(1.0,     c,   0.0,     c), Color::make_argb(0.0,   d, 0.0,   d)),  // Magenta
+        (Color::make_argb(1.0,   0.0,   0.0, 1.0-c), Color::make_argb(0.0, 0.0, 0.0,  -d)),  // Blue
+        (Color::make_argb(1.0,     c,     c,   0.0), Color::make_argb(0.0,   d,   d, 0.0)),  // Yellow
+    ];
+
+    for y in 0..recs.len() {
+        let (mut color, delta) = recs[y];
+        for x in 0..256 {
+            let rect = Rect::make_xywh((x*ramp_w) as f32, (y*ramp_h) as f32, ramp_w as f32, ramp_h as f32);
+
+            canvas.fill_rect(&rect, &color);
+
+            color.a += delta.a;
+            color.r += delta.r;
+            color.g += delta.g;
+            color.b += delta.b;
+        }
+    }
+}
+
+fn draw_blend_ramp(canvas: &mut dyn DrawContext, bg: &Color) {
+    canvas.clear(bg);
+
+    let mut rect = Rect::make_xywh(-25.0, -25.0, 70.0, 70.0); 
+
+    let delta = 8.0;
+    let mut i = 0;
+    while i < 200 {
+        let j = i as f32;
+
+        let r = j / 200.0;
+        let g = (j / 40.0).cos().abs();
+        let b = (j / 50.0).sin().abs();
+
+        let color = Color::make_argb(0.3, r, g, b);
+
+        canvas.fill_rect(&rect, &color);
+
+        rect.offset(delta, delta);
+
+        i += delta as i32;
+    }
+}
+
+fn draw_blend_black(canvas: &mut dyn DrawContext) {
+    draw_blend_ramp(canvas, &Color::black());
+}
+
+fn draw_blend_white(canvas: &mut dyn DrawContext) {
+    draw_blend_ramp(canvas, &Color::white());
+}
+
+fn draw_spocks_quad(canvas: &mut dyn DrawContext) {
+    let n = 300.0;
+
+    let bitmap = spock();
+
+    for y in 0..2 {
+        for x in 0..2 {
+            let (xf, yf) = (x as f32, y as f32);
+            let rect = Rect::make_xywh(xf*n - n/2.0, yf*n - n/2.0, n, n);
+            canvas.fill_bitmap_rect(&bitmap, &rect);
+        }
+    }
+}
+
+fn draw_spocks_zoom(canvas: &mut dyn DrawContext) {
+    let n = 300.0;
+
+    let bitmap = spock();
+
+    for i in 0..9 {
+        let f = i as f32;
+        let r = Rect::make_ltrb(f*10.0, f*10.0, n - f*10.0, n - f*10.0);
+        canvas.fill_bitmap_rect(&bitmap, &r);
+    }
+}
+
+fn make_circle(bitmap: &mut Bitmap, color: &Color) {
+    let px = color.pin_to_unit().to_pixel();
+
+    let (cx, cy) = (bitmap.width as f32 / 2.0, bitmap.height as f32 / 2.0);
+
+    let r  = cx - 1.0;
+    let r2 = r*r;
+
+    for y in 0..bitmap.height {
+        let dy = y as f32 - cy;
+        for x in 0..bitmap.width {
+            let dx = x as f32 - cx;
+            let d2 = dx*dx + dy*dy;
+            if d2 <= r2 {
+                bitmap.set(x, y, &px);
+            } else {
+                bitmap.set(x, y, &Pixel::pack_argb(0,0,0,0));
+            }
+        }
+    }
+}
+
+fn draw_bm_circles(canvas: &mut dyn DrawContext) {
+    let n = 300.0;
+
+    let mut bitmap = Bitmap::new(n as usize, n as usize);
+
+    let recs = [
+        (Rect::make_xywh(0.0, 0.0, n, n), Color::make_argb(1.0, 1.0, 1.0, 1.0)),
+
+        (Rect::make_xywh(  0.0,   0.0, n/2.0, n/2.0), Color::make_argb(0.8, 0.0, 0.0, 1.0)),
+        (Rect::make_xywh(n/2.0,   0.0, n/2.0, n/2.0), Color::make_argb(0.6, 0.0, 1.0, 0.0)),
+        (Rect::make_xywh(  0.0, n/2.0, n/2.0, n/2.0), Color::make_argb(0.4, 1.0, 0.0, 0.0)),
+        (Rect::make_xywh(n/2.0, n/2.0, n/2.0, n/2.0), Color::make_argb(0.2, 0.0, 0.0, 0.0)),
+
+        (Rect::make_xywh(  0.0, n/3.0,     n, n/3.0), Color::make_argb(0.5, 1.0, 1.0, 0.0)),
+        (Rect::make_xywh(n/3.0,   0.0, n/3.0,     n), Color::make_argb(0.5, 0.0, 1.0, 1.0)),
+        (Rect::make_xywh(n/3.0, n/3.0, n/3.0, n/3.0), Color::make_argb(0.5, 1.0, 0.0, 1.0)),
+    ];
+
+    for i in 0..recs.len() {
+        let rect  = &recs[i].0;
+        let color = &recs[i].1;
+
+        make_circle(&mut bitmap, color);
+
+        canvas.fill_bitmap_rect(&bitmap, rect);
+    }
+}
+
+fn draw_circle_big(canvas: &mut dyn DrawContext) {
+    let n = 300.0;
+    let a = 0.4;
+
+    let colors = [
+        Color::make_argb(a, 1.0, 0.0, 0.0),
+        Color::make_argb(a, 0.0, 1.0, 0.0),
+        Color::make_argb(a, 0.0, 0.0, 1.0),
+    ];
+
+    let mut x = 0;
+    let mut m = n as i32;
+    let mut i = 0;
+    while m > 4 {
+        let mut bitmap = Bitmap::new(m as usize, m as usize);
+        make_circle(&mut bitmap, &colors[i % colors.len()]);
+
+        canvas.fill_bitmap_rect(&bitmap, &Rect::make_xywh(x as f32, 0.0, n, n));
+
+        x += n as i32 / 12;
+        m >>= 1;
+        i += 1;
+    }
+}
+
+fn draw_tri(canvas: &mut dyn DrawContext) {
+    let points = [
+        Point::new( 10.0,  10.0),
+        Point::new(200.0,  50.0),
+        Point::new(100.0, 200.0),
+    ];
+
+    canvas.fill_convex_polygon(&points, &Color::green());
+}
+
+fn draw_tri_clipped(canvas: &mut dyn DrawContext) {
+    let points = [
+        Point::new(-10.0, -10.0),
+        Point::new(300.0,  50.0),
+        Point::new(100.0, 300.0),
+    ];
+
+    canvas.fill_convex_polygon(&points, &Color::yellow());
+}
+
+fn make_regular_poly(points: &mut[Point], count: usize, cx: f32, cy: f32, radius: f32) {
+    let mut angle = 0f32;
+    let delta_angle = std::f32::consts::PI*2.0 / count as f32;
+
+    for i in 0..count {
+        points[i] = Point::new(cx + angle.cos()*radius, cy + angle.sin()*radius);
+        angle += delta_angle;
+    }
+}
+
+fn dr_poly(canvas: &mut dyn DrawContext, dx: f32, dy: f32) {
+    let mut points = [Point::new(0.0, 0.0); 12];
+    for count in (3..13).rev() {
+        make_regular_poly(&mut points, count, 256.0, 256.0, (count*10 + 120) as f32);
+
+        for i in 0..count {
+            points[i].x += dx;
+            points[i].y += dy;
+        }
+
+        let color = Color::make_argb(0.8,
+                                     (count as f32 *  7.0).sin().abs(),
+                                     (count as f32 * 11.0).sin().abs(),
+                                     (count as f32 * 17.0).sin().abs());
+
+        canvas.fill_convex_polygon(&points[0..count], &color);
+    }
+}
+
+fn draw_poly(canvas: &mut dyn DrawContext) {
+    dr_poly(canvas, 0.0, 0.0);
+}
+
+fn draw_poly_center(canvas: &mut dyn DrawContext) {
+    dr_poly(canvas, -128.0, -128.0);
+}
+
+fn scale(vec: &Point, size: f32) -> Point {
+    let scale = size / (vec.x*vec.x + vec.y*vec.y).sqrt();
+    Point::new(vec.x*scale, vec.y*scale)
+}
+
+fn draw_line(canvas: &mut dyn DrawContext, a: &Point, b: &Point, width: f32, color: &Color) {
+    let norm = scale(&Point::new(b.y-a.y, b.x-a.x), width/2.0);
+
+    let points = [
+        Point::new(a.x - norm.x, a.y - norm.y),
+        Point::new(b.x - norm.x, b.y - norm.y),
+        Point::new(b.x + norm.x, b.y + norm.y),
+        Point::new(a.x + norm.x, a.y + norm.y),
+    ];
+
+    canvas.fill_convex_polygon(&points, color);
+}
+
+fn draw_poly_rotate(canvas: &mut dyn DrawContext) {
+    let start = Point::new(20.0, 20.0);
+    let scale = 200f32;
+
+    let n = 10.0;
+    let mut color = Color::red();
+    let delta_r = -1.0/n;
+    let delta_b =  1.0/n;
+
+    let width = 10.0;
+
+    let mut angle = 0.0;
+    while angle <= std::f32::consts::PI / 2.0 {
+        let end = Point::new(start.x + angle.cos()*scale, start.y + angle.sin()*scale);
+
+        draw_line(canvas, &start, &end, width, &color);
+
+        color.r += delta_r;
+        color.b += delta_b;
+
+        angle += std::f32::consts::PI / 2.0 / n;
+    }
+}
+
+fn draw_translate_rect(canvas: &mut dyn DrawContext) {
+    canvas.clear(&Color::white());
+
+    canvas.translate(25.0, 25.0);
+
+    let angle = std::f32::consts::PI / 8.0;
+
+    for _ in 0..4 {
+        canvas.rotate_about(angle, &Point::new(50.0, 50.0));
+
+        canvas.fill_rect(&Rect::make_wh(50.0, 50.0), &Color::make_argb(0.5, 1.0, 0.0, 0.0));
+    }
+}
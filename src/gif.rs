@@ -0,0 +1,409 @@
+use bitmap::Bitmap;
+use pixel::Pixel;
+
+use std::path::Path;
+use std::fs::File;
+use std::io;
+use std::io::prelude::*;
+use std::io::BufWriter;
+use std::collections::HashMap;
+
+const MAX_COLORS: usize = 256;
+const MAX_CODE_SIZE: u32 = 12;
+
+// Pixels with less coverage than this become fully transparent
+const ALPHA_THRESHOLD: u8 = 128;
+
+pub struct Frame {
+    pub bitmap: Bitmap,
+    pub delay:  u16,  // Hundredths of a second
+}
+
+impl Frame {
+    pub fn new(bitmap: Bitmap, delay: u16) -> Frame {
+        Frame {
+            bitmap: bitmap,
+            delay:  delay,
+        }
+    }
+}
+
+pub struct Options {
+    pub dither: bool,
+    pub repeat: u16,  // Zero loops forever
+}
+
+impl Options {
+    pub fn new() -> Options {
+        Options {
+            dither: false,
+            repeat: 0,
+        }
+    }
+}
+
+impl Default for Options {
+    fn default() -> Options {
+        Options::new()
+    }
+}
+
+pub fn write(frames: &[Frame], options: &Options, path: &Path) -> io::Result<()> {
+    let mut file = BufWriter::new(File::create(path)?);
+    file.write_all(&encode(frames, options)?)?;
+    file.flush()
+}
+
+// All frames must match the size of the first one. Each frame gets its own
+// median-cut palette, with one entry reserved for transparency if needed.
+pub fn encode(frames: &[Frame], options: &Options) -> io::Result<Vec<u8>> {
+    if frames.is_empty() {
+        return Err(invalid_input("no frames to encode"));
+    }
+
+    let (w, h) = (frames[0].bitmap.width, frames[0].bitmap.height);
+    if w > 0xffff || h > 0xffff {
+        return Err(invalid_input("bitmap too large for GIF"));
+    }
+    if frames.iter().any(|f| f.bitmap.width != w || f.bitmap.height != h) {
+        return Err(invalid_input("frames differ in size"));
+    }
+
+    let mut out = Vec::new();
+
+    // Header and logical screen descriptor, without a global color table
+    out.extend_from_slice(b"GIF89a");
+    put_u16(&mut out, w as u16);
+    put_u16(&mut out, h as u16);
+    out.extend_from_slice(&[0, 0, 0]);
+
+    // NETSCAPE2.0 looping extension
+    if frames.len() > 1 {
+        out.extend_from_slice(&[0x21, 0xff, 11]);
+        out.extend_from_slice(b"NETSCAPE2.0");
+        out.extend_from_slice(&[3, 1]);
+        put_u16(&mut out, options.repeat);
+        out.push(0);
+    }
+
+    let transparent: Vec<bool> = frames.iter()
+                                       .map(|f| f.bitmap.pixels.iter().any(|px| px.a < ALPHA_THRESHOLD))
+                                       .collect();
+
+    for (i, frame) in frames.iter().enumerate() {
+        let has_transparency = transparent[i];
+
+        // A frame is cleared to the background when the one after it (the
+        // first one, on looping) has holes its predecessor must not show through
+        let dispose = if transparent[(i + 1) % frames.len()] {2} else {1};
+
+        let max_colors = if has_transparency {MAX_COLORS - 1} else {MAX_COLORS};
+        let palette = median_cut(&frame.bitmap, max_colors);
+        let indices = map_to_palette(&frame.bitmap, &palette, options.dither);
+
+        // Color tables must have a power-of-two size, with at least two entries
+        let used = palette.len() + has_transparency as usize;
+        let mut bits = 1;
+        while (1 << bits) < used {
+            bits += 1;
+        }
+        let transparent_index = palette.len() as u8;
+
+        // Graphic control extension
+        out.extend_from_slice(&[0x21, 0xf9, 4]);
+        out.push(dispose << 2 | has_transparency as u8);
+        put_u16(&mut out, frame.delay);
+        out.push(if has_transparency {transparent_index} else {0});
+        out.push(0);
+
+        // Image descriptor, with a local color table
+        out.push(0x2c);
+        put_u16(&mut out, 0);
+        put_u16(&mut out, 0);
+        put_u16(&mut out, w as u16);
+        put_u16(&mut out, h as u16);
+        out.push(0x80 | (bits - 1) as u8);
+
+        for i in 0..1 << bits {
+            let rgb = palette.get(i).cloned().unwrap_or([0, 0, 0]);
+            out.extend_from_slice(&rgb);
+        }
+
+        let indices: Vec<u8> = indices.iter()
+                                      .map(|i| i.unwrap_or(transparent_index))
+                                      .collect();
+
+        // Image data, in sub-blocks of at most 255 bytes
+        let min_code_size = bits.max(2);
+        out.push(min_code_size as u8);
+        for block in lzw_encode(&indices, min_code_size).chunks(255) {
+            out.push(block.len() as u8);
+            out.extend_from_slice(block);
+        }
+        out.push(0);
+    }
+
+    out.push(0x3b);
+
+    Ok(out)
+}
+
+// A box of histogram entries, split along its widest channel
+struct ColorBox {
+    colors: Vec<([u8; 3], u32)>,
+}
+
+impl ColorBox {
+    fn widest_channel(&self) -> (usize, u8) {
+        let mut widest = (0, 0);
+        for c in 0..3 {
+            let lo = self.colors.iter().map(|e| e.0[c]).min().unwrap();
+            let hi = self.colors.iter().map(|e| e.0[c]).max().unwrap();
+            if hi - lo > widest.1 {
+                widest = (c, hi - lo);
+            }
+        }
+        widest
+    }
+
+    fn average(&self) -> [u8; 3] {
+        let mut sum = [0u64; 3];
+        let mut total = 0u64;
+        for &(rgb, count) in &self.colors {
+            for c in 0..3 {
+                sum[c] += rgb[c] as u64 * count as u64;
+            }
+            total += count as u64;
+        }
+
+        let avg = |c: usize| ((sum[c] + total/2) / total) as u8;
+        [avg(0), avg(1), avg(2)]
+    }
+}
+
+fn median_cut(bitmap: &Bitmap, max_colors: usize) -> Vec<[u8; 3]> {
+    let mut histogram: HashMap<[u8; 3], u32> = HashMap::new();
+    for px in bitmap.pixels.iter().filter(|px| px.a >= ALPHA_THRESHOLD) {
+        let (_, r, g, b) = px.unpremul_argb();
+        *histogram.entry([r, g, b]).or_insert(0) += 1;
+    }
+
+    if histogram.is_empty() {
+        return Vec::new();
+    }
+
+    let mut colors: Vec<_> = histogram.into_iter().collect();
+    colors.sort();
+
+    let mut boxes = vec![ColorBox { colors: colors }];
+    while boxes.len() < max_colors {
+        // Split the box spanning the widest range of any channel
+        let (i, (channel, range)) = boxes.iter()
+                                         .map(|b| b.widest_channel())
+                                         .enumerate()
+                                         .max_by_key(|&(_, (_, range))| range)
+                                         .unwrap();
+        if range == 0 {
+            break;
+        }
+
+        let mut colors = boxes.swap_remove(i).colors;
+        colors.sort_by_key(|e| e.0[channel]);
+
+        // Split at the pixel-weighted median, keeping both halves non-empty
+        let total: u64 = colors.iter().map(|e| e.1 as u64).sum();
+        let mut seen = 0;
+        let mut split = 1;
+        for (j, e) in colors.iter().enumerate() {
+            seen += e.1 as u64;
+            if seen*2 >= total {
+                split = (j + 1).clamp(1, colors.len() - 1);
+                break;
+            }
+        }
+
+        let upper = colors.split_off(split);
+        boxes.push(ColorBox { colors: colors });
+        boxes.push(ColorBox { colors: upper });
+    }
+
+    boxes.iter().map(|b| b.average()).collect()
+}
+
+fn nearest(palette: &[[u8; 3]], rgb: [i32; 3]) -> usize {
+    let mut best = (0, i32::MAX);
+    for (i, entry) in palette.iter().enumerate() {
+        let dr = entry[0] as i32 - rgb[0];
+        let dg = entry[1] as i32 - rgb[1];
+        let db = entry[2] as i32 - rgb[2];
+        let dist = dr*dr + dg*dg + db*db;
+        if dist < best.1 {
+            best = (i, dist);
+        }
+    }
+    best.0
+}
+
+// None marks a transparent pixel
+fn map_to_palette(bitmap: &Bitmap, palette: &[[u8; 3]], dither: bool) -> Vec<Option<u8>> {
+    let (w, h) = (bitmap.width, bitmap.height);
+
+    let rgb = |px: &Pixel| -> [i32; 3] {
+        let (_, r, g, b) = px.unpremul_argb();
+        [r as i32, g as i32, b as i32]
+    };
+
+    let mut cache: HashMap<[i32; 3], usize> = HashMap::new();
+    let mut lookup = |color: [i32; 3]| -> usize {
+        *cache.entry(color).or_insert_with(|| nearest(palette, color))
+    };
+
+    if !dither {
+        return bitmap.pixels.iter().map(|px| {
+            if px.a < ALPHA_THRESHOLD {
+                None
+            } else {
+                Some(lookup(rgb(px)) as u8)
+            }
+        }).collect();
+    }
+
+    // Floyd-Steinberg, diffusing error in 1/16ths
+    let mut work: Vec<[i32; 3]> = bitmap.pixels.iter().map(rgb).collect();
+    let mut indices = vec![None; w*h];
+
+    for y in 0..h {
+        for x in 0..w {
+            let i = x + y*w;
+            if bitmap.pixels[i].a < ALPHA_THRESHOLD {
+                continue;
+            }
+
+            let color = [
+                work[i][0].clamp(0, 255),
+                work[i][1].clamp(0, 255),
+                work[i][2].clamp(0, 255),
+            ];
+            let index = lookup(color);
+            indices[i] = Some(index as u8);
+
+            let err = [
+                color[0] - palette[index][0] as i32,
+                color[1] - palette[index][1] as i32,
+                color[2] - palette[index][2] as i32,
+            ];
+
+            let mut diffuse = |dx: isize, dy: usize, weight: i32| {
+                let nx = x as isize + dx;
+                if nx < 0 || nx >= w as isize || y + dy >= h {
+                    return;
+                }
+                let j = nx as usize + (y + dy)*w;
+                for c in 0..3 {
+                    work[j][c] += err[c] * weight / 16;
+                }
+            };
+            diffuse( 1, 0, 7);
+            diffuse(-1, 1, 3);
+            diffuse( 0, 1, 5);
+            diffuse( 1, 1, 1);
+        }
+    }
+
+    indices
+}
+
+// Variable-width LZW as described in the GIF89a spec, packed LSB-first
+fn lzw_encode(indices: &[u8], min_code_size: u32) -> Vec<u8> {
+    let clear = 1u16 << min_code_size;
+    let eoi   = clear + 1;
+
+    let mut writer = BitWriter::new();
+    let mut table: HashMap<(u16, u8), u16> = HashMap::new();
+    let mut code_size = min_code_size + 1;
+    let mut next_code = eoi + 1;
+
+    writer.write(clear, code_size);
+
+    let mut iter = indices.iter();
+    let mut prefix = match iter.next() {
+        Some(&i) => i as u16,
+        None     => {
+            writer.write(eoi, code_size);
+            return writer.finish();
+        },
+    };
+
+    for &index in iter {
+        if let Some(&code) = table.get(&(prefix, index)) {
+            prefix = code;
+            continue;
+        }
+
+        writer.write(prefix, code_size);
+
+        if next_code < 4095 {
+            table.insert((prefix, index), next_code);
+            next_code += 1;
+            if next_code > (1 << code_size) && code_size < MAX_CODE_SIZE {
+                code_size += 1;
+            }
+        } else {
+            writer.write(clear, code_size);
+            table.clear();
+            code_size = min_code_size + 1;
+            next_code = eoi + 1;
+        }
+
+        prefix = index as u16;
+    }
+
+    writer.write(prefix, code_size);
+    if next_code >= (1 << code_size) && code_size < MAX_CODE_SIZE {
+        code_size += 1;
+    }
+    writer.write(eoi, code_size);
+
+    writer.finish()
+}
+
+struct BitWriter {
+    bytes: Vec<u8>,
+    acc:   u32,
+    bits:  u32,
+}
+
+impl BitWriter {
+    fn new() -> BitWriter {
+        BitWriter {
+            bytes: Vec::new(),
+            acc:   0,
+            bits:  0,
+        }
+    }
+
+    fn write(&mut self, code: u16, size: u32) {
+        self.acc |= (code as u32) << self.bits;
+        self.bits += size;
+        while self.bits >= 8 {
+            self.bytes.push(self.acc as u8);
+            self.acc >>= 8;
+            self.bits -= 8;
+        }
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.bits > 0 {
+            self.bytes.push(self.acc as u8);
+        }
+        self.bytes
+    }
+}
+
+fn invalid_input(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, msg)
+}
+
+fn put_u16(out: &mut Vec<u8>, v: u16) {
+    out.extend_from_slice(&[v as u8, (v >> 8) as u8]);
+}
@@ -1,11 +1,28 @@
 pub mod color;
 pub mod ppm;
+pub mod bmp;
+pub mod qoi;
+pub mod tga;
+pub mod gif;
+pub mod png;
+pub mod zlib;
+pub mod format;
 pub mod pixel;
 pub mod rect;
 pub mod triangle;
 pub mod bitmap;
+pub mod view;
+pub mod resize;
 pub mod canvas;
+pub mod context;
+pub mod picture;
+pub mod tiled;
+pub mod serialize;
+pub mod scene;
+pub mod gallery;
+pub mod svg;
 pub mod shader;
 pub mod point;
 pub mod matrix;
 pub mod util;
+pub mod simd;
@@ -1,360 +1,293 @@
 extern crate dinky;
 
-use dinky::color::Color;
-use dinky::pixel::Pixel;
 use dinky::rect::Rect;
-use dinky::point::Point;
 use dinky::canvas::Canvas;
 use dinky::bitmap::Bitmap;
-
-use std::path::Path;
-
-fn draw_solid_ramp(pathstr: &str) {
-    let mut canvas = Canvas::new(Bitmap::new(256, 196));
-
-    let (ramp_w, ramp_h) = (1, 28);
-
-    let c = 1.0/512.0;
-    let d = 1.0/256.0;
-
-    let recs = [
-        (Color::make_argb(1.0,     c,     c,     c), Color::make_argb(0.0,   d,   d,   d)),  // Grey
-        (Color::make_argb(1.0, 1.0-c,   0.0,   0.0), Color::make_argb(0.0,  -d, 0.0, 0.0)),  // Red
-        (Color::make_argb(1.0,   0.0,     c,     c), Color::make_argb(0.0, 0.0,   d,   d)),  // Cyan
-        (Color::make_argb(1.0,   0.0, 1.0-c,   0.0), Color::make_argb(0.0, 0.0,  -d, 0.0)),  // Green
-        (Color::make_argb(1.0,     c,   0.0,     c), Color::make_argb(0.0,   d, 0.0,   d)),  // Magenta
-        (Color::make_argb(1.0,   0.0,   0.0, 1.0-c), Color::make_argb(0.0, 0.0, 0.0,  -d)),  // Blue
-        (Color::make_argb(1.0,     c,     c,   0.0), Color::make_argb(0.0,   d,   d, 0.0)),  // Yellow
-    ];
-
-    for y in 0..recs.len() {
-        let (mut color, delta) = recs[y];
-        for x in 0..256 {
-            let rect = Rect::make_xywh((x*ramp_w) as f32, (y*ramp_h) as f32, ramp_w as f32, ramp_h as f32);
-
-            canvas.fill_rect(&rect, &color);
-
-            color.a += delta.a;
-            color.r += delta.r;
-            color.g += delta.g;
-            color.b += delta.b;
-        }
-    }
-
-    canvas.write(&Path::new(&pathstr));
+use dinky::context::DrawContext;
+use dinky::format::ImageFormat;
+use dinky::scene::{Scene, MAX_SIZE};
+use dinky::gallery;
+use dinky::tiled;
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process;
+
+const USAGE: &str = "\
+usage: dinky <command> [options]
+
+commands:
+    render <scene>        render a scene file (next to it, unless -o is given)
+    convert <in> <out>    convert between image formats
+    diff <a> <b>          compare two images
+    gallery [dir]         regenerate the built-in images (default: results/<format>)
+
+options:
+    --width <n>           output width, up to 16384; keeps the aspect ratio if alone
+    --height <n>          output height, up to 16384; keeps the aspect ratio if alone
+    --format <ext>        output format: ppm, bmp, qoi, tga or png
+    --threads <n>         threads to render a scene on (default: one per core)
+    -o, --output <path>   output path for render, or where diff writes a diff image
+
+exit codes:
+    0  success (for diff: the images are identical)
+    1  diff found differences
+    2  bad usage or a failure to read, parse or write";
+
+const EXIT_DIFFERENT: i32 = 1;
+const EXIT_FAILURE:   i32 = 2;
+
+struct Options {
+    args:    Vec<String>,
+    width:   Option<usize>,
+    height:  Option<usize>,
+    format:  Option<ImageFormat>,
+    output:  Option<PathBuf>,
+    threads: Option<usize>,
 }
 
-fn draw_blend_ramp(bg: &Color, pathstr: &str) {
-    let mut canvas = Canvas::new(Bitmap::new(200, 200));
-    canvas.clear(&bg);
-
-    let mut rect = Rect::make_xywh(-25.0, -25.0, 70.0, 70.0); 
-
-    let delta = 8.0;
-    let mut i = 0;
-    while i < 200 {
-        let j = i as f32;
-
-        let r = j / 200.0;
-        let g = (j / 40.0).cos().abs();
-        let b = (j / 50.0).sin().abs();
-
-        let color = Color::make_argb(0.3, r, g, b);
-
-        canvas.fill_rect(&rect, &color);
-
-        rect.offset(delta, delta);
+fn parse_options(args: &[String]) -> Result<Options, String> {
+    let mut options = Options {
+        args:    Vec::new(),
+        width:   None,
+        height:  None,
+        format:  None,
+        output:  None,
+        threads: None,
+    };
+
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        if !arg.starts_with('-') || arg == "-" {
+            options.args.push(arg.clone());
+            continue;
+        }
 
-        i += delta as i32;
+        let value = match iter.next() {
+            Some(value) => value,
+            None        => return Err(format!("{} needs a value", arg)),
+        };
+        let size = || match value.parse::<usize>() {
+            Ok(n) if n > 0 => Ok(n),
+            _              => Err(format!("{} expects a positive integer, got '{}'", arg, value)),
+        };
+        // Output sizes share the scene format's limit
+        let dimension = || match size()? {
+            n if n <= MAX_SIZE => Ok(n),
+            _                  => Err(format!("{} can be at most {}, got '{}'", arg, MAX_SIZE, value)),
+        };
+
+        match arg.as_str() {
+            "--width"         => options.width  = Some(dimension()?),
+            "--height"        => options.height = Some(dimension()?),
+            "--format"        => match ImageFormat::from_extension(value) {
+                Some(format) => options.format = Some(format),
+                None         => return Err(format!("unknown format '{}'", value)),
+            },
+            "--threads"       => options.threads = Some(size()?),
+            "-o" | "--output" => options.output = Some(PathBuf::from(value)),
+            _                 => return Err(format!("unknown option '{}'", arg)),
+        }
     }
 
-    canvas.write(&Path::new(&pathstr));
+    Ok(options)
 }
 
-fn draw_spocks_quad(pathstr: &str) {
-    let mut canvas = Canvas::new(Bitmap::new(300, 300));
-
-    let n = 300.0;
-
-    let mut bitmap = Bitmap::new(100, 100);
-    bitmap.read(&Path::new("spock.ppm"));
-
-    for y in 0..2 {
-        for x in 0..2 {
-            let (xf, yf) = (x as f32, y as f32);
-            let rect = Rect::make_xywh(xf*n - n/2.0, yf*n - n/2.0, n, n);
-            canvas.fill_bitmap_rect(&bitmap, &rect);
+impl Options {
+    fn expect_args(&self, command: &str, min: usize, max: usize) -> Result<(), String> {
+        if self.args.len() < min || self.args.len() > max {
+            return Err(format!("wrong number of arguments for {}", command));
         }
+        Ok(())
     }
 
-    canvas.write(&Path::new(&pathstr));
-}
-
-fn draw_spocks_zoom(pathstr: &str) {
-    let mut canvas = Canvas::new(Bitmap::new(300, 300));
-
-    let n = 300.0;
-
-    let mut bitmap = Bitmap::new(100, 100);
-    bitmap.read(&Path::new("spock.ppm"));
-
-    for i in 0..9 {
-        let f = i as f32;
-        let r = Rect::make_ltrb(f*10.0, f*10.0, n - f*10.0, n - f*10.0);
-        canvas.fill_bitmap_rect(&bitmap, &r);
+    // The requested size for an image that is naturally w x h. The side
+    // that keeps the aspect ratio is held to MAX_SIZE too.
+    fn size(&self, w: usize, h: usize) -> Result<(usize, usize), String> {
+        let scaled = |n: usize, to: usize, from: usize| {
+            let scaled = (n as f64 * to as f64 / from as f64).round().max(1.0);
+            if scaled > MAX_SIZE as f64 {
+                return Err(format!("keeping the aspect ratio of {}x{} needs a side over {}", w, h, MAX_SIZE));
+            }
+            Ok(scaled as usize)
+        };
+
+        Ok(match (self.width, self.height) {
+            (Some(tw), Some(th)) => (tw, th),
+            (Some(tw), None)     => (tw, scaled(h, tw, w)?),
+            (None, Some(th))     => (scaled(w, th, h)?, th),
+            (None, None)         => (w, h),
+        })
     }
 
-    canvas.write(&Path::new(&pathstr));
-}
-
-fn make_circle(bitmap: &mut Bitmap, color: &Color) {
-    let px = color.pin_to_unit().to_pixel();
-
-    let (cx, cy) = (bitmap.width as f32 / 2.0, bitmap.height as f32 / 2.0);
-
-    let r  = cx - 1.0;
-    let r2 = r*r;
-
-    for y in 0..bitmap.height {
-        let dy = y as f32 - cy;
-        for x in 0..bitmap.width {
-            let dx = x as f32 - cx;
-            let d2 = dx*dx + dy*dy;
-            if d2 <= r2 {
-                bitmap.set(x, y, &px);
-            } else {
-                bitmap.set(x, y, &Pixel::pack_argb(0,0,0,0));
-            }
+    // An explicit output path decides the format, so --format must agree
+    fn output_path(&self, default: &Path, fallback: ImageFormat) -> Result<PathBuf, String> {
+        match (self.output.as_ref(), self.format) {
+            (Some(path), Some(format)) if ImageFormat::from_path(path) != Some(format) => {
+                Err(format!("{} does not have the extension for --format {}", path.display(), format.extension()))
+            },
+            (Some(path), _)      => Ok(path.clone()),
+            (None, Some(format)) => Ok(default.with_extension(format.extension())),
+            (None, None)         => Ok(default.with_extension(fallback.extension())),
         }
     }
 }
 
-fn draw_bm_circles(pathstr: &str) {
-    let mut canvas = Canvas::new(Bitmap::new(300, 300));
-
-    let n = 300.0;
-
-    let mut bitmap = Bitmap::new(n as usize, n as usize);
-
-    let recs = [
-        (Rect::make_xywh(0.0, 0.0, n, n), Color::make_argb(1.0, 1.0, 1.0, 1.0)),
-
-        (Rect::make_xywh(  0.0,   0.0, n/2.0, n/2.0), Color::make_argb(0.8, 0.0, 0.0, 1.0)),
-        (Rect::make_xywh(n/2.0,   0.0, n/2.0, n/2.0), Color::make_argb(0.6, 0.0, 1.0, 0.0)),
-        (Rect::make_xywh(  0.0, n/2.0, n/2.0, n/2.0), Color::make_argb(0.4, 1.0, 0.0, 0.0)),
-        (Rect::make_xywh(n/2.0, n/2.0, n/2.0, n/2.0), Color::make_argb(0.2, 0.0, 0.0, 0.0)),
-
-        (Rect::make_xywh(  0.0, n/3.0,     n, n/3.0), Color::make_argb(0.5, 1.0, 1.0, 0.0)),
-        (Rect::make_xywh(n/3.0,   0.0, n/3.0,     n), Color::make_argb(0.5, 0.0, 1.0, 1.0)),
-        (Rect::make_xywh(n/3.0, n/3.0, n/3.0, n/3.0), Color::make_argb(0.5, 1.0, 0.0, 1.0)),
-    ];
-
-    for i in 0..recs.len() {
-        let rect  = &recs[i].0;
-        let color = &recs[i].1;
-
-        make_circle(&mut bitmap, &color);
-
-        canvas.fill_bitmap_rect(&bitmap, &rect);
+// Draws into a canvas of the given size, scaled so the natural size fills it
+fn render_scaled(natural: (usize, usize), size: (usize, usize), draw: &dyn Fn(&mut dyn DrawContext)) -> Bitmap {
+    let mut canvas = Canvas::new(Bitmap::new(size.0, size.1));
+    if size != natural {
+        canvas.scale(size.0 as f32 / natural.0 as f32, size.1 as f32 / natural.1 as f32);
     }
-
-    canvas.write(&Path::new(&pathstr));
+    draw(&mut canvas);
+    canvas.bitmap().clone()
 }
 
-fn draw_circle_big(pathstr: &str) {
-    let mut canvas = Canvas::new(Bitmap::new(400, 300));
-
-    let n = 300.0;
-    let a = 0.4;
-
-    let colors = [
-        Color::make_argb(a, 1.0, 0.0, 0.0),
-        Color::make_argb(a, 0.0, 1.0, 0.0),
-        Color::make_argb(a, 0.0, 0.0, 1.0),
-    ];
-
-    let mut x = 0;
-    let mut m = n as i32;
-    let mut i = 0;
-    while m > 4 {
-        let mut bitmap = Bitmap::new(m as usize, m as usize);
-        make_circle(&mut bitmap, &colors[i % colors.len()]);
-
-        canvas.fill_bitmap_rect(&bitmap, &Rect::make_xywh(x as f32, 0.0, n, n));
-
-        x += n as i32 / 12;
-        m >>= 1;
-        i += 1;
-    }
-
-    canvas.write(&Path::new(&pathstr));
+fn load(path: &Path) -> Result<Bitmap, String> {
+    Bitmap::load(path).map_err(|err| format!("{}: {}", path.display(), err))
 }
 
-fn draw_tri(pathstr: &str) {
-    let mut canvas = Canvas::new(Bitmap::new(256, 256));
-
-    let points = [
-        Point::new( 10.0,  10.0),
-        Point::new(200.0,  50.0),
-        Point::new(100.0, 200.0),
-    ];
-
-    canvas.fill_convex_polygon(&points, &Color::green());
-
-    canvas.write(&Path::new(&pathstr));
+fn save(bitmap: &Bitmap, path: &Path) -> Result<(), String> {
+    bitmap.save(path).map_err(|err| format!("{}: {}", path.display(), err))
 }
 
-fn draw_tri_clipped(pathstr: &str) {
-    let mut canvas = Canvas::new(Bitmap::new(256, 256));
-
-    let points = [
-        Point::new(-10.0, -10.0),
-        Point::new(300.0,  50.0),
-        Point::new(100.0, 300.0),
-    ];
+fn render(options: &Options) -> Result<i32, String> {
+    options.expect_args("render", 1, 1)?;
+    let scene_path = Path::new(&options.args[0]);
 
-    canvas.fill_convex_polygon(&points, &Color::yellow());
+    let text = fs::read_to_string(scene_path).map_err(|err| format!("{}: {}", scene_path.display(), err))?;
+    let base = scene_path.parent().unwrap_or(Path::new(""));
+    let scene = Scene::parse(&text, base).map_err(|err| format!("{}:{}", scene_path.display(), err))?;
 
-    canvas.write(&Path::new(&pathstr));
-}
+    let out_path = options.output_path(scene_path, scene.format)?;
+    let (width, height) = options.size(scene.width, scene.height)?;
+    let (sx, sy) = (width as f32 / scene.width as f32, height as f32 / scene.height as f32);
 
-fn make_regular_poly(points: &mut[Point], count: usize, cx: f32, cy: f32, radius: f32) {
-    let mut angle = 0f32;
-    let delta_angle = std::f32::consts::PI*2.0 / count as f32;
+    // Same as render_scaled, but split across threads
+    let mut bitmap = Bitmap::new(width, height);
+    let threads = options.threads.unwrap_or_else(tiled::default_threads);
+    tiled::playback(&scene.picture, &mut bitmap, [sx, 0.0, 0.0, 0.0, sy, 0.0], threads);
+    save(&bitmap, &out_path)?;
 
-    for i in 0..count {
-        points[i] = Point::new(cx + angle.cos()*radius, cy + angle.sin()*radius);
-        angle += delta_angle;
-    }
+    Ok(0)
 }
 
-fn dr_poly(canvas: &mut Canvas, dx: f32, dy: f32) {
-    let mut points = [Point::new(0.0, 0.0); 12];
-    for count in (3 as usize .. 13 as usize).rev() {
-        make_regular_poly(&mut points, count, 256 as f32, 256 as f32, (count*10 + 120) as f32);
-
-        for i in 0..count {
-            points[i].x += dx;
-            points[i].y += dy;
+fn convert(options: &Options) -> Result<i32, String> {
+    options.expect_args("convert", 2, 2)?;
+    if options.output.is_some() {
+        return Err("convert takes its output as the second argument".to_string());
+    }
+    let (in_path, out_path) = (Path::new(&options.args[0]), Path::new(&options.args[1]));
+    if let Some(format) = options.format {
+        if ImageFormat::from_path(out_path) != Some(format) {
+            return Err(format!("{} does not have the extension for --format {}", out_path.display(), format.extension()));
         }
-
-        let color = Color::make_argb(0.8,
-                                     (count as f32 *  7.0).sin().abs(),
-                                     (count as f32 * 11.0).sin().abs(),
-                                     (count as f32 * 17.0).sin().abs());
-
-        canvas.fill_convex_polygon(&points[0..count], &color);
     }
-}
 
-fn draw_poly(pathstr: &str) {
-    let mut canvas = Canvas::new(Bitmap::new(512, 512));
-    dr_poly(&mut canvas, 0.0, 0.0);
-    canvas.write(&Path::new(&pathstr));
-}
+    let mut bitmap = load(in_path)?;
 
-fn draw_poly_center(pathstr: &str) {
-    let mut canvas = Canvas::new(Bitmap::new(256, 256));
-    dr_poly(&mut canvas, -128.0, -128.0);
-    canvas.write(&Path::new(&pathstr));
-}
-
-fn scale(vec: &Point, size: f32) -> Point {
-    let scale = size / (vec.x*vec.x + vec.y*vec.y).sqrt();
-    Point::new(vec.x*scale, vec.y*scale)
-}
-
-fn draw_line(canvas: &mut Canvas, a: &Point, b: &Point, width: f32, color: &Color) {
-    let norm = scale(&Point::new(b.y-a.y, b.x-a.x), width/2.0);
-
-    let points = [
-        Point::new(a.x - norm.x, a.y - norm.y),
-        Point::new(b.x - norm.x, b.y - norm.y),
-        Point::new(b.x + norm.x, b.y + norm.y),
-        Point::new(a.x + norm.x, a.y + norm.y),
-    ];
+    let (w, h) = options.size(bitmap.width, bitmap.height)?;
+    if (w, h) != (bitmap.width, bitmap.height) {
+        let mut canvas = Canvas::new(Bitmap::new(w, h));
+        canvas.fill_bitmap_rect(&bitmap, &Rect::make_wh(w as f32, h as f32));
+        bitmap = canvas.bitmap().clone();
+    }
+    save(&bitmap, out_path)?;
 
-    canvas.fill_convex_polygon(&points, &color);
+    Ok(0)
 }
 
-fn draw_poly_rotate(pathstr: &str) {
-    let mut canvas = Canvas::new(Bitmap::new(230, 230));
-
-    let start = Point::new(20.0, 20.0);
-    let scale = 200f32;
-
-    let n = 10.0;
-    let mut color = Color::red();
-    let delta_r = -1.0/n;
-    let delta_b =  1.0/n;
-
-    let width = 10.0;
-
-    let mut angle = 0.0;
-    while angle <= std::f32::consts::PI / 2.0 {
-        let end = Point::new(start.x + angle.cos()*scale, start.y + angle.sin()*scale);
+fn diff(options: &Options) -> Result<i32, String> {
+    options.expect_args("diff", 2, 2)?;
+    if options.width.is_some() || options.height.is_some() || options.format.is_some() {
+        return Err("diff takes no --width, --height or --format".to_string());
+    }
 
-        draw_line(&mut canvas, &start, &end, width, &color);
+    let a = load(Path::new(&options.args[0]))?;
+    let b = load(Path::new(&options.args[1]))?;
 
-        color.r += delta_r;
-        color.b += delta_b;
+    if (a.width, a.height) != (b.width, b.height) {
+        println!("sizes differ: {}x{} vs {}x{}", a.width, a.height, b.width, b.height);
+        return Ok(EXIT_DIFFERENT);
+    }
 
-        angle += std::f32::consts::PI / 2.0 / n;
+    if let Some(ref path) = options.output {
+        save(&a.diff_image(&b, 0), path)?;
     }
 
-    canvas.write(&Path::new(&pathstr));
+    let stats = a.diff(&b);
+    let bounds = match stats.bounds {
+        Some(bounds) => bounds,
+        None         => {
+            println!("identical");
+            return Ok(0);
+        },
+    };
+
+    println!("{} of {} pixels differ", stats.differing, a.pixels.len());
+    println!("max channel delta {}", stats.max_delta);
+    println!("psnr {:.2} dB", stats.psnr);
+    println!("ssim {:.4}", a.ssim(&b));
+    println!("bounds {} {} {} {}", bounds.left(), bounds.top(), bounds.right(), bounds.bottom());
+
+    Ok(EXIT_DIFFERENT)
 }
 
-fn draw_translate_rect(pathstr: &str) {
-    let mut canvas = Canvas::new(Bitmap::new(100, 100));
-    canvas.clear(&Color::white());
-
-    canvas.translate(25.0, 25.0);
-
-    let angle = std::f32::consts::PI / 8.0;
-
-    for i in 0..4 {
-        canvas.rotate_about(angle, &Point::new(50.0, 50.0));
-
-        canvas.fill_rect(&Rect::make_wh(50.0, 50.0), &Color::make_argb(0.5, 1.0, 0.0, 0.0));
+fn gallery(options: &Options) -> Result<i32, String> {
+    options.expect_args("gallery", 0, 1)?;
+    if options.output.is_some() {
+        return Err("gallery takes its directory as an argument".to_string());
     }
 
-    canvas.write(&Path::new(&pathstr));
-}
+    let format = options.format.unwrap_or(ImageFormat::Ppm);
+    let dir = match options.args.first() {
+        Some(dir) => PathBuf::from(dir),
+        None      => Path::new("results").join(format.extension()),
+    };
+    fs::create_dir_all(&dir).map_err(|err| format!("{}: {}", dir.display(), err))?;
 
-/*
-fn test() {
-    let mut canvas = Canvas::new(Bitmap::new(100, 100));
+    for image in gallery::IMAGES.iter() {
+        let natural = (image.width, image.height);
+        let bitmap = render_scaled(natural, options.size(image.width, image.height)?, &|ctx| (image.draw)(ctx));
 
-    let points = [
-        Point::new(50.0, 25.0),
-        Point::new(75.0, 50.0),
-        Point::new(50.0, 75.0),
-        Point::new(25.0, 50.0),
-    ];
-    canvas.fill_convex_polygon(&points, &Color::red());
+        let path = dir.join(image.name).with_extension(format.extension());
+        save(&bitmap, &path)?;
+    }
 
-    canvas.write(&Path::new("test.ppm"));
+    Ok(0)
 }
-*/
 
 fn main() {
-    draw_solid_ramp("results/ppm/solid_ramp.ppm");
-    draw_blend_ramp(&Color::black(), "results/ppm/blend_black.ppm");
-    draw_blend_ramp(&Color::white(), "results/ppm/blend_white.ppm");
-
-    draw_spocks_quad("results/ppm/spocks_quad.ppm");
-    draw_spocks_zoom("results/ppm/spocks_zoom.ppm");
-    draw_bm_circles("results/ppm/circles_blend.ppm");
-    draw_circle_big("results/ppm/circles_fat.ppm");
-
-    draw_tri("results/ppm/tri.ppm");
-    draw_tri_clipped("results/ppm/tri_clipped.ppm");
-    draw_poly("results/ppm/poly.ppm");
-    draw_poly_center("results/ppm/poly_center.ppm");
-    draw_poly_rotate("results/ppm/poly_rotate.ppm");
-
-    draw_translate_rect("test.ppm");
+    let args: Vec<String> = env::args().skip(1).collect();
+
+    let command = match args.first() {
+        Some(command) => command.as_str(),
+        None          => {
+            eprintln!("{}", USAGE);
+            process::exit(EXIT_FAILURE);
+        },
+    };
+    if command == "help" || command == "-h" || command == "--help" {
+        println!("{}", USAGE);
+        return;
+    }
 
-    //test();
+    let result = parse_options(&args[1..]).and_then(|options| {
+        match command {
+            "render"  => render(&options),
+            "convert" => convert(&options),
+            "diff"    => diff(&options),
+            "gallery" => gallery(&options),
+            _         => Err(format!("unknown command '{}'\n\n{}", command, USAGE)),
+        }
+    });
+
+    match result {
+        Ok(code) => process::exit(code),
+        Err(msg) => {
+            eprintln!("dinky: {}", msg);
+            process::exit(EXIT_FAILURE);
+        },
+    }
 }
@@ -1,23 +1,119 @@
 use point::Point;
+use rect::Rect;
 
-#[derive(Copy, Clone)]
+// What a matrix does, from least to most general, so fast paths can ask
+// for e.g. anything up to ScaleTranslate
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum MatrixType {
+    Identity,
+    Translate,
+    ScaleTranslate,
+    Affine,
+    Perspective,
+}
+
+// An affine matrix as translate * rotate * skew_x * scale. Angles are in
+// radians; a mirror shows up as a negative scale_y.
+#[derive(Copy, Clone, Debug)]
+pub struct Decomposition {
+    pub translate_x: f32,
+    pub translate_y: f32,
+    pub rotate:      f32,
+    pub skew_x:      f32,
+    pub scale_x:     f32,
+    pub scale_y:     f32,
+}
+
+impl Decomposition {
+    pub fn to_matrix(&self) -> Matrix {
+        let (sin, cos) = self.rotate.sin_cos();
+        let skew = self.skew_x.tan();
+        let (sx, sy) = (self.scale_x, self.scale_y);
+
+        Matrix::new([
+            cos*sx, (cos*skew - sin)*sy, self.translate_x,
+            sin*sx, (sin*skew + cos)*sy, self.translate_y,
+        ])
+    }
+}
+
+// Row-major 3x3 homogeneous matrix. The last row is [0 0 1] unless the
+// matrix has perspective.
+#[derive(Copy, Clone, Debug)]
 pub struct Matrix {
-    mat: [f32; 6],
+    mat: [f32; 9],
 }
 
 impl Matrix {
+    // Affine, from the top two rows
     pub fn new(mat: [f32; 6]) -> Matrix {
+        Matrix {
+            mat: [mat[0], mat[1], mat[2], mat[3], mat[4], mat[5], 0.0, 0.0, 1.0],
+        }
+    }
+
+    pub fn from_3x3(mat: [f32; 9]) -> Matrix {
         Matrix {
             mat: mat,
         }
     }
 
     pub fn identity() -> Matrix {
-        Matrix {
-            mat: [1.0, 0.0, 0.0, 0.0, 1.0, 0.0],
+        Matrix::new([1.0, 0.0, 0.0, 0.0, 1.0, 0.0])
+    }
+
+    pub fn is_perspective(&self) -> bool {
+        self.mat[6] != 0.0 || self.mat[7] != 0.0 || self.mat[8] != 1.0
+    }
+
+    pub fn get_type(&self) -> MatrixType {
+        let m = &self.mat;
+
+        if self.is_perspective() {
+            MatrixType::Perspective
+        } else if m[1] != 0.0 || m[3] != 0.0 {
+            MatrixType::Affine
+        } else if m[0] != 1.0 || m[4] != 1.0 {
+            MatrixType::ScaleTranslate
+        } else if m[2] != 0.0 || m[5] != 0.0 {
+            MatrixType::Translate
+        } else {
+            MatrixType::Identity
+        }
+    }
+
+    // None under perspective or when the matrix is singular
+    pub fn decompose(&self) -> Option<Decomposition> {
+        if self.is_perspective() {
+            return None;
         }
+
+        let (a, b, c) = (self.mat[0], self.mat[1], self.mat[2]);
+        let (d, e, f) = (self.mat[3], self.mat[4], self.mat[5]);
+
+        // Take the rotation off the first column, leaving an upper
+        // triangular [sx k; 0 sy] = skew * scale
+        let sx = a.hypot(d);
+        if sx == 0.0 {
+            return None;
+        }
+        let k  = (a*b + d*e) / sx;
+        let sy = (a*e - b*d) / sx;
+        if sy == 0.0 || !sy.is_finite() {
+            return None;
+        }
+
+        Some(Decomposition {
+            translate_x: c,
+            translate_y: f,
+            rotate:      d.atan2(a),
+            skew_x:      (k / sy).atan(),
+            scale_x:     sx,
+            scale_y:     sy,
+        })
     }
 
+    // Maps a point, dividing through by w. Only meaningful where w > 0.
     pub fn apply(&self, point: &Point) -> Point {
         let (a, b, c) = (self.mat[0], self.mat[1], self.mat[2]);
         let (d, e, f) = (self.mat[3], self.mat[4], self.mat[5]);
@@ -27,10 +123,57 @@ impl Matrix {
         let xp = a*x + b*y + c;
         let yp = d*x + e*y + f;
 
-        Point::new(xp, yp)
+        if !self.is_perspective() {
+            return Point::new(xp, yp);
+        }
+
+        let w = self.mat[6]*x + self.mat[7]*y + self.mat[8];
+        Point::new(xp / w, yp / w)
+    }
+
+    // Maps a point without the divide, as [x, y, w]
+    pub fn apply_homogeneous(&self, point: &Point) -> [f32; 3] {
+        let m = &self.mat;
+        let (x, y) = (point.x, point.y);
+
+        [m[0]*x + m[1]*y + m[2], m[3]*x + m[4]*y + m[5], m[6]*x + m[7]*y + m[8]]
+    }
+
+    // Bounds of the mapped corners. Under perspective this assumes the whole
+    // rect maps to w > 0.
+    pub fn map_rect(&self, rect: &Rect) -> Rect {
+        let corners = [
+            self.apply(&Point::new(rect.left(),  rect.top())),
+            self.apply(&Point::new(rect.right(), rect.top())),
+            self.apply(&Point::new(rect.right(), rect.bottom())),
+            self.apply(&Point::new(rect.left(),  rect.bottom())),
+        ];
+
+        let l = corners.iter().fold(f32::INFINITY,     |acc, p| acc.min(p.x));
+        let t = corners.iter().fold(f32::INFINITY,     |acc, p| acc.min(p.y));
+        let r = corners.iter().fold(f32::NEG_INFINITY, |acc, p| acc.max(p.x));
+        let b = corners.iter().fold(f32::NEG_INFINITY, |acc, p| acc.max(p.y));
+
+        Rect::make_ltrb(l, t, r, b)
+    }
+
+    // None when the matrix is singular, or so close to it that the
+    // inverse doesn't fit in an f32
+    pub fn invert(&self) -> Option<Matrix> {
+        let inv = if self.is_perspective() {
+            self.inv_3x3()
+        } else {
+            self.inv_affine()
+        };
+
+        if inv.mat.iter().all(|v| v.is_finite()) {
+            Some(inv)
+        } else {
+            None
+        }
     }
 
-    pub fn inv(&self) -> Matrix {
+    fn inv_affine(&self) -> Matrix {
         let mut res = [0.0; 6];
 
         let (a, b, c) = (self.mat[0], self.mat[1], self.mat[2]);
@@ -51,7 +194,27 @@ impl Matrix {
         Matrix::new(res)
     }
 
+    // Adjugate over determinant
+    fn inv_3x3(&self) -> Matrix {
+        let (a, b, c) = (self.mat[0], self.mat[1], self.mat[2]);
+        let (d, e, f) = (self.mat[3], self.mat[4], self.mat[5]);
+        let (g, h, i) = (self.mat[6], self.mat[7], self.mat[8]);
+
+        let (co_a, co_b, co_c) = (e*i - f*h, f*g - d*i, d*h - e*g);
+        let det_inv = 1.0 / (a*co_a + b*co_b + c*co_c);
+
+        Matrix::from_3x3([
+            co_a * det_inv, (c*h - b*i) * det_inv, (b*f - c*e) * det_inv,
+            co_b * det_inv, (a*i - c*g) * det_inv, (c*d - a*f) * det_inv,
+            co_c * det_inv, (b*g - a*h) * det_inv, (a*e - b*d) * det_inv,
+        ])
+    }
+
     pub fn mul(&self, other: &Matrix) -> Matrix {
+        if self.is_perspective() || other.is_perspective() {
+            return self.mul_3x3(other);
+        }
+
         let mut res = [0.0; 6];
 
         let (a, b, c) = (self.mat[0], self.mat[1], self.mat[2]);
@@ -70,11 +233,29 @@ impl Matrix {
         Matrix::new(res)
     }
 
+    fn mul_3x3(&self, other: &Matrix) -> Matrix {
+        let mut res = [0.0; 9];
+        for row in 0..3 {
+            for col in 0..3 {
+                res[row*3 + col] = self.mat[row*3]     * other.mat[col] +
+                                   self.mat[row*3 + 1] * other.mat[3 + col] +
+                                   self.mat[row*3 + 2] * other.mat[6 + col];
+            }
+        }
+
+        Matrix::from_3x3(res)
+    }
+
     pub fn at(&self, i: usize) -> f32 {
         self.mat[i]
     }
 
+    // The top two rows, which is all of an affine matrix
     pub fn get_floats(&self) -> [f32; 6] {
+        [self.mat[0], self.mat[1], self.mat[2], self.mat[3], self.mat[4], self.mat[5]]
+    }
+
+    pub fn get_floats_3x3(&self) -> [f32; 9] {
         self.mat
     }
 }
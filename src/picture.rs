@@ -0,0 +1,233 @@
+use bitmap::Bitmap;
+use color::Color;
+use rect::Rect;
+use point::Point;
+use matrix::Matrix;
+use shader::{Shader, ShaderDesc, Shaders};
+use context::DrawContext;
+use util::map_polygon;
+
+#[derive(Clone)]
+pub enum DrawOp {
+    Clear(Color),
+    FillRect(Rect, Color),
+    FillConvexPolygon(Vec<Point>, Color),
+    FillBitmapRect(Bitmap, Rect),
+    ShadeRect(Rect, ShaderDesc),
+    ShadeConvexPolygon(Vec<Point>, ShaderDesc),
+    Save,
+    Restore,
+    Concat(Matrix),
+    ClipRect(Rect),
+}
+
+// An immutable list of recorded draws, replayable onto any DrawContext
+#[derive(Clone)]
+pub struct Picture {
+    ops:  Vec<DrawOp>,
+    cull: Rect,
+}
+
+impl Picture {
+    pub fn new(ops: Vec<DrawOp>, cull: Rect) -> Picture {
+        Picture {
+            ops:  ops,
+            cull: cull,
+        }
+    }
+
+    pub fn ops(&self) -> &[DrawOp] {
+        &self.ops
+    }
+
+    // Conservative bounds of everything the picture draws, in recording space
+    pub fn cull_rect(&self) -> Rect {
+        self.cull
+    }
+
+    pub fn playback(&self, ctx: &mut dyn DrawContext, mat: [f32; 6]) {
+        ctx.save();
+        ctx.concat(mat);
+
+        // The recorded CTM, relative to where playback started
+        let mut ctms = vec![Matrix::identity()];
+
+        for op in &self.ops {
+            match *op {
+                DrawOp::Clear(ref color)                   => self.clear_cull(ctx, &ctms[ctms.len()-1], color),
+                DrawOp::FillRect(ref rect, ref color)      => ctx.fill_rect(rect, color),
+                DrawOp::FillConvexPolygon(ref pts, ref c)  => ctx.fill_convex_polygon(pts, c),
+                DrawOp::FillBitmapRect(ref bitmap, ref r)  => ctx.fill_bitmap_rect(bitmap, r),
+                DrawOp::ShadeRect(ref rect, ref desc)      => ctx.shade_rect(rect, &mut *Shaders::from_desc(desc)),
+                DrawOp::ShadeConvexPolygon(ref pts, ref d) => ctx.shade_convex_polygon(pts, &mut *Shaders::from_desc(d)),
+                DrawOp::Save => {
+                    let ctm = ctms[ctms.len()-1];
+                    ctms.push(ctm);
+                    ctx.save();
+                },
+                DrawOp::Restore => {
+                    ctms.pop();
+                    ctx.restore();
+                },
+                DrawOp::Concat(ref mat) => {
+                    let len = ctms.len();
+                    ctms[len-1] = ctms[len-1].mul(mat);
+                    ctx.concat_matrix(mat);
+                },
+                DrawOp::ClipRect(ref rect)                 => ctx.clip_rect(rect),
+            }
+        }
+
+        ctx.restore();
+    }
+
+    // Clears stand for the recording's bounds, not the whole target, so step
+    // back out to the space playback started in and clip to the cull rect.
+    // Only clears get the clip: clipped edges snap a little differently.
+    fn clear_cull(&self, ctx: &mut dyn DrawContext, ctm: &Matrix, color: &Color) {
+        ctx.save();
+        if let Some(inverse) = ctm.invert() {
+            ctx.concat_matrix(&inverse);
+            ctx.clip_rect(&self.cull);
+        }
+        ctx.clear(color);
+        ctx.restore();
+    }
+}
+
+// Records draws into a Picture. The bounds given here start out as the clip,
+// and the picture's cull rect grows to cover what is drawn inside the clip.
+// Playback clips only clears to the cull rect; other draws are not clipped.
+pub struct PictureRecorder {
+    ops:   Vec<DrawOp>,
+    ctms:  Vec<Matrix>,
+    clips: Vec<Rect>,  // Device-space bounds of the clip
+    cull:  Rect,
+}
+
+impl PictureRecorder {
+    pub fn new(bounds: Rect) -> PictureRecorder {
+        PictureRecorder {
+            ops:   Vec::new(),
+            ctms:  vec![Matrix::identity()],
+            clips: vec![bounds],
+            cull:  Rect::make_wh(0.0, 0.0),
+        }
+    }
+
+    // Unbalanced saves are closed so the picture leaves no state behind
+    pub fn finish(mut self) -> Picture {
+        for _ in 1..self.ctms.len() {
+            self.ops.push(DrawOp::Restore);
+        }
+
+        // Round out to whole pixels
+        let cull = Rect::make_ltrb(self.cull.left().floor(),  self.cull.top().floor(),
+                                   self.cull.right().ceil(),  self.cull.bottom().ceil());
+
+        Picture::new(self.ops, cull)
+    }
+
+    fn get_ctm(&self) -> Matrix {
+        self.ctms[self.ctms.len()-1]
+    }
+
+    fn device_bounds(&self, points: &[Point]) -> Rect {
+        let mapped = map_polygon(&self.get_ctm(), points);
+
+        let l = mapped.iter().fold(f32::INFINITY,     |acc, p| acc.min(p.x));
+        let t = mapped.iter().fold(f32::INFINITY,     |acc, p| acc.min(p.y));
+        let r = mapped.iter().fold(f32::NEG_INFINITY, |acc, p| acc.max(p.x));
+        let b = mapped.iter().fold(f32::NEG_INFINITY, |acc, p| acc.max(p.y));
+
+        Rect::make_ltrb(l, t, r, b)
+    }
+
+    fn accumulate(&mut self, points: &[Point]) {
+        let mut bounds = self.device_bounds(points);
+        if bounds.intersect(&self.clips[self.clips.len()-1]) {
+            self.cull.join(&bounds);
+        }
+    }
+
+    // Canvas snaps rects to whole local units, so cover both versions
+    fn accumulate_rect(&mut self, rect: &Rect) {
+        let mut covered = *rect;
+        covered.join(&rect.round());
+        self.accumulate(&corners(&covered));
+    }
+}
+
+impl DrawContext for PictureRecorder {
+    fn clear(&mut self, color: &Color) {
+        let clip = self.clips[self.clips.len()-1];
+        self.cull.join(&clip);
+        self.ops.push(DrawOp::Clear(*color));
+    }
+
+    fn shade_rect(&mut self, rect: &Rect, shader: &mut dyn Shader) {
+        self.accumulate_rect(rect);
+        self.ops.push(DrawOp::ShadeRect(*rect, shader.desc()));
+    }
+
+    fn shade_convex_polygon(&mut self, points: &[Point], shader: &mut dyn Shader) {
+        self.accumulate(points);
+        self.ops.push(DrawOp::ShadeConvexPolygon(points.to_vec(), shader.desc()));
+    }
+
+    fn fill_rect(&mut self, rect: &Rect, color: &Color) {
+        self.accumulate_rect(rect);
+        self.ops.push(DrawOp::FillRect(*rect, *color));
+    }
+
+    fn fill_convex_polygon(&mut self, points: &[Point], color: &Color) {
+        self.accumulate(points);
+        self.ops.push(DrawOp::FillConvexPolygon(points.to_vec(), *color));
+    }
+
+    fn fill_bitmap_rect(&mut self, src: &Bitmap, dst: &Rect) {
+        self.accumulate_rect(dst);
+        self.ops.push(DrawOp::FillBitmapRect(src.clone(), *dst));
+    }
+
+    fn save(&mut self) {
+        let ctm  = self.get_ctm();
+        let clip = self.clips[self.clips.len()-1];
+        self.ctms.push(ctm);
+        self.clips.push(clip);
+        self.ops.push(DrawOp::Save);
+    }
+
+    fn restore(&mut self) {
+        assert!(self.ctms.len() > 1, "restore without matching save");
+        self.ctms.pop();
+        self.clips.pop();
+        self.ops.push(DrawOp::Restore);
+    }
+
+    fn concat_matrix(&mut self, mat: &Matrix) {
+        let ctm = self.get_ctm();
+        let len = self.ctms.len();
+        self.ctms[len-1] = ctm.mul(mat);
+        self.ops.push(DrawOp::Concat(*mat));
+    }
+
+    fn clip_rect(&mut self, rect: &Rect) {
+        let bounds = self.device_bounds(&corners(rect));
+
+        let len = self.clips.len();
+        if !self.clips[len-1].intersect(&bounds) {
+            self.clips[len-1] = Rect::make_wh(0.0, 0.0);
+        }
+        self.ops.push(DrawOp::ClipRect(*rect));
+    }
+}
+
+fn corners(rect: &Rect) -> [Point; 4] {
+    [
+        Point::new(rect.left(),  rect.top()),
+        Point::new(rect.right(), rect.top()),
+        Point::new(rect.right(), rect.bottom()),
+        Point::new(rect.left(),  rect.bottom()),
+    ]
+}
@@ -1,14 +1,41 @@
 use color::Color;
 
+// Byte orders a pixel can have in memory
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum PixelOrder {
+    Bgra,  // 0xAARRGGBB as a little-endian u32; Windows, Cairo, most framebuffers
+    Rgba,  // 0xAABBGGRR as a little-endian u32; OpenGL, Vulkan, WebGPU
+}
+
+// Premultiplied, laid out in Pixel::ORDER so bitmaps can be handed to other
+// libraries as they are and rows loaded straight into SIMD registers. The
+// order is BGRA unless the "rgba" feature is enabled.
+#[cfg(not(feature = "rgba"))]
 #[derive(Copy, Clone)]
+#[repr(C)]
 pub struct Pixel {
+    pub b: u8,
+    pub g: u8,
+    pub r: u8,
     pub a: u8,
+}
+
+#[cfg(feature = "rgba")]
+#[derive(Copy, Clone)]
+#[repr(C)]
+pub struct Pixel {
     pub r: u8,
     pub g: u8,
     pub b: u8,
+    pub a: u8,
 }
 
 impl Pixel {
+    #[cfg(not(feature = "rgba"))]
+    pub const ORDER: PixelOrder = PixelOrder::Bgra;
+    #[cfg(feature = "rgba")]
+    pub const ORDER: PixelOrder = PixelOrder::Rgba;
+
     pub fn pack_argb(a: u8, r: u8, g: u8, b: u8) -> Pixel {
         // Assert r,g,b are already in premul form
         assert!(r <= a, "r > a");
@@ -27,6 +54,49 @@ impl Pixel {
         Pixel::pack_argb(255, r, g, b)
     }
 
+    pub fn pack_unpremul_argb(a: u8, r: u8, g: u8, b: u8) -> Pixel {
+        // Convert straight alpha to premul
+        let premul = |c: u8| ((c as u32 * a as u32 + 127) / 255) as u8;
+
+        Pixel::pack_argb(a, premul(r), premul(g), premul(b))
+    }
+
+    pub fn unpremul_argb(&self) -> (u8, u8, u8, u8) {
+        let (a, mut r, mut g, mut b) = (self.a, self.r, self.g, self.b);
+        if a != 0 && a != 255 {
+            r = ((r as i32 * 255 + a as i32/2) / a as i32) as u8;
+            g = ((g as i32 * 255 + a as i32/2) / a as i32) as u8;
+            b = ((b as i32 * 255 + a as i32/2) / a as i32) as u8;
+        }
+
+        (a, r, g, b)
+    }
+
+    // The bytes as they are in memory
+    pub fn to_bytes(&self) -> [u8; 4] {
+        match Pixel::ORDER {
+            PixelOrder::Bgra => [self.b, self.g, self.r, self.a],
+            PixelOrder::Rgba => [self.r, self.g, self.b, self.a],
+        }
+    }
+
+    // The inverse of to_bytes. Like pack_argb, expects premultiplied bytes.
+    pub fn from_bytes(bytes: [u8; 4]) -> Pixel {
+        match Pixel::ORDER {
+            PixelOrder::Bgra => Pixel::pack_argb(bytes[3], bytes[2], bytes[1], bytes[0]),
+            PixelOrder::Rgba => Pixel::pack_argb(bytes[3], bytes[0], bytes[1], bytes[2]),
+        }
+    }
+
+    // The pixel as a native-endian u32, e.g. 0xAARRGGBB for BGRA on x86
+    pub fn to_u32(&self) -> u32 {
+        u32::from_ne_bytes(self.to_bytes())
+    }
+
+    pub fn from_u32(value: u32) -> Pixel {
+        Pixel::from_bytes(value.to_ne_bytes())
+    }
+
     pub fn to_color(&self) -> Color {
         let a = (self.a as f32) / 256.0;
         let r = (self.r as f32) / 256.0;
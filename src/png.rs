@@ -0,0 +1,274 @@
+use bitmap::Bitmap;
+use zlib;
+
+use std::path::Path;
+use std::fs::File;
+use std::io;
+use std::io::prelude::*;
+use std::io::BufWriter;
+
+const SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n'];
+
+// Color type for 8-bit straight RGBA
+const COLOR_RGBA: u8 = 6;
+
+// What happens to a frame's region before the next frame is drawn
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum DisposeOp {
+    None,
+    Background,
+    Previous,
+}
+
+// How a frame is combined with the output buffer
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum BlendOp {
+    Source,
+    Over,
+}
+
+pub struct Frame {
+    pub bitmap:    Bitmap,
+    pub x:         u32,
+    pub y:         u32,
+    pub delay_num: u16,
+    pub delay_den: u16,  // Zero means hundredths of a second
+    pub dispose:   DisposeOp,
+    pub blend:     BlendOp,
+}
+
+impl Frame {
+    pub fn new(bitmap: Bitmap, delay_num: u16, delay_den: u16) -> Frame {
+        Frame {
+            bitmap:    bitmap,
+            x:         0,
+            y:         0,
+            delay_num: delay_num,
+            delay_den: delay_den,
+            dispose:   DisposeOp::None,
+            blend:     BlendOp::Source,
+        }
+    }
+}
+
+pub fn write(bitmap: &Bitmap, path: &Path) -> io::Result<()> {
+    let mut file = BufWriter::new(File::create(path)?);
+    file.write_all(&encode(bitmap)?)?;
+    file.flush()
+}
+
+pub fn write_animation(frames: &[Frame], plays: u32, path: &Path) -> io::Result<()> {
+    let mut file = BufWriter::new(File::create(path)?);
+    file.write_all(&encode_animation(frames, plays)?)?;
+    file.flush()
+}
+
+pub fn encode(bitmap: &Bitmap) -> io::Result<Vec<u8>> {
+    check_size(bitmap.width, bitmap.height)?;
+
+    let mut out = SIGNATURE.to_vec();
+
+    write_chunk(&mut out, b"IHDR", &header(bitmap.width, bitmap.height));
+    write_chunk(&mut out, b"IDAT", &compress(bitmap));
+    write_chunk(&mut out, b"IEND", &[]);
+
+    Ok(out)
+}
+
+// The first frame doubles as the default image and must cover the whole
+// canvas. Later frames may be smaller and placed anywhere inside it.
+pub fn encode_animation(frames: &[Frame], plays: u32) -> io::Result<Vec<u8>> {
+    if frames.is_empty() {
+        return Err(invalid_input("no frames to encode"));
+    }
+
+    let (w, h) = (frames[0].bitmap.width, frames[0].bitmap.height);
+    if frames[0].x != 0 || frames[0].y != 0 {
+        return Err(invalid_input("first frame must cover the canvas"));
+    }
+    for frame in frames {
+        // fcTL requires every frame to have pixels
+        check_size(frame.bitmap.width, frame.bitmap.height)?;
+        if frame.x as usize + frame.bitmap.width  > w ||
+           frame.y as usize + frame.bitmap.height > h {
+            return Err(invalid_input("frame outside canvas"));
+        }
+    }
+
+    let mut out = SIGNATURE.to_vec();
+
+    write_chunk(&mut out, b"IHDR", &header(w, h));
+
+    let mut actl = Vec::with_capacity(8);
+    actl.extend_from_slice(&(frames.len() as u32).to_be_bytes());
+    actl.extend_from_slice(&plays.to_be_bytes());
+    write_chunk(&mut out, b"acTL", &actl);
+
+    // fcTL and fdAT chunks share one sequence
+    let mut sequence = 0u32;
+
+    for (i, frame) in frames.iter().enumerate() {
+        let mut fctl = Vec::with_capacity(26);
+        fctl.extend_from_slice(&sequence.to_be_bytes());
+        fctl.extend_from_slice(&(frame.bitmap.width  as u32).to_be_bytes());
+        fctl.extend_from_slice(&(frame.bitmap.height as u32).to_be_bytes());
+        fctl.extend_from_slice(&frame.x.to_be_bytes());
+        fctl.extend_from_slice(&frame.y.to_be_bytes());
+        fctl.extend_from_slice(&frame.delay_num.to_be_bytes());
+        fctl.extend_from_slice(&frame.delay_den.to_be_bytes());
+        fctl.push(frame.dispose as u8);
+        fctl.push(frame.blend as u8);
+        write_chunk(&mut out, b"fcTL", &fctl);
+        sequence += 1;
+
+        let data = compress(&frame.bitmap);
+        if i == 0 {
+            write_chunk(&mut out, b"IDAT", &data);
+        } else {
+            let mut fdat = Vec::with_capacity(4 + data.len());
+            fdat.extend_from_slice(&sequence.to_be_bytes());
+            fdat.extend_from_slice(&data);
+            write_chunk(&mut out, b"fdAT", &fdat);
+            sequence += 1;
+        }
+    }
+
+    write_chunk(&mut out, b"IEND", &[]);
+
+    Ok(out)
+}
+
+// Both dimensions must be from 1 to 2^31 - 1
+fn check_size(w: usize, h: usize) -> io::Result<()> {
+    let max = i32::MAX as usize;
+    if w == 0 || h == 0 {
+        return Err(invalid_input("PNG images cannot be empty"));
+    }
+    if w > max || h > max {
+        return Err(invalid_input("bitmap too large for PNG"));
+    }
+    Ok(())
+}
+
+fn header(w: usize, h: usize) -> Vec<u8> {
+    let mut ihdr = Vec::with_capacity(13);
+    ihdr.extend_from_slice(&(w as u32).to_be_bytes());
+    ihdr.extend_from_slice(&(h as u32).to_be_bytes());
+    ihdr.extend_from_slice(&[8, COLOR_RGBA, 0, 0, 0]);
+    ihdr
+}
+
+// Filters each straight-alpha RGBA row, then deflates the lot
+fn compress(bitmap: &Bitmap) -> Vec<u8> {
+    let (w, h) = (bitmap.width, bitmap.height);
+    let stride = w*4;
+
+    let mut raw = Vec::with_capacity((stride + 1)*h);
+    let mut prior = vec![0u8; stride];
+    let mut row = vec![0u8; stride];
+
+    for y in 0..h {
+        for x in 0..w {
+            let (a, r, g, b) = bitmap.get(x, y).unpremul_argb();
+            row[4*x..4*x + 4].copy_from_slice(&[r, g, b, a]);
+        }
+
+        let (filter, filtered) = best_filter(&row, &prior);
+        raw.push(filter);
+        raw.extend_from_slice(&filtered);
+
+        std::mem::swap(&mut row, &mut prior);
+    }
+
+    zlib::compress(&raw)
+}
+
+// Picks the filter minimizing the sum of absolute (signed) residuals
+fn best_filter(row: &[u8], prior: &[u8]) -> (u8, Vec<u8>) {
+    let mut best: Option<(u8, Vec<u8>, u64)> = None;
+
+    for filter in 0..5u8 {
+        let filtered: Vec<u8> = (0..row.len()).map(|i| {
+            let a = if i >= 4 {row[i - 4]} else {0};
+            let b = prior[i];
+            let c = if i >= 4 {prior[i - 4]} else {0};
+            let predictor = match filter {
+                0 => 0,
+                1 => a,
+                2 => b,
+                3 => ((a as u16 + b as u16) / 2) as u8,
+                _ => paeth(a, b, c),
+            };
+            row[i].wrapping_sub(predictor)
+        }).collect();
+
+        let cost = filtered.iter().map(|&v| (v as i8).unsigned_abs() as u64).sum();
+        let better = match best {
+            Some((_, _, best_cost)) => cost < best_cost,
+            None                    => true,
+        };
+        if better {
+            best = Some((filter, filtered, cost));
+        }
+    }
+
+    let (filter, filtered, _) = best.unwrap();
+    (filter, filtered)
+}
+
+fn paeth(a: u8, b: u8, c: u8) -> u8 {
+    let p  = a as i16 + b as i16 - c as i16;
+    let pa = (p - a as i16).abs();
+    let pb = (p - b as i16).abs();
+    let pc = (p - c as i16).abs();
+
+    if pa <= pb && pa <= pc {
+        a
+    } else if pb <= pc {
+        b
+    } else {
+        c
+    }
+}
+
+fn write_chunk(out: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    out.extend_from_slice(&(data.len() as u32).to_be_bytes());
+
+    let start = out.len();
+    out.extend_from_slice(kind);
+    out.extend_from_slice(data);
+
+    let crc = crc32(&out[start..]);
+    out.extend_from_slice(&crc.to_be_bytes());
+}
+
+pub fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = 0xffffffffu32;
+    for &byte in bytes {
+        crc = CRC_TABLE[((crc ^ byte as u32) & 0xff) as usize] ^ (crc >> 8);
+    }
+    !crc
+}
+
+// Built at compile time, for the reflected polynomial
+const CRC_TABLE: [u32; 256] = crc_table();
+
+const fn crc_table() -> [u32; 256] {
+    let mut table = [0u32; 256];
+    let mut n = 0;
+    while n < 256 {
+        let mut c = n as u32;
+        let mut k = 0;
+        while k < 8 {
+            c = if c & 1 != 0 {0xedb88320 ^ (c >> 1)} else {c >> 1};
+            k += 1;
+        }
+        table[n] = c;
+        n += 1;
+    }
+    table
+}
+
+fn invalid_input(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, msg)
+}
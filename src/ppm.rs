@@ -1,11 +1,125 @@
 use color::Color;
 use pixel::Pixel;
+use bitmap::Bitmap;
 
 use std::path::Path;
 use std::fs::File;
+use std::io;
 use std::io::prelude::*;
 use std::io::BufWriter;
 
+pub fn read(path: &Path) -> io::Result<Bitmap> {
+    let mut bytes = Vec::new();
+    File::open(path)?.read_to_end(&mut bytes)?;
+
+    decode(&bytes)
+}
+
+pub fn write(bitmap: &Bitmap, path: &Path) -> io::Result<()> {
+    let mut file = BufWriter::new(File::create(path)?);
+    file.write_all(&encode(bitmap))?;
+    file.flush()
+}
+
+// ASCII (P3), like PPMImage writes
+pub fn encode(bitmap: &Bitmap) -> Vec<u8> {
+    let mut out = format!("P3\n{} {} 255\n", bitmap.width, bitmap.height);
+    for px in &bitmap.pixels {
+        let (_, r, g, b) = px.unpremul_argb();
+        out.push_str(&format!("{} {} {}\n", r, g, b));
+    }
+
+    out.into_bytes()
+}
+
+// Accepts ASCII (P3) and binary (P6) pixmaps with any maxval up to 255
+pub fn decode(bytes: &[u8]) -> io::Result<Bitmap> {
+    let mut pos = 0;
+
+    let magic = header_token(bytes, &mut pos)?;
+    let binary = match magic {
+        b"P3" => false,
+        b"P6" => true,
+        _     => return Err(invalid("not a P3 or P6 PPM file")),
+    };
+
+    let width  = header_value(bytes, &mut pos)?;
+    let height = header_value(bytes, &mut pos)?;
+    let maxval = header_value(bytes, &mut pos)?;
+    if maxval == 0 || maxval > 255 {
+        return Err(invalid("unsupported PPM maxval"));
+    }
+
+    let count = match width.checked_mul(height) {
+        Some(count) if count.checked_mul(3).is_some() => count,
+        _                                             => return Err(invalid("PPM dimensions too large")),
+    };
+
+    let mut samples = Vec::with_capacity(count.min(bytes.len()) * 3);
+    if binary {
+        // A single whitespace byte separates the header from the raster
+        let start = pos + 1;
+        if start > bytes.len() || bytes.len() - start < count*3 {
+            return Err(invalid("truncated PPM data"));
+        }
+        samples.extend_from_slice(&bytes[start..start + count*3]);
+    } else {
+        for _ in 0..count*3 {
+            let v = header_value(bytes, &mut pos)?;
+            if v > maxval {
+                return Err(invalid("PPM sample exceeds maxval"));
+            }
+            samples.push(v as u8);
+        }
+    }
+
+    let scale = |v: u8| ((v as usize * 255 + maxval/2) / maxval) as u8;
+
+    let mut bitmap = Bitmap::new(width, height);
+    for (px, rgb) in bitmap.pixels.iter_mut().zip(samples.chunks(3)) {
+        *px = Pixel::pack_rgb(scale(rgb[0]), scale(rgb[1]), scale(rgb[2]));
+    }
+
+    Ok(bitmap)
+}
+
+// Next whitespace-separated token, skipping '#' comments
+fn header_token<'a>(bytes: &'a [u8], pos: &mut usize) -> io::Result<&'a [u8]> {
+    loop {
+        while *pos < bytes.len() && bytes[*pos].is_ascii_whitespace() {
+            *pos += 1;
+        }
+        if *pos < bytes.len() && bytes[*pos] == b'#' {
+            while *pos < bytes.len() && bytes[*pos] != b'\n' {
+                *pos += 1;
+            }
+            continue;
+        }
+        break;
+    }
+
+    let start = *pos;
+    while *pos < bytes.len() && !bytes[*pos].is_ascii_whitespace() {
+        *pos += 1;
+    }
+    if start == *pos {
+        return Err(invalid("truncated PPM file"));
+    }
+
+    Ok(&bytes[start..*pos])
+}
+
+fn header_value(bytes: &[u8], pos: &mut usize) -> io::Result<usize> {
+    let token = header_token(bytes, pos)?;
+    std::str::from_utf8(token).ok()
+        .and_then(|s| s.parse::<usize>().ok())
+        .ok_or_else(|| invalid("malformed PPM number"))
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
 pub struct PPMImage {
     pub width:  usize,
     pub height: usize,
@@ -62,12 +176,7 @@ impl PPMImage {
             let pixel = self.colors[i].to_pixel();
 
             // Undo premul
-            let (a, mut r, mut g, mut b) = (pixel.a, pixel.r, pixel.g, pixel.b);
-            if a != 0 && a != 255 {
-                r = ((r as i32 * 255 + a as i32/2) / a as i32) as u8;
-                g = ((g as i32 * 255 + a as i32/2) / a as i32) as u8;
-                b = ((b as i32 * 255 + a as i32/2) / a as i32) as u8;
-            }
+            let (_, r, g, b) = pixel.unpremul_argb();
 
             write!(file, "{} {} {}\n", r, g, b).unwrap();
         }
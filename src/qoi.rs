@@ -0,0 +1,198 @@
+use bitmap::Bitmap;
+use pixel::Pixel;
+
+use std::path::Path;
+use std::fs::File;
+use std::io;
+use std::io::prelude::*;
+use std::io::BufWriter;
+
+// See https://qoiformat.org/qoi-specification.pdf
+const HEADER_SIZE: usize = 14;
+const END_MARKER: [u8; 8] = [0, 0, 0, 0, 0, 0, 0, 1];
+
+const QOI_OP_INDEX: u8 = 0x00;
+const QOI_OP_DIFF:  u8 = 0x40;
+const QOI_OP_LUMA:  u8 = 0x80;
+const QOI_OP_RUN:   u8 = 0xc0;
+const QOI_OP_RGB:   u8 = 0xfe;
+const QOI_OP_RGBA:  u8 = 0xff;
+const QOI_MASK_2:   u8 = 0xc0;
+
+// Refuse headers claiming more than 400 million pixels, as the reference decoder does
+const MAX_PIXELS: usize = 400_000_000;
+
+// Unpremul channels, in (r, g, b, a) order
+type Rgba = [u8; 4];
+
+pub fn read(path: &Path) -> io::Result<Bitmap> {
+    let mut bytes = Vec::new();
+    File::open(path)?.read_to_end(&mut bytes)?;
+
+    decode(&bytes)
+}
+
+pub fn write(bitmap: &Bitmap, path: &Path) -> io::Result<()> {
+    let mut file = BufWriter::new(File::create(path)?);
+    file.write_all(&encode(bitmap))?;
+    file.flush()
+}
+
+pub fn decode(bytes: &[u8]) -> io::Result<Bitmap> {
+    if bytes.len() < HEADER_SIZE + END_MARKER.len() || &bytes[0..4] != b"qoif" {
+        return Err(invalid("not a QOI file"));
+    }
+
+    let w = get_u32_be(bytes, 4) as usize;
+    let h = get_u32_be(bytes, 8) as usize;
+    let channels = bytes[12];
+
+    if w == 0 || h == 0 || w.saturating_mul(h) > MAX_PIXELS {
+        return Err(invalid("invalid QOI dimensions"));
+    }
+    if channels != 3 && channels != 4 {
+        return Err(invalid("invalid QOI channel count"));
+    }
+
+    let mut bitmap = Bitmap::new(w, h);
+
+    let mut index = [[0u8; 4]; 64];
+    let mut px: Rgba = [0, 0, 0, 255];
+    let mut run = 0;
+
+    let data_end = bytes.len() - END_MARKER.len();
+    let mut pos = HEADER_SIZE;
+
+    for i in 0..w*h {
+        if run > 0 {
+            run -= 1;
+        } else {
+            if pos >= data_end {
+                return Err(invalid("truncated QOI data"));
+            }
+            let op = bytes[pos];
+            pos += 1;
+
+            if op == QOI_OP_RGB || op == QOI_OP_RGBA {
+                let len = if op == QOI_OP_RGB {3} else {4};
+                if pos + len > data_end {
+                    return Err(invalid("truncated QOI data"));
+                }
+                px[..len].copy_from_slice(&bytes[pos..pos + len]);
+                pos += len;
+            } else {
+                match op & QOI_MASK_2 {
+                    QOI_OP_INDEX => px = index[op as usize],
+                    QOI_OP_DIFF  => {
+                        px[0] = px[0].wrapping_add((op >> 4) & 0x03).wrapping_sub(2);
+                        px[1] = px[1].wrapping_add((op >> 2) & 0x03).wrapping_sub(2);
+                        px[2] = px[2].wrapping_add( op       & 0x03).wrapping_sub(2);
+                    },
+                    QOI_OP_LUMA  => {
+                        if pos >= data_end {
+                            return Err(invalid("truncated QOI data"));
+                        }
+                        let next = bytes[pos];
+                        pos += 1;
+
+                        let dg = (op & 0x3f).wrapping_sub(32);
+                        px[0] = px[0].wrapping_add(dg).wrapping_add(next >> 4).wrapping_sub(8);
+                        px[1] = px[1].wrapping_add(dg);
+                        px[2] = px[2].wrapping_add(dg).wrapping_add(next & 0x0f).wrapping_sub(8);
+                    },
+                    _ => run = op & 0x3f,
+                }
+            }
+
+            index[hash(&px)] = px;
+        }
+
+        bitmap.pixels[i] = Pixel::pack_unpremul_argb(px[3], px[0], px[1], px[2]);
+    }
+
+    Ok(bitmap)
+}
+
+pub fn encode(bitmap: &Bitmap) -> Vec<u8> {
+    let (w, h) = (bitmap.width, bitmap.height);
+
+    let mut out = Vec::with_capacity(HEADER_SIZE + w*h*5 + END_MARKER.len());
+
+    out.extend_from_slice(b"qoif");
+    put_u32_be(&mut out, w as u32);
+    put_u32_be(&mut out, h as u32);
+    out.push(4);  // RGBA
+    out.push(0);  // sRGB with linear alpha
+
+    let mut index = [[0u8; 4]; 64];
+    let mut prev: Rgba = [0, 0, 0, 255];
+    let mut run = 0;
+
+    for (i, pixel) in bitmap.pixels.iter().enumerate() {
+        let (a, r, g, b) = pixel.unpremul_argb();
+        let px = [r, g, b, a];
+
+        if px == prev {
+            run += 1;
+            if run == 62 || i == w*h - 1 {
+                out.push(QOI_OP_RUN | (run - 1));
+                run = 0;
+            }
+            continue;
+        }
+
+        if run > 0 {
+            out.push(QOI_OP_RUN | (run - 1));
+            run = 0;
+        }
+
+        let slot = hash(&px);
+        if index[slot] == px {
+            out.push(QOI_OP_INDEX | slot as u8);
+        } else {
+            index[slot] = px;
+
+            if px[3] == prev[3] {
+                let dr = px[0].wrapping_sub(prev[0]) as i8;
+                let dg = px[1].wrapping_sub(prev[1]) as i8;
+                let db = px[2].wrapping_sub(prev[2]) as i8;
+
+                let dr_dg = dr.wrapping_sub(dg);
+                let db_dg = db.wrapping_sub(dg);
+
+                if (-2..2).contains(&dr) && (-2..2).contains(&dg) && (-2..2).contains(&db) {
+                    out.push(QOI_OP_DIFF | ((dr + 2) as u8) << 4 | ((dg + 2) as u8) << 2 | (db + 2) as u8);
+                } else if (-32..32).contains(&dg) && (-8..8).contains(&dr_dg) && (-8..8).contains(&db_dg) {
+                    out.push(QOI_OP_LUMA | (dg + 32) as u8);
+                    out.push(((dr_dg + 8) as u8) << 4 | (db_dg + 8) as u8);
+                } else {
+                    out.extend_from_slice(&[QOI_OP_RGB, px[0], px[1], px[2]]);
+                }
+            } else {
+                out.extend_from_slice(&[QOI_OP_RGBA, px[0], px[1], px[2], px[3]]);
+            }
+        }
+
+        prev = px;
+    }
+
+    out.extend_from_slice(&END_MARKER);
+
+    out
+}
+
+fn hash(px: &Rgba) -> usize {
+    (px[0] as usize*3 + px[1] as usize*5 + px[2] as usize*7 + px[3] as usize*11) % 64
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+fn get_u32_be(bytes: &[u8], i: usize) -> u32 {
+    (bytes[i] as u32) << 24 | (bytes[i+1] as u32) << 16 | (bytes[i+2] as u32) << 8 | bytes[i+3] as u32
+}
+
+fn put_u32_be(out: &mut Vec<u8>, v: u32) {
+    out.extend_from_slice(&[(v >> 24) as u8, (v >> 16) as u8, (v >> 8) as u8, v as u8]);
+}
@@ -1,3 +1,4 @@
+#[derive(Copy, Clone, Debug)]
 pub struct Rect {
     left:   f32,
     top:    f32,
@@ -46,6 +47,21 @@ impl Rect {
         self.bottom += dy;
     }
 
+    pub fn join(&mut self, other: &Rect) {
+        if other.empty() {
+            return;
+        }
+        if self.empty() {
+            *self = *other;
+            return;
+        }
+
+        self.left   = self.left.min(other.left);
+        self.top    = self.top.min(other.top);
+        self.right  = self.right.max(other.right);
+        self.bottom = self.bottom.max(other.bottom);
+    }
+
     pub fn intersects(&self, other: &Rect) -> bool {
         let l = self.left.max(other.left);
         let t = self.top.max(other.top);
@@ -59,7 +75,7 @@ impl Rect {
         let t = self.top.max(other.top);
         let r = self.right.min(other.right);
         let b = self.bottom.min(other.bottom);
-    
+
         if l < r && t < b {
             self.left   = l;
             self.top    = t;
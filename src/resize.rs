@@ -0,0 +1,156 @@
+use bitmap::Bitmap;
+use pixel::Pixel;
+
+use std::f32::consts::PI;
+
+// Reconstruction filters for Bitmap::resize, from sharpest to smoothest
+// roughly Lanczos3, Mitchell, Triangle, Box
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Filter {
+    Box,       // Averages the source pixels each output pixel covers
+    Triangle,  // Bilinear when enlarging
+    Lanczos3,  // Windowed sinc; sharp, with slight ringing at hard edges
+    Mitchell,  // Cubic with B = C = 1/3; little ringing, a touch soft
+}
+
+impl Filter {
+    // How far from the center the filter is non-zero, in source pixels at
+    // a scale of 1
+    fn support(&self) -> f32 {
+        match *self {
+            Filter::Box      => 0.5,
+            Filter::Triangle => 1.0,
+            Filter::Lanczos3 => 3.0,
+            Filter::Mitchell => 2.0,
+        }
+    }
+
+    fn weight(&self, x: f32) -> f32 {
+        let x = x.abs();
+        match *self {
+            Filter::Box      => if x <= 0.5 { 1.0 } else { 0.0 },
+            Filter::Triangle => (1.0 - x).max(0.0),
+            Filter::Lanczos3 => if x < 3.0 { sinc(x) * sinc(x / 3.0) } else { 0.0 },
+            Filter::Mitchell => {
+                let (b, c) = (1.0/3.0, 1.0/3.0);
+                if x < 1.0 {
+                    ((12.0 - 9.0*b - 6.0*c)*x*x*x + (-18.0 + 12.0*b + 6.0*c)*x*x + (6.0 - 2.0*b)) / 6.0
+                } else if x < 2.0 {
+                    ((-b - 6.0*c)*x*x*x + (6.0*b + 30.0*c)*x*x + (-12.0*b - 48.0*c)*x + (8.0*b + 24.0*c)) / 6.0
+                } else {
+                    0.0
+                }
+            },
+        }
+    }
+}
+
+fn sinc(x: f32) -> f32 {
+    if x == 0.0 {
+        1.0
+    } else {
+        (PI * x).sin() / (PI * x)
+    }
+}
+
+// The source pixels contributing to one output pixel, from start on
+struct Taps {
+    start:   usize,
+    weights: Vec<f32>,
+}
+
+// Taps for every output pixel along an axis. Shrinking widens the filter
+// so every source pixel is covered; taps past the edges are dropped and
+// the rest renormalized, so flat colors stay flat up to the border.
+fn taps(filter: Filter, src: usize, dst: usize) -> Vec<Taps> {
+    let scale = src as f32 / dst as f32;
+    let stretch = scale.max(1.0);
+    let support = filter.support() * stretch;
+
+    (0..dst).map(|i| {
+        let center = (i as f32 + 0.5) * scale;
+        let start = (center - support).floor().max(0.0) as usize;
+        let end = ((center + support).ceil() as usize).min(src);
+
+        let mut weights: Vec<f32> = (start..end).map(|j| filter.weight((j as f32 + 0.5 - center) / stretch)).collect();
+        let total: f32 = weights.iter().sum();
+        if total.abs() > 1e-6 {
+            for w in weights.iter_mut() {
+                *w /= total;
+            }
+        } else {
+            // Too narrow to catch a sample; use the nearest pixel
+            let nearest = (center as usize).min(src - 1);
+            return Taps { start: nearest, weights: vec![1.0] };
+        }
+
+        Taps {
+            start:   start,
+            weights: weights,
+        }
+    }).collect()
+}
+
+type Channels = [f32; 4];  // a, r, g, b
+
+fn channels(px: &Pixel) -> Channels {
+    [px.a as f32, px.r as f32, px.g as f32, px.b as f32]
+}
+
+// Negative lobes can push channels out of range, and past alpha
+fn to_pixel(c: &Channels) -> Pixel {
+    let byte = |v: f32, hi: u8| (v + 0.5).max(0.0).min(hi as f32) as u8;
+    let a = byte(c[0], 255);
+    Pixel::pack_argb(a, byte(c[1], a), byte(c[2], a), byte(c[3], a))
+}
+
+fn accumulate(sum: &mut Channels, c: &Channels, w: f32) {
+    for i in 0..4 {
+        sum[i] += c[i] * w;
+    }
+}
+
+impl Bitmap {
+    // A resampled copy at width x height. Filters the premultiplied pixels,
+    // so transparent areas don't bleed their color into the edges, and
+    // filters across then down.
+    pub fn resize(&self, width: usize, height: usize, filter: Filter) -> Bitmap {
+        if width == 0 || height == 0 || self.width == 0 || self.height == 0 {
+            return Bitmap::new(width, height);
+        }
+
+        // Across into floats, keeping precision for the second pass
+        let across = taps(filter, self.width, width);
+        let mut mid = vec![[0.0; 4]; width * self.height];
+        for y in 0..self.height {
+            let row = &self.pixels[y*self.width .. (y + 1)*self.width];
+            for (x, tap) in across.iter().enumerate() {
+                let sum = &mut mid[y*width + x];
+                for (k, &w) in tap.weights.iter().enumerate() {
+                    accumulate(sum, &channels(&row[tap.start + k]), w);
+                }
+            }
+        }
+
+        // Then down, a whole row at a time so the reads stay in order
+        let down = taps(filter, self.height, height);
+        let mut bitmap = Bitmap::new(width, height);
+        let mut sums = vec![[0.0; 4]; width];
+        for (y, tap) in down.iter().enumerate() {
+            for sum in sums.iter_mut() {
+                *sum = [0.0; 4];
+            }
+            for (k, &w) in tap.weights.iter().enumerate() {
+                let src = &mid[(tap.start + k)*width .. (tap.start + k + 1)*width];
+                for (sum, c) in sums.iter_mut().zip(src) {
+                    accumulate(sum, c, w);
+                }
+            }
+            for (px, sum) in bitmap.pixels[y*width .. (y + 1)*width].iter_mut().zip(&sums) {
+                *px = to_pixel(sum);
+            }
+        }
+
+        bitmap
+    }
+}
@@ -0,0 +1,360 @@
+use bitmap::Bitmap;
+use color::Color;
+use rect::Rect;
+use point::Point;
+use matrix::Matrix;
+use canvas::Canvas;
+use context::DrawContext;
+use format::ImageFormat;
+use picture::{Picture, PictureRecorder};
+
+use std::path::Path;
+use std::fmt;
+use std::error::Error;
+
+// A declarative, line-based description of an image:
+//
+//   # Comments start with '#' followed by a space
+//   size 100 100                  canvas size, before any drawing
+//   format png                    output format (ppm by default)
+//   clear white
+//   translate 25 25
+//   rotate 22.5 about 50 50       degrees, optionally about a point
+//   scale 2 2
+//   concat 1 0 0 0 1 0            a b c d e f, or all nine of a 3x3
+//   save / restore
+//   clip 0 0 50 50                x y w h
+//   rect 0 0 50 50 #80ff0000      x y w h color
+//   polygon yellow 0 0 30 5 10 30 color x y x y x y ...
+//   bitmap spock.ppm 0 0 100 100  path x y w h, relative to the scene
+//
+// Colors are names (red, white, ...), #rrggbb, #aarrggbb or unit floats
+// written a,r,g,b (e.g. 0.5,1,0,0).
+pub struct Scene {
+    pub width:   usize,
+    pub height:  usize,
+    pub format:  ImageFormat,
+    pub picture: Picture,
+}
+
+// Largest width or height a scene may ask for; a full-size canvas is a GiB
+pub const MAX_SIZE: usize = 16384;
+
+#[derive(Debug)]
+pub struct SceneError {
+    pub line:    usize,
+    pub column:  usize,
+    pub message: String,
+}
+
+impl fmt::Display for SceneError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.line, self.column, self.message)
+    }
+}
+
+impl Error for SceneError {}
+
+impl Scene {
+    // Bitmap paths are resolved against base, usually the scene's directory
+    pub fn parse(text: &str, base: &Path) -> Result<Scene, SceneError> {
+        let mut size: Option<(usize, usize)> = None;
+        let mut format = ImageFormat::Ppm;
+        let mut recorder: Option<PictureRecorder> = None;
+        let mut depth = 0;
+
+        for (i, line) in text.lines().enumerate() {
+            let mut args = Args::new(line, i + 1);
+            let command = match args.next() {
+                Some(token) => token,
+                None        => continue,
+            };
+
+            if command.text == "size" {
+                if size.is_some() {
+                    return Err(args.error_at(&command, "size given more than once"));
+                }
+                let w = args.dimension()?;
+                let h = args.dimension()?;
+                args.finish()?;
+
+                size = Some((w, h));
+                recorder = Some(PictureRecorder::new(Rect::make_wh(w as f32, h as f32)));
+                continue;
+            }
+            if command.text == "format" {
+                let token = args.expect("an image format")?;
+                format = match ImageFormat::from_extension(token.text) {
+                    Some(format) => format,
+                    None         => return Err(args.error_at(&token, &format!("unknown image format '{}'", token.text))),
+                };
+                args.finish()?;
+                continue;
+            }
+
+            let ctx = match recorder {
+                Some(ref mut recorder) => recorder,
+                None                   => return Err(args.error_at(&command, "size must come before any drawing")),
+            };
+
+            match command.text {
+                "clear" => {
+                    let color = args.color()?;
+                    args.finish()?;
+                    ctx.clear(&color);
+                },
+                "save" => {
+                    args.finish()?;
+                    depth += 1;
+                    ctx.save();
+                },
+                "restore" => {
+                    args.finish()?;
+                    if depth == 0 {
+                        return Err(args.error_at(&command, "restore without matching save"));
+                    }
+                    depth -= 1;
+                    ctx.restore();
+                },
+                "translate" => {
+                    let (tx, ty) = (args.number()?, args.number()?);
+                    args.finish()?;
+                    ctx.translate(tx, ty);
+                },
+                "scale" => {
+                    let (sx, sy) = (args.number()?, args.number()?);
+                    args.finish()?;
+                    ctx.scale(sx, sy);
+                },
+                "rotate" => {
+                    let radians = args.number()?.to_radians();
+                    match args.next() {
+                        None => ctx.rotate(radians),
+                        Some(ref token) if token.text == "about" => {
+                            let center = Point::new(args.number()?, args.number()?);
+                            args.finish()?;
+                            ctx.rotate_about(radians, &center);
+                        },
+                        Some(token) => return Err(args.error_at(&token, "expected 'about' or end of line")),
+                    }
+                },
+                "concat" => {
+                    let mut mat = [0f32; 9];
+                    for v in mat[..6].iter_mut() {
+                        *v = args.number()?;
+                    }
+                    if args.more() {
+                        for v in mat[6..].iter_mut() {
+                            *v = args.number()?;
+                        }
+                        args.finish()?;
+                        ctx.concat_matrix(&Matrix::from_3x3(mat));
+                    } else {
+                        ctx.concat([mat[0], mat[1], mat[2], mat[3], mat[4], mat[5]]);
+                    }
+                },
+                "clip" => {
+                    let rect = args.rect()?;
+                    args.finish()?;
+                    ctx.clip_rect(&rect);
+                },
+                "rect" => {
+                    let rect  = args.rect()?;
+                    let color = args.color()?;
+                    args.finish()?;
+                    ctx.fill_rect(&rect, &color);
+                },
+                "polygon" => {
+                    let color = args.color()?;
+                    let mut points = Vec::new();
+                    while args.more() {
+                        points.push(Point::new(args.number()?, args.number()?));
+                    }
+                    if points.len() < 3 {
+                        return Err(args.error_at(&command, "polygon needs at least three points"));
+                    }
+                    ctx.fill_convex_polygon(&points, &color);
+                },
+                "bitmap" => {
+                    let token = args.expect("a bitmap path")?;
+                    let bitmap = match Bitmap::load(&base.join(token.text)) {
+                        Ok(bitmap) => bitmap,
+                        Err(err)   => return Err(args.error_at(&token, &format!("cannot load '{}': {}", token.text, err))),
+                    };
+                    let rect = args.rect()?;
+                    args.finish()?;
+                    ctx.fill_bitmap_rect(&bitmap, &rect);
+                },
+                _ => return Err(args.error_at(&command, &format!("unknown command '{}'", command.text))),
+            }
+        }
+
+        let ((width, height), recorder) = match (size, recorder) {
+            (Some(size), Some(recorder)) => (size, recorder),
+            _                            => return Err(SceneError {
+                line:    text.lines().count().max(1),
+                column:  1,
+                message: "scene has no size".to_string(),
+            }),
+        };
+
+        Ok(Scene {
+            width:   width,
+            height:  height,
+            format:  format,
+            picture: recorder.finish(),
+        })
+    }
+
+    pub fn render(&self) -> Bitmap {
+        let mut canvas = Canvas::new(Bitmap::new(self.width, self.height));
+        self.picture.playback(&mut canvas, [1.0, 0.0, 0.0, 0.0, 1.0, 0.0]);
+        canvas.bitmap().clone()
+    }
+}
+
+struct Token<'a> {
+    text:   &'a str,
+    column: usize,
+}
+
+// The whitespace-separated tokens of one line, minus any trailing comment
+struct Args<'a> {
+    tokens: Vec<Token<'a>>,
+    next:   usize,
+    line:   usize,
+    end:    usize,  // Column just past the last character
+}
+
+impl<'a> Args<'a> {
+    fn new(text: &'a str, line: usize) -> Args<'a> {
+        let mut tokens = Vec::new();
+        let mut start: Option<(usize, usize)> = None;  // Byte offset and column
+
+        let chars = text.char_indices().chain(Some((text.len(), ' ')));
+        for (column, (offset, c)) in (1..).zip(chars) {
+            if c.is_whitespace() {
+                if let Some((begin, col)) = start.take() {
+                    let token = &text[begin..offset];
+                    if is_comment(token) {
+                        break;
+                    }
+                    tokens.push(Token { text: token, column: col });
+                }
+            } else if start.is_none() {
+                start = Some((offset, column));
+            }
+        }
+
+        Args {
+            tokens: tokens,
+            next:   0,
+            line:   line,
+            end:    text.chars().count() + 1,
+        }
+    }
+
+    fn more(&self) -> bool {
+        self.next < self.tokens.len()
+    }
+
+    fn next(&mut self) -> Option<Token<'a>> {
+        let token = self.tokens.get(self.next).map(|t| Token { text: t.text, column: t.column });
+        self.next += 1;
+        token
+    }
+
+    fn error_at(&self, token: &Token, message: &str) -> SceneError {
+        SceneError {
+            line:    self.line,
+            column:  token.column,
+            message: message.to_string(),
+        }
+    }
+
+    fn expect(&mut self, what: &str) -> Result<Token<'a>, SceneError> {
+        match self.next() {
+            Some(token) => Ok(token),
+            None        => Err(SceneError {
+                line:    self.line,
+                column:  self.end,
+                message: format!("expected {}", what),
+            }),
+        }
+    }
+
+    fn finish(&mut self) -> Result<(), SceneError> {
+        match self.next() {
+            Some(token) => Err(self.error_at(&token, &format!("unexpected '{}'", token.text))),
+            None        => Ok(()),
+        }
+    }
+
+    fn number(&mut self) -> Result<f32, SceneError> {
+        let token = self.expect("a number")?;
+        match token.text.parse::<f32>() {
+            Ok(v) if v.is_finite() => Ok(v),
+            _                      => Err(self.error_at(&token, &format!("expected a number, found '{}'", token.text))),
+        }
+    }
+
+    fn dimension(&mut self) -> Result<usize, SceneError> {
+        let token = self.expect("a size")?;
+        match token.text.parse::<usize>() {
+            Ok(v) if v > 0 && v <= MAX_SIZE => Ok(v),
+            _                               => Err(self.error_at(&token, &format!("expected a size from 1 to {}, found '{}'", MAX_SIZE, token.text))),
+        }
+    }
+
+    fn rect(&mut self) -> Result<Rect, SceneError> {
+        Ok(Rect::make_xywh(self.number()?, self.number()?, self.number()?, self.number()?))
+    }
+
+    fn color(&mut self) -> Result<Color, SceneError> {
+        let token = self.expect("a color")?;
+        match parse_color(token.text) {
+            Some(color) => Ok(color),
+            None        => Err(self.error_at(&token, &format!("expected a color, found '{}'", token.text))),
+        }
+    }
+}
+
+// '#' followed by anything but a hex digit, so '#ff0000' stays a color
+fn is_comment(token: &str) -> bool {
+    let mut chars = token.chars();
+    chars.next() == Some('#') && !chars.next().is_some_and(|c| c.is_ascii_hexdigit())
+}
+
+fn parse_color(text: &str) -> Option<Color> {
+    match text {
+        "white"       => return Some(Color::white()),
+        "black"       => return Some(Color::black()),
+        "red"         => return Some(Color::red()),
+        "green"       => return Some(Color::green()),
+        "blue"        => return Some(Color::blue()),
+        "yellow"      => return Some(Color::yellow()),
+        "magenta"     => return Some(Color::magenta()),
+        "cyan"        => return Some(Color::cyan()),
+        "transparent" => return Some(Color::make_argb(0.0, 0.0, 0.0, 0.0)),
+        _             => {},
+    }
+
+    if text.contains(',') {
+        let floats: Vec<f32> = text.split(',').map(|v| v.parse::<f32>().ok()).collect::<Option<_>>()?;
+        if floats.len() != 4 || floats.iter().any(|v| !(0.0..=1.0).contains(v)) {
+            return None;
+        }
+        return Some(Color::make_argb(floats[0], floats[1], floats[2], floats[3]));
+    }
+
+    let hex = text.strip_prefix('#')?;
+    if (hex.len() != 6 && hex.len() != 8) || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+
+    let v = u32::from_str_radix(hex, 16).ok()?;
+    let channel = |shift: u32| ((v >> shift) & 0xff) as f32 / 255.0;
+    let a = if hex.len() == 8 { channel(24) } else { 1.0 };
+
+    Some(Color::make_argb(a, channel(16), channel(8), channel(0)))
+}
@@ -0,0 +1,431 @@
+use bitmap::Bitmap;
+use color::Color;
+use pixel::Pixel;
+use rect::Rect;
+use point::Point;
+use matrix::Matrix;
+use shader::ShaderDesc;
+use picture::{Picture, DrawOp};
+
+use std::path::Path;
+use std::fs::File;
+use std::io;
+use std::io::prelude::*;
+use std::io::BufWriter;
+use std::fmt::Write as FmtWrite;
+
+// Binary layout, all little-endian:
+//
+//   "DNKP" magic, u16 version, cull rect, varint op count, then the ops
+//
+// Each op is a one-byte tag followed by its operands. Floats are stored as
+// raw f32 bits. Coordinates and matrix entries must be finite and no larger
+// than MAX_VALUE, or the reader rejects the picture. Matrices are nine
+// floats, row-major.
+const MAGIC:   &[u8; 4] = b"DNKP";
+const VERSION: u16 = 1;
+
+const OP_CLEAR:                u8 = 0;
+const OP_FILL_RECT:            u8 = 1;
+const OP_FILL_CONVEX_POLYGON:  u8 = 2;
+const OP_FILL_BITMAP_RECT:     u8 = 3;
+const OP_SHADE_RECT:           u8 = 4;
+const OP_SHADE_CONVEX_POLYGON: u8 = 5;
+const OP_SAVE:                 u8 = 6;
+const OP_RESTORE:              u8 = 7;
+const OP_CONCAT:               u8 = 8;
+const OP_CLIP_RECT:            u8 = 9;
+
+// Far past anything the rasterizer can place, while leaving room to
+// multiply a few together without overflowing
+const MAX_VALUE: f32 = 1.0e9;
+
+const SHADER_COLOR:  u8 = 0;
+const SHADER_BITMAP: u8 = 1;
+
+pub fn read(path: &Path) -> io::Result<Picture> {
+    let mut bytes = Vec::new();
+    File::open(path)?.read_to_end(&mut bytes)?;
+
+    decode(&bytes)
+}
+
+pub fn write(picture: &Picture, path: &Path) -> io::Result<()> {
+    let mut file = BufWriter::new(File::create(path)?);
+    file.write_all(&encode(picture))?;
+    file.flush()
+}
+
+pub fn encode(picture: &Picture) -> Vec<u8> {
+    let mut out = Vec::new();
+
+    out.extend_from_slice(MAGIC);
+    out.extend_from_slice(&VERSION.to_le_bytes());
+    put_rect(&mut out, &picture.cull_rect());
+    put_varint(&mut out, picture.ops().len());
+
+    for op in picture.ops() {
+        match *op {
+            DrawOp::Clear(ref color) => {
+                out.push(OP_CLEAR);
+                put_color(&mut out, color);
+            },
+            DrawOp::FillRect(ref rect, ref color) => {
+                out.push(OP_FILL_RECT);
+                put_rect(&mut out, rect);
+                put_color(&mut out, color);
+            },
+            DrawOp::FillConvexPolygon(ref points, ref color) => {
+                out.push(OP_FILL_CONVEX_POLYGON);
+                put_points(&mut out, points);
+                put_color(&mut out, color);
+            },
+            DrawOp::FillBitmapRect(ref bitmap, ref rect) => {
+                out.push(OP_FILL_BITMAP_RECT);
+                put_bitmap(&mut out, bitmap);
+                put_rect(&mut out, rect);
+            },
+            DrawOp::ShadeRect(ref rect, ref desc) => {
+                out.push(OP_SHADE_RECT);
+                put_rect(&mut out, rect);
+                put_shader(&mut out, desc);
+            },
+            DrawOp::ShadeConvexPolygon(ref points, ref desc) => {
+                out.push(OP_SHADE_CONVEX_POLYGON);
+                put_points(&mut out, points);
+                put_shader(&mut out, desc);
+            },
+            DrawOp::Save    => out.push(OP_SAVE),
+            DrawOp::Restore => out.push(OP_RESTORE),
+            DrawOp::Concat(ref mat) => {
+                out.push(OP_CONCAT);
+                put_floats(&mut out, &mat.get_floats_3x3());
+            },
+            DrawOp::ClipRect(ref rect) => {
+                out.push(OP_CLIP_RECT);
+                put_rect(&mut out, rect);
+            },
+        }
+    }
+
+    out
+}
+
+pub fn decode(bytes: &[u8]) -> io::Result<Picture> {
+    let mut reader = Reader::new(bytes);
+
+    if reader.bytes(4)? != MAGIC {
+        return Err(invalid("not a dinky picture"));
+    }
+    let version = reader.u16()?;
+    if version != VERSION {
+        return Err(invalid(&format!("unsupported picture version {}", version)));
+    }
+
+    let cull  = reader.rect()?;
+    let count = reader.varint()?;
+
+    // Every op takes at least its tag byte
+    if count > reader.remaining() {
+        return Err(invalid("picture op count exceeds data"));
+    }
+
+    let mut ops = Vec::with_capacity(count);
+    let mut depth = 0usize;
+
+    for _ in 0..count {
+        let op = match reader.u8()? {
+            OP_CLEAR                => DrawOp::Clear(reader.color()?),
+            OP_FILL_RECT            => DrawOp::FillRect(reader.rect()?, reader.color()?),
+            OP_FILL_CONVEX_POLYGON  => DrawOp::FillConvexPolygon(reader.points()?, reader.color()?),
+            OP_FILL_BITMAP_RECT     => DrawOp::FillBitmapRect(reader.bitmap()?, reader.rect()?),
+            OP_SHADE_RECT           => DrawOp::ShadeRect(reader.rect()?, reader.shader()?),
+            OP_SHADE_CONVEX_POLYGON => DrawOp::ShadeConvexPolygon(reader.points()?, reader.shader()?),
+            OP_SAVE                 => {
+                depth += 1;
+                DrawOp::Save
+            },
+            OP_RESTORE              => {
+                if depth == 0 {
+                    return Err(invalid("picture restores without a matching save"));
+                }
+                depth -= 1;
+                DrawOp::Restore
+            },
+            OP_CONCAT               => DrawOp::Concat(reader.matrix()?),
+            OP_CLIP_RECT            => DrawOp::ClipRect(reader.rect()?),
+            tag                     => return Err(invalid(&format!("unknown picture op {}", tag))),
+        };
+        ops.push(op);
+    }
+
+    // Playback must leave the caller's canvas as it found it
+    if depth != 0 {
+        return Err(invalid("picture saves without a matching restore"));
+    }
+    if reader.remaining() != 0 {
+        return Err(invalid("trailing bytes after picture"));
+    }
+
+    Ok(Picture::new(ops, cull))
+}
+
+// Human-readable listing, indented by save depth
+pub fn dump(picture: &Picture) -> String {
+    let mut out = String::new();
+
+    let cull = picture.cull_rect();
+    writeln!(out, "picture v{} cull {} ops {}", VERSION, fmt_rect(&cull), picture.ops().len()).unwrap();
+
+    let mut depth = 0usize;
+    for (i, op) in picture.ops().iter().enumerate() {
+        // Pictures built by hand may restore more than they save
+        if let DrawOp::Restore = *op {
+            depth = depth.saturating_sub(1);
+        }
+
+        let text = match *op {
+            DrawOp::Clear(ref color)                      => format!("clear {}", fmt_color(color)),
+            DrawOp::FillRect(ref rect, ref color)         => format!("fill_rect {} {}", fmt_rect(rect), fmt_color(color)),
+            DrawOp::FillConvexPolygon(ref points, ref c)  => format!("fill_convex_polygon {} {}", fmt_points(points), fmt_color(c)),
+            DrawOp::FillBitmapRect(ref bitmap, ref rect)  => format!("fill_bitmap_rect {} {}", fmt_bitmap(bitmap), fmt_rect(rect)),
+            DrawOp::ShadeRect(ref rect, ref desc)         => format!("shade_rect {} {}", fmt_rect(rect), fmt_shader(desc)),
+            DrawOp::ShadeConvexPolygon(ref points, ref d) => format!("shade_convex_polygon {} {}", fmt_points(points), fmt_shader(d)),
+            DrawOp::Save                                  => "save".to_string(),
+            DrawOp::Restore                               => "restore".to_string(),
+            DrawOp::Concat(ref mat)                       => format!("concat {}", fmt_matrix(mat)),
+            DrawOp::ClipRect(ref rect)                    => format!("clip_rect {}", fmt_rect(rect)),
+        };
+        writeln!(out, "{:4}: {}{}", i, "  ".repeat(depth), text).unwrap();
+
+        if let DrawOp::Save = *op {
+            depth += 1;
+        }
+    }
+
+    out
+}
+
+fn fmt_floats(floats: &[f32]) -> String {
+    let floats: Vec<String> = floats.iter().map(|f| f.to_string()).collect();
+    format!("[{}]", floats.join(" "))
+}
+
+// Affine matrices keep their familiar six-float form
+fn fmt_matrix(mat: &Matrix) -> String {
+    if mat.is_perspective() {
+        fmt_floats(&mat.get_floats_3x3())
+    } else {
+        fmt_floats(&mat.get_floats())
+    }
+}
+
+fn fmt_rect(rect: &Rect) -> String {
+    fmt_floats(&[rect.left(), rect.top(), rect.right(), rect.bottom()])
+}
+
+fn fmt_color(color: &Color) -> String {
+    format!("argb({} {} {} {})", color.a, color.r, color.g, color.b)
+}
+
+fn fmt_points(points: &[Point]) -> String {
+    let points: Vec<String> = points.iter().map(|p| format!("({} {})", p.x, p.y)).collect();
+    format!("[{}]", points.join(" "))
+}
+
+fn fmt_bitmap(bitmap: &Bitmap) -> String {
+    format!("bitmap({}x{})", bitmap.width, bitmap.height)
+}
+
+fn fmt_shader(desc: &ShaderDesc) -> String {
+    match *desc {
+        ShaderDesc::Color(ref color)              => format!("color_shader {}", fmt_color(color)),
+        ShaderDesc::Bitmap(ref bitmap, ref local) => format!("bitmap_shader {} {}", fmt_bitmap(bitmap),
+                                                             fmt_matrix(local)),
+    }
+}
+
+fn put_varint(out: &mut Vec<u8>, mut v: usize) {
+    while v >= 0x80 {
+        out.push(v as u8 | 0x80);
+        v >>= 7;
+    }
+    out.push(v as u8);
+}
+
+fn put_floats(out: &mut Vec<u8>, floats: &[f32]) {
+    for f in floats {
+        out.extend_from_slice(&f.to_le_bytes());
+    }
+}
+
+fn put_rect(out: &mut Vec<u8>, rect: &Rect) {
+    put_floats(out, &[rect.left(), rect.top(), rect.right(), rect.bottom()]);
+}
+
+fn put_color(out: &mut Vec<u8>, color: &Color) {
+    put_floats(out, &[color.a, color.r, color.g, color.b]);
+}
+
+fn put_points(out: &mut Vec<u8>, points: &[Point]) {
+    put_varint(out, points.len());
+    for p in points {
+        put_floats(out, &[p.x, p.y]);
+    }
+}
+
+fn put_bitmap(out: &mut Vec<u8>, bitmap: &Bitmap) {
+    put_varint(out, bitmap.width);
+    put_varint(out, bitmap.height);
+    for px in &bitmap.pixels {
+        out.extend_from_slice(&[px.a, px.r, px.g, px.b]);
+    }
+}
+
+fn put_shader(out: &mut Vec<u8>, desc: &ShaderDesc) {
+    match *desc {
+        ShaderDesc::Color(ref color) => {
+            out.push(SHADER_COLOR);
+            put_color(out, color);
+        },
+        ShaderDesc::Bitmap(ref bitmap, ref local) => {
+            out.push(SHADER_BITMAP);
+            put_bitmap(out, bitmap);
+            put_floats(out, &local.get_floats_3x3());
+        },
+    }
+}
+
+// False for NaN and infinities too
+fn in_range(v: f32) -> bool {
+    v.abs() <= MAX_VALUE
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+// Bounds-checked cursor; every read fails cleanly on truncated input
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos:   usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(bytes: &'a [u8]) -> Reader<'a> {
+        Reader {
+            bytes: bytes,
+            pos:   0,
+        }
+    }
+
+    fn remaining(&self) -> usize {
+        self.bytes.len() - self.pos
+    }
+
+    fn bytes(&mut self, len: usize) -> io::Result<&'a [u8]> {
+        if len > self.remaining() {
+            return Err(invalid("truncated picture"));
+        }
+        let bytes = &self.bytes[self.pos..self.pos + len];
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> io::Result<u8> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn u16(&mut self) -> io::Result<u16> {
+        let b = self.bytes(2)?;
+        Ok(u16::from_le_bytes([b[0], b[1]]))
+    }
+
+    fn f32(&mut self) -> io::Result<f32> {
+        let b = self.bytes(4)?;
+        Ok(f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    fn varint(&mut self) -> io::Result<usize> {
+        let mut v = 0usize;
+        for shift in (0..64).step_by(7) {
+            let byte = self.u8()?;
+            v |= ((byte & 0x7f) as usize) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(v);
+            }
+        }
+        Err(invalid("malformed varint"))
+    }
+
+    fn coord(&mut self) -> io::Result<f32> {
+        let v = self.f32()?;
+        if !in_range(v) {
+            return Err(invalid("picture coordinate out of range"));
+        }
+        Ok(v)
+    }
+
+    fn rect(&mut self) -> io::Result<Rect> {
+        Ok(Rect::make_ltrb(self.coord()?, self.coord()?, self.coord()?, self.coord()?))
+    }
+
+    fn color(&mut self) -> io::Result<Color> {
+        Ok(Color::make_argb(self.f32()?, self.f32()?, self.f32()?, self.f32()?))
+    }
+
+    fn matrix(&mut self) -> io::Result<Matrix> {
+        let mut mat = [0f32; 9];
+        for v in mat.iter_mut() {
+            *v = self.f32()?;
+            if !in_range(*v) {
+                return Err(invalid("picture matrix out of range"));
+            }
+        }
+        Ok(Matrix::from_3x3(mat))
+    }
+
+    fn points(&mut self) -> io::Result<Vec<Point>> {
+        let count = self.varint()?;
+        if count > self.remaining() / 8 {
+            return Err(invalid("truncated picture"));
+        }
+
+        let mut points = Vec::with_capacity(count);
+        for _ in 0..count {
+            points.push(Point::new(self.coord()?, self.coord()?));
+        }
+        Ok(points)
+    }
+
+    fn bitmap(&mut self) -> io::Result<Bitmap> {
+        let w = self.varint()?;
+        let h = self.varint()?;
+        if w == 0 || h == 0 {
+            return Err(invalid("empty bitmap"));
+        }
+        let len = match w.checked_mul(h).and_then(|n| n.checked_mul(4)) {
+            Some(len) => len,
+            None      => return Err(invalid("bitmap too large")),
+        };
+
+        let data = self.bytes(len)?;
+
+        let mut bitmap = Bitmap::new(w, h);
+        for (px, argb) in bitmap.pixels.iter_mut().zip(data.chunks(4)) {
+            if argb[1] > argb[0] || argb[2] > argb[0] || argb[3] > argb[0] {
+                return Err(invalid("bitmap pixel is not premultiplied"));
+            }
+            *px = Pixel::pack_argb(argb[0], argb[1], argb[2], argb[3]);
+        }
+        Ok(bitmap)
+    }
+
+    fn shader(&mut self) -> io::Result<ShaderDesc> {
+        match self.u8()? {
+            SHADER_COLOR  => Ok(ShaderDesc::Color(self.color()?)),
+            SHADER_BITMAP => Ok(ShaderDesc::Bitmap(self.bitmap()?, self.matrix()?)),
+            tag           => Err(invalid(&format!("unknown shader type {}", tag))),
+        }
+    }
+}
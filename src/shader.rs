@@ -1,35 +1,67 @@
 use pixel::Pixel;
 use color::Color;
 use point::Point;
-use matrix::Matrix;
+use matrix::{Matrix, MatrixType};
 use bitmap::Bitmap;
 use util::clamp;
+use simd;
 
 // Functions shared by all shaders
 pub trait Shader {
-    fn shade_row(&self, x: usize, y: usize, count: usize) -> Vec<Pixel>;
-    fn set_context(&mut self, ctm: [f32; 6]) -> bool;
+    // Fills row with the pixels for device pixels x.. x + row.len() on row y
+    fn shade_row(&self, x: usize, y: usize, row: &mut [Pixel]);
+    // False if the shader can't be used under this CTM, and the draw
+    // should be skipped
+    fn set_context(&mut self, ctm: &Matrix) -> bool;
+    fn desc(&self) -> ShaderDesc;
+
+    // Fast-path hints, valid after set_context: the one pixel the shader
+    // produces everywhere, or an unscaled bitmap it reads from at a whole
+    // pixel offset (device x + dx, y + dy)
+    fn solid_pixel(&self) -> Option<Pixel> {
+        None
+    }
+    fn offset_bitmap(&self) -> Option<(&Bitmap, i64, i64)> {
+        None
+    }
+}
+
+// Plain-data description of a shader, so draws using it can be recorded
+#[derive(Clone)]
+pub enum ShaderDesc {
+    Color(Color),
+    Bitmap(Bitmap, Matrix),
 }
 
 // Color shader
 pub struct ColorShader {
-    src: Pixel,
+    color: Color,
+    src:   Pixel,
 }
 impl ColorShader {
     pub fn new(src: Color) -> ColorShader {
         ColorShader {
-            src: src.to_pixel(),
-        } 
+            color: src,
+            src:   src.to_pixel(),
+        }
     }
 }
 impl Shader for ColorShader {
-    fn shade_row(&self, _x: usize, _y: usize, count: usize) -> Vec<Pixel> {
-        vec![self.src; count]
+    fn shade_row(&self, _x: usize, _y: usize, row: &mut [Pixel]) {
+        row.fill(self.src);
     }
 
-    fn set_context(&mut self, _ctm: [f32; 6]) -> bool {
+    fn set_context(&mut self, _ctm: &Matrix) -> bool {
         true
     }
+
+    fn desc(&self) -> ShaderDesc {
+        ShaderDesc::Color(self.color)
+    }
+
+    fn solid_pixel(&self) -> Option<Pixel> {
+        Some(self.src)
+    }
 }
 
 // Bitmap shader
@@ -46,37 +78,72 @@ impl BitmapShader {
             xform: Matrix::identity(),
         }
     }
+
+    // Divides through by w per pixel
+    fn shade_row_perspective(&self, x: usize, y: usize, row: &mut [Pixel]) {
+        let w_1 = self.src.width  as f32 - 1.0;
+        let h_1 = self.src.height as f32 - 1.0;
+
+        for (n, px) in row.iter_mut().enumerate() {
+            let [lx, ly, lw] = self.xform.apply_homogeneous(&Point::new((x + n) as f32 + 0.5, y as f32 + 0.5));
+
+            let lookup_x = clamp(0.0, lx / lw, w_1) as usize;
+            let lookup_y = clamp(0.0, ly / lw, h_1) as usize;
+
+            *px = self.src.get(lookup_x, lookup_y);
+        }
+    }
 }
 impl Shader for BitmapShader {
-    fn shade_row(&self, x: usize, y: usize, count: usize) -> Vec<Pixel> {
-        let start = Point::new(x as f32 + 0.5, y as f32 + 0.5);
-        let mut lookup = self.xform.apply(&start);
+    fn shade_row(&self, x: usize, y: usize, row: &mut [Pixel]) {
+        if self.xform.is_perspective() {
+            return self.shade_row_perspective(x, y, row);
+        }
 
         let w_1 = self.src.width  as f32 - 1.0;
         let h_1 = self.src.height as f32 - 1.0;
 
-        let a = self.xform.at(0);
-        let d = self.xform.at(3);
+        let done = simd::sample_row(simd::level(), &self.xform, &self.src, x, y, row);
 
-        let mut res = Vec::with_capacity(count);
+        // Mapped per pixel rather than stepped, so a pixel samples the same
+        // texel whichever span it is drawn in
+        for (n, px) in row.iter_mut().enumerate().skip(done) {
+            let lookup = self.xform.apply(&Point::new((x + n) as f32 + 0.5, y as f32 + 0.5));
 
-        for _ in 0..count {
             let lookup_x = clamp(0.0, lookup.x, w_1) as usize;
             let lookup_y = clamp(0.0, lookup.y, h_1) as usize;
 
-            res.push(self.src.get(lookup_x, lookup_y));
+            *px = self.src.get(lookup_x, lookup_y);
+        }
+    }
 
-            lookup.x += a;
-            lookup.y += d;
+    // Fails when the bitmap is empty or collapses to nothing, e.g. under
+    // scale(0, 1)
+    fn set_context(&mut self, ctm: &Matrix) -> bool {
+        if self.src.width == 0 || self.src.height == 0 {
+            return false;
         }
+        match ctm.mul(&self.local).invert() {
+            Some(xform) => {
+                self.xform = xform;
+                true
+            },
+            None => false,
+        }
+    }
 
-        res
+    fn desc(&self) -> ShaderDesc {
+        ShaderDesc::Bitmap(self.src.clone(), self.local)
     }
 
-    fn set_context(&mut self, ctm: [f32; 6]) -> bool {
-        let ctm = Matrix::new(ctm);
-        self.xform = (ctm.mul(&self.local)).inv();
-        true
+    // Sampling at pixel centers, a whole-pixel translate lands exactly on
+    // source pixels
+    fn offset_bitmap(&self) -> Option<(&Bitmap, i64, i64)> {
+        let (dx, dy) = (self.xform.at(2), self.xform.at(5));
+        if self.xform.get_type() > MatrixType::Translate || dx.fract() != 0.0 || dy.fract() != 0.0 {
+            return None;
+        }
+        Some((&self.src, dx as i64, dy as i64))
     }
 }
 
@@ -90,4 +157,11 @@ impl Shaders {
     pub fn from_bitmap(bitmap: &Bitmap, local: [f32; 6]) -> BitmapShader {
         BitmapShader::new(bitmap, Matrix::new(local))
     }
+
+    pub fn from_desc(desc: &ShaderDesc) -> Box<dyn Shader> {
+        match *desc {
+            ShaderDesc::Color(color)              => Box::new(Shaders::from_color(color)),
+            ShaderDesc::Bitmap(ref bitmap, local) => Box::new(BitmapShader::new(bitmap, local)),
+        }
+    }
 }
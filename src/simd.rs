@@ -0,0 +1,256 @@
+use pixel::Pixel;
+use bitmap::Bitmap;
+use matrix::Matrix;
+
+#[cfg(target_arch = "x86")]
+use std::arch::x86::*;
+#[cfg(target_arch = "x86_64")]
+use std::arch::x86_64::*;
+
+use std::cmp::min;
+use std::sync::OnceLock;
+
+// Vector versions of the per-pixel loops in blending and BitmapShader. Each
+// handles whole vectors from the front of a row and returns how many pixels
+// it did; callers finish the rest with the scalar code, which the vector
+// code matches bit for bit. Passing a level above what the CPU supports is
+// safe, it is lowered to level().
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    Scalar,
+    Sse2,
+    Avx2,
+}
+
+// The best level this CPU supports, detected once
+pub fn level() -> Level {
+    static LEVEL: OnceLock<Level> = OnceLock::new();
+    *LEVEL.get_or_init(detect)
+}
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+fn detect() -> Level {
+    if is_x86_feature_detected!("avx2") {
+        Level::Avx2
+    } else if is_x86_feature_detected!("sse2") {
+        Level::Sse2
+    } else {
+        Level::Scalar
+    }
+}
+
+#[cfg(not(any(target_arch = "x86", target_arch = "x86_64")))]
+fn detect() -> Level {
+    Level::Scalar
+}
+
+// Blends src over dst, like util::blend
+pub fn blend_row(level: Level, src: &[Pixel], dst: &mut [Pixel]) -> usize {
+    let len = min(src.len(), dst.len());
+    let (src, dst) = (&src[..len], &mut dst[..len]);
+
+    match min(level, self::level()) {
+        #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+        Level::Avx2 => unsafe { blend_row_avx2(src, dst) },
+        #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+        Level::Sse2 => unsafe { blend_row_sse2(src, dst) },
+        _           => 0,
+    }
+}
+
+// Blends one pixel over all of dst
+pub fn blend_color(level: Level, src: Pixel, dst: &mut [Pixel]) -> usize {
+    match min(level, self::level()) {
+        #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+        Level::Avx2 => unsafe { blend_color_avx2(src, dst) },
+        #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+        Level::Sse2 => unsafe { blend_color_sse2(src, dst) },
+        _           => 0,
+    }
+}
+
+// Samples src at the centers of pixels x.. on row y, mapped to it by the
+// affine xform and clamped to its edges, like BitmapShader
+pub fn sample_row(level: Level, xform: &Matrix, src: &Bitmap, x: usize, y: usize, row: &mut [Pixel]) -> usize {
+    // Coordinates must convert to f32 and indices to i32 exactly, and an
+    // empty bitmap has nothing to gather from
+    let exact = 1 << 24;
+    if src.pixels.is_empty() || xform.is_perspective() || x + row.len() > exact || y > exact || src.pixels.len() > i32::MAX as usize {
+        return 0;
+    }
+
+    match min(level, self::level()) {
+        #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+        Level::Avx2 => unsafe { sample_row_avx2(xform, src, x, y, row) },
+        #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+        Level::Sse2 => unsafe { sample_row_sse2(xform, src, x, y, row) },
+        _           => 0,
+    }
+}
+
+// Alpha is the last byte in either pixel order, so once widened it is the
+// fourth 16-bit word of each pixel. Shuffles copy it to all four.
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+const ALPHA: i32 = 0b11_11_11_11;
+
+fn pixel_bits(px: Pixel) -> i32 {
+    px.to_u32() as i32
+}
+
+// dst * (255 - src alpha) / 255 for two pixels widened to 16-bit lanes.
+// (x + 128 + ((x + 128) >> 8)) >> 8 rounds exactly like util::blend's
+// magic multiply for every product of two bytes, and fits in 16 bits.
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+#[inline]
+#[target_feature(enable = "sse2")]
+unsafe fn scale_dst_sse2(src: __m128i, dst: __m128i) -> __m128i {
+    let alpha = _mm_shufflehi_epi16(_mm_shufflelo_epi16(src, ALPHA), ALPHA);
+    let x = _mm_mullo_epi16(dst, _mm_sub_epi16(_mm_set1_epi16(255), alpha));
+    let x = _mm_add_epi16(x, _mm_set1_epi16(128));
+    _mm_srli_epi16(_mm_add_epi16(x, _mm_srli_epi16(x, 8)), 8)
+}
+
+// Four pixels at a time. Premultiplied channels never exceed alpha, so the
+// final add can't carry out of a byte.
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+#[inline]
+#[target_feature(enable = "sse2")]
+unsafe fn blend_sse2(src: __m128i, dst: __m128i) -> __m128i {
+    let zero = _mm_setzero_si128();
+    let lo = scale_dst_sse2(_mm_unpacklo_epi8(src, zero), _mm_unpacklo_epi8(dst, zero));
+    let hi = scale_dst_sse2(_mm_unpackhi_epi8(src, zero), _mm_unpackhi_epi8(dst, zero));
+    _mm_add_epi8(src, _mm_packus_epi16(lo, hi))
+}
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+#[target_feature(enable = "sse2")]
+unsafe fn blend_row_sse2(src: &[Pixel], dst: &mut [Pixel]) -> usize {
+    let done = src.len() / 4 * 4;
+    for i in (0..done).step_by(4) {
+        let s = _mm_loadu_si128(src.as_ptr().add(i) as *const __m128i);
+        let d = _mm_loadu_si128(dst.as_ptr().add(i) as *const __m128i);
+        _mm_storeu_si128(dst.as_mut_ptr().add(i) as *mut __m128i, blend_sse2(s, d));
+    }
+    done
+}
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+#[target_feature(enable = "sse2")]
+unsafe fn blend_color_sse2(src: Pixel, dst: &mut [Pixel]) -> usize {
+    let s = _mm_set1_epi32(pixel_bits(src));
+    let done = dst.len() / 4 * 4;
+    for i in (0..done).step_by(4) {
+        let d = _mm_loadu_si128(dst.as_ptr().add(i) as *const __m128i);
+        _mm_storeu_si128(dst.as_mut_ptr().add(i) as *mut __m128i, blend_sse2(s, d));
+    }
+    done
+}
+
+// The AVX2 versions do the same eight pixels at a time. The unpacks,
+// shuffles and pack all work within 128-bit lanes, so pixel order comes
+// out as it went in.
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+#[inline]
+#[target_feature(enable = "avx2")]
+unsafe fn scale_dst_avx2(src: __m256i, dst: __m256i) -> __m256i {
+    let alpha = _mm256_shufflehi_epi16(_mm256_shufflelo_epi16(src, ALPHA), ALPHA);
+    let x = _mm256_mullo_epi16(dst, _mm256_sub_epi16(_mm256_set1_epi16(255), alpha));
+    let x = _mm256_add_epi16(x, _mm256_set1_epi16(128));
+    _mm256_srli_epi16(_mm256_add_epi16(x, _mm256_srli_epi16(x, 8)), 8)
+}
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+#[inline]
+#[target_feature(enable = "avx2")]
+unsafe fn blend_avx2(src: __m256i, dst: __m256i) -> __m256i {
+    let zero = _mm256_setzero_si256();
+    let lo = scale_dst_avx2(_mm256_unpacklo_epi8(src, zero), _mm256_unpacklo_epi8(dst, zero));
+    let hi = scale_dst_avx2(_mm256_unpackhi_epi8(src, zero), _mm256_unpackhi_epi8(dst, zero));
+    _mm256_add_epi8(src, _mm256_packus_epi16(lo, hi))
+}
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+#[target_feature(enable = "avx2")]
+unsafe fn blend_row_avx2(src: &[Pixel], dst: &mut [Pixel]) -> usize {
+    let done = src.len() / 8 * 8;
+    for i in (0..done).step_by(8) {
+        let s = _mm256_loadu_si256(src.as_ptr().add(i) as *const __m256i);
+        let d = _mm256_loadu_si256(dst.as_ptr().add(i) as *const __m256i);
+        _mm256_storeu_si256(dst.as_mut_ptr().add(i) as *mut __m256i, blend_avx2(s, d));
+    }
+    done + blend_row_sse2(&src[done..], &mut dst[done..])
+}
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+#[target_feature(enable = "avx2")]
+unsafe fn blend_color_avx2(src: Pixel, dst: &mut [Pixel]) -> usize {
+    let s = _mm256_set1_epi32(pixel_bits(src));
+    let done = dst.len() / 8 * 8;
+    for i in (0..done).step_by(8) {
+        let d = _mm256_loadu_si256(dst.as_ptr().add(i) as *const __m256i);
+        _mm256_storeu_si256(dst.as_mut_ptr().add(i) as *mut __m256i, blend_avx2(s, d));
+    }
+    done + blend_color_sse2(src, &mut dst[done..])
+}
+
+// Lookups are computed as ((a*x + b*y) + c) with the same f32 roundings
+// as Matrix::apply, and clamped with min then max so NaN ends up at the
+// far edge just like f32::min does
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+#[target_feature(enable = "sse2")]
+unsafe fn sample_row_sse2(xform: &Matrix, src: &Bitmap, x: usize, y: usize, row: &mut [Pixel]) -> usize {
+    let py = y as f32 + 0.5;
+    let (a, by, c) = (_mm_set1_ps(xform.at(0)), _mm_set1_ps(xform.at(1) * py), _mm_set1_ps(xform.at(2)));
+    let (d, ey, f) = (_mm_set1_ps(xform.at(3)), _mm_set1_ps(xform.at(4) * py), _mm_set1_ps(xform.at(5)));
+
+    let zero = _mm_setzero_ps();
+    let w_1 = _mm_set1_ps(src.width  as f32 - 1.0);
+    let h_1 = _mm_set1_ps(src.height as f32 - 1.0);
+
+    let done = row.len() / 4 * 4;
+    let (mut lx, mut ly) = ([0i32; 4], [0i32; 4]);
+    for i in (0..done).step_by(4) {
+        let ix = _mm_add_epi32(_mm_set1_epi32((x + i) as i32), _mm_set_epi32(3, 2, 1, 0));
+        let px = _mm_add_ps(_mm_cvtepi32_ps(ix), _mm_set1_ps(0.5));
+
+        let sx = _mm_add_ps(_mm_add_ps(_mm_mul_ps(a, px), by), c);
+        let sy = _mm_add_ps(_mm_add_ps(_mm_mul_ps(d, px), ey), f);
+        _mm_storeu_si128(lx.as_mut_ptr() as *mut __m128i, _mm_cvttps_epi32(_mm_max_ps(_mm_min_ps(sx, w_1), zero)));
+        _mm_storeu_si128(ly.as_mut_ptr() as *mut __m128i, _mm_cvttps_epi32(_mm_max_ps(_mm_min_ps(sy, h_1), zero)));
+
+        // No gathers before AVX2
+        for n in 0..4 {
+            row[i + n] = src.get(lx[n] as usize, ly[n] as usize);
+        }
+    }
+    done
+}
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+#[target_feature(enable = "avx2")]
+unsafe fn sample_row_avx2(xform: &Matrix, src: &Bitmap, x: usize, y: usize, row: &mut [Pixel]) -> usize {
+    let py = y as f32 + 0.5;
+    let (a, by, c) = (_mm256_set1_ps(xform.at(0)), _mm256_set1_ps(xform.at(1) * py), _mm256_set1_ps(xform.at(2)));
+    let (d, ey, f) = (_mm256_set1_ps(xform.at(3)), _mm256_set1_ps(xform.at(4) * py), _mm256_set1_ps(xform.at(5)));
+
+    let zero = _mm256_setzero_ps();
+    let w_1 = _mm256_set1_ps(src.width  as f32 - 1.0);
+    let h_1 = _mm256_set1_ps(src.height as f32 - 1.0);
+    let width = _mm256_set1_epi32(src.width as i32);
+
+    let done = row.len() / 8 * 8;
+    for i in (0..done).step_by(8) {
+        let ix = _mm256_add_epi32(_mm256_set1_epi32((x + i) as i32), _mm256_set_epi32(7, 6, 5, 4, 3, 2, 1, 0));
+        let px = _mm256_add_ps(_mm256_cvtepi32_ps(ix), _mm256_set1_ps(0.5));
+
+        let sx = _mm256_add_ps(_mm256_add_ps(_mm256_mul_ps(a, px), by), c);
+        let sy = _mm256_add_ps(_mm256_add_ps(_mm256_mul_ps(d, px), ey), f);
+        let lx = _mm256_cvttps_epi32(_mm256_max_ps(_mm256_min_ps(sx, w_1), zero));
+        let ly = _mm256_cvttps_epi32(_mm256_max_ps(_mm256_min_ps(sy, h_1), zero));
+
+        let index = _mm256_add_epi32(_mm256_mullo_epi32(ly, width), lx);
+        let pixels = _mm256_i32gather_epi32(src.pixels.as_ptr() as *const i32, index, 4);
+        _mm256_storeu_si256(row.as_mut_ptr().add(i) as *mut __m256i, pixels);
+    }
+    done + sample_row_sse2(xform, src, x + done, y, &mut row[done..])
+}
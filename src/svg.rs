@@ -0,0 +1,314 @@
+use bitmap::Bitmap;
+use color::Color;
+use rect::Rect;
+use point::Point;
+use matrix::Matrix;
+use shader::{Shader, Shaders};
+use context::DrawContext;
+use util::{map_polygon, map_rect_to_rect_mat};
+use png;
+
+use std::path::Path;
+use std::fs::File;
+use std::io::prelude::*;
+use std::fmt::Write as FmtWrite;
+
+// Records the same drawing calls as Canvas, but as SVG elements
+pub struct SvgCanvas {
+    width:    usize,
+    height:   usize,
+    ctms:     Vec<Matrix>,
+    clips:    Vec<usize>,  // Ids of the clip groups currently open
+    depths:   Vec<usize>,  // Open clip groups at each save
+    next_id:  usize,
+    defs:     String,
+    elements: String,
+}
+
+impl SvgCanvas {
+    pub fn new(width: usize, height: usize) -> SvgCanvas {
+        let ctms = vec![Matrix::identity()];
+        SvgCanvas {
+            width:    width,
+            height:   height,
+            ctms:     ctms,
+            clips:    Vec::new(),
+            depths:   Vec::new(),
+            next_id:  0,
+            defs:     String::new(),
+            elements: String::new(),
+        }
+    }
+
+    fn get_ctm(&self) -> Matrix {
+        self.ctms[self.ctms.len()-1]
+    }
+
+    pub fn document(&self) -> String {
+        let (w, h) = (self.width, self.height);
+
+        let mut doc = String::new();
+        writeln!(doc, "<?xml version=\"1.0\" encoding=\"UTF-8\"?>").unwrap();
+        writeln!(doc, "<svg xmlns=\"http://www.w3.org/2000/svg\" xmlns:xlink=\"http://www.w3.org/1999/xlink\" \
+                       width=\"{}\" height=\"{}\" viewBox=\"0 0 {} {}\">", w, h, w, h).unwrap();
+        if !self.defs.is_empty() {
+            writeln!(doc, "<defs>").unwrap();
+            doc.push_str(&self.defs);
+            writeln!(doc, "</defs>").unwrap();
+        }
+        doc.push_str(&self.elements);
+        for _ in &self.clips {
+            writeln!(doc, "</g>").unwrap();
+        }
+        writeln!(doc, "</svg>").unwrap();
+
+        doc
+    }
+
+    pub fn write(&self, path: &Path) {
+        let mut file = File::create(path).unwrap();
+        file.write_all(self.document().as_bytes()).unwrap();
+    }
+
+    // Empty for the identity, so untransformed documents stay readable.
+    // SVG transforms are affine, so under perspective shapes are mapped to
+    // device space up front (see shape_points) and this is empty too.
+    fn transform(&self) -> String {
+        let ctm = self.get_ctm();
+        let m = ctm.get_floats();
+        if ctm.is_perspective() || m == Matrix::identity().get_floats() {
+            return String::new();
+        }
+
+        // SVG's matrix(a b c d e f) is column-major
+        format!(" transform=\"matrix({} {} {} {} {} {})\"", m[0], m[3], m[1], m[4], m[2], m[5])
+    }
+
+    // Registers a clip path in the current user space and returns its id
+    fn define_clip(&mut self, shape: &str) -> String {
+        let id = format!("clip{}", self.next_id);
+        self.next_id += 1;
+
+        let transform = self.transform();
+        writeln!(self.defs, "<clipPath id=\"{}\"><{}{}/></clipPath>", id, shape, transform).unwrap();
+
+        id
+    }
+
+    fn perspective(&self) -> bool {
+        self.get_ctm().is_perspective()
+    }
+
+    // Points to emit for a polygon: as given, or in device space when the
+    // CTM can't be written as an SVG transform
+    fn shape_points(&self, points: &[Point]) -> Vec<Point> {
+        if self.perspective() {
+            map_polygon(&self.get_ctm(), points)
+        } else {
+            points.to_vec()
+        }
+    }
+
+    fn rect_or_polygon(&self, rect: &Rect) -> String {
+        if self.perspective() {
+            format!("polygon points=\"{}\"", points_attr(&self.shape_points(&corners(rect))))
+        } else {
+            rect_shape(rect)
+        }
+    }
+
+    // SVG has no notion of an arbitrary shader, so shade its device-space
+    // bounds into a bitmap and clip that to the shape
+    fn shade_shape(&mut self, shape: &str, points: &[Point], shader: &mut dyn Shader) {
+        let ctm = self.get_ctm();
+        let device = map_polygon(&ctm, points);
+        if device.is_empty() {
+            return;
+        }
+
+        let l = device.iter().fold(f32::INFINITY,     |acc, p| acc.min(p.x)).floor().max(0.0);
+        let t = device.iter().fold(f32::INFINITY,     |acc, p| acc.min(p.y)).floor().max(0.0);
+        let r = device.iter().fold(f32::NEG_INFINITY, |acc, p| acc.max(p.x)).ceil().min(self.width  as f32);
+        let b = device.iter().fold(f32::NEG_INFINITY, |acc, p| acc.max(p.y)).ceil().min(self.height as f32);
+        if l >= r || t >= b {
+            return;
+        }
+
+        if !shader.set_context(&ctm) {
+            return;
+        }
+
+        let (x, y) = (l as usize, t as usize);
+        let mut bitmap = Bitmap::new((r - l) as usize, (b - t) as usize);
+        for row in 0..bitmap.height {
+            let start = row*bitmap.width;
+            shader.shade_row(x, y + row, &mut bitmap.pixels[start..start + bitmap.width]);
+        }
+
+        // At least a pixel each way, so the PNG encodes
+        let id = self.define_clip(shape);
+        writeln!(self.elements,
+                 "<image x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" style=\"image-rendering:pixelated\" \
+                  clip-path=\"url(#{})\" xlink:href=\"data:image/png;base64,{}\"/>",
+                 x, y, bitmap.width, bitmap.height, id, base64(&png::encode(&bitmap).unwrap())).unwrap();
+    }
+}
+
+impl DrawContext for SvgCanvas {
+    fn clear(&mut self, color: &Color) {
+        // Unclipped, everything drawn so far would be covered anyway. Inside
+        // the open clip groups SVG can only paint over, which is the same
+        // for opaque colors.
+        if self.clips.is_empty() {
+            self.elements.clear();
+        }
+
+        let (w, h) = (self.width, self.height);
+        writeln!(self.elements, "<rect width=\"{}\" height=\"{}\" {}/>", w, h, fill(color)).unwrap();
+    }
+
+    fn shade_rect(&mut self, rect: &Rect, shader: &mut dyn Shader) {
+        if rect.empty() {
+            return;
+        }
+
+        let shape = self.rect_or_polygon(rect);
+        self.shade_shape(&shape, &corners(rect), shader);
+    }
+
+    fn shade_convex_polygon(&mut self, points: &[Point], shader: &mut dyn Shader) {
+        // Reject lines and points
+        if points.len() <= 2 {
+            return
+        }
+
+        let shape = format!("polygon points=\"{}\"", points_attr(&self.shape_points(points)));
+        self.shade_shape(&shape, points, shader);
+    }
+
+    fn fill_bitmap_rect(&mut self, src: &Bitmap, dst: &Rect) {
+        if dst.empty() || src.width == 0 || src.height == 0 {
+            return;
+        }
+
+        // An <image> can't be put in perspective, so shade it instead
+        if self.perspective() {
+            let srcrect = Rect::make_wh(src.width as f32, src.height as f32);
+            let mut shader = Shaders::from_bitmap(src, map_rect_to_rect_mat(&srcrect, dst).get_floats());
+            return self.shade_rect(dst, &mut shader);
+        }
+
+        // Not empty, so the PNG encodes
+        let transform = self.transform();
+        writeln!(self.elements,
+                 "<image x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" preserveAspectRatio=\"none\" \
+                  style=\"image-rendering:pixelated\"{} xlink:href=\"data:image/png;base64,{}\"/>",
+                 dst.left(), dst.top(), dst.width(), dst.height(), transform,
+                 base64(&png::encode(src).unwrap())).unwrap();
+    }
+
+    fn fill_rect(&mut self, rect: &Rect, color: &Color) {
+        if rect.empty() {
+            return;
+        }
+        if self.perspective() {
+            return self.fill_convex_polygon(&corners(rect), color);
+        }
+
+        let transform = self.transform();
+        writeln!(self.elements, "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" {}{}/>",
+                 rect.left(), rect.top(), rect.width(), rect.height(), fill(color), transform).unwrap();
+    }
+
+    fn fill_convex_polygon(&mut self, points: &[Point], color: &Color) {
+        // Reject lines and points
+        if points.len() <= 2 {
+            return
+        }
+
+        let transform = self.transform();
+        writeln!(self.elements, "<polygon points=\"{}\" {}{}/>",
+                 points_attr(&self.shape_points(points)), fill(color), transform).unwrap();
+    }
+
+    fn save(&mut self) {
+        let ctm = self.get_ctm();
+        self.ctms.push(ctm);
+        self.depths.push(self.clips.len());
+    }
+
+    fn restore(&mut self) {
+        self.ctms.pop().unwrap();
+
+        let depth = self.depths.pop().unwrap();
+        while self.clips.len() > depth {
+            self.clips.pop();
+            writeln!(self.elements, "</g>").unwrap();
+        }
+    }
+
+    fn concat_matrix(&mut self, mat: &Matrix) {
+        let ctm = self.get_ctm();
+        let len = self.ctms.len();
+        self.ctms[len-1] = ctm.mul(mat);
+    }
+
+    fn clip_rect(&mut self, rect: &Rect) {
+        let shape = self.rect_or_polygon(rect);
+
+        self.clips.push(self.next_id);
+        let id = self.define_clip(&shape);
+        writeln!(self.elements, "<g clip-path=\"url(#{})\">", id).unwrap();
+    }
+}
+
+fn corners(rect: &Rect) -> [Point; 4] {
+    [
+        Point::new(rect.left(),  rect.top()),
+        Point::new(rect.right(), rect.top()),
+        Point::new(rect.right(), rect.bottom()),
+        Point::new(rect.left(),  rect.bottom()),
+    ]
+}
+
+fn rect_shape(rect: &Rect) -> String {
+    format!("rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\"",
+            rect.left(), rect.top(), rect.width().max(0.0), rect.height().max(0.0))
+}
+
+fn points_attr(points: &[Point]) -> String {
+    let points: Vec<String> = points.iter().map(|p| format!("{},{}", p.x, p.y)).collect();
+    points.join(" ")
+}
+
+fn fill(color: &Color) -> String {
+    let c = color.pin_to_unit();
+    let channel = |v: f32| (v * 255.0).round() as u8;
+
+    let mut attrs = format!("fill=\"rgb({},{},{})\"", channel(c.r), channel(c.g), channel(c.b));
+    if c.a < 1.0 {
+        write!(attrs, " fill-opacity=\"{}\"", c.a).unwrap();
+    }
+
+    attrs
+}
+
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let b = [chunk[0], *chunk.get(1).unwrap_or(&0), *chunk.get(2).unwrap_or(&0)];
+        let v = (b[0] as u32) << 16 | (b[1] as u32) << 8 | b[2] as u32;
+
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[(v >> (18 - 6*i)) as usize & 0x3f] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+
+    out
+}
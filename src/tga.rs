@@ -0,0 +1,216 @@
+use bitmap::Bitmap;
+use pixel::Pixel;
+
+use std::path::Path;
+use std::fs::File;
+use std::io;
+use std::io::prelude::*;
+use std::io::BufWriter;
+use std::cmp::min;
+
+const HEADER_SIZE: usize = 18;
+
+// Image types
+const TRUE_COLOR:     u8 = 2;
+const GRAYSCALE:      u8 = 3;
+const RLE_TRUE_COLOR: u8 = 10;
+const RLE_GRAYSCALE:  u8 = 11;
+
+// Image descriptor bits
+const ALPHA_BITS_MASK: u8 = 0x0f;
+const RIGHT_TO_LEFT:   u8 = 0x10;
+const TOP_TO_BOTTOM:   u8 = 0x20;
+
+// TGA 2.0 footer, with no extension or developer areas
+const FOOTER: &[u8] = b"\0\0\0\0\0\0\0\0TRUEVISION-XFILE.\0";
+
+pub fn read(path: &Path) -> io::Result<Bitmap> {
+    let mut bytes = Vec::new();
+    File::open(path)?.read_to_end(&mut bytes)?;
+
+    decode(&bytes)
+}
+
+pub fn write(bitmap: &Bitmap, path: &Path) -> io::Result<()> {
+    let mut file = BufWriter::new(File::create(path)?);
+    file.write_all(&encode(bitmap, true)?)?;
+    file.flush()
+}
+
+pub fn decode(bytes: &[u8]) -> io::Result<Bitmap> {
+    if bytes.len() < HEADER_SIZE {
+        return Err(invalid("truncated TGA header"));
+    }
+
+    let id_length     = bytes[0] as usize;
+    let colormap_type = bytes[1];
+    let image_type    = bytes[2];
+    let colormap_len  = get_u16(bytes, 5) as usize;
+    let colormap_bits = bytes[7] as usize;
+    let w             = get_u16(bytes, 12) as usize;
+    let h             = get_u16(bytes, 14) as usize;
+    let bpp           = bytes[16];
+    let descriptor    = bytes[17];
+
+    let grayscale = image_type == GRAYSCALE || image_type == RLE_GRAYSCALE;
+    let rle       = image_type == RLE_TRUE_COLOR || image_type == RLE_GRAYSCALE;
+
+    match (image_type, bpp) {
+        (TRUE_COLOR, 24) | (TRUE_COLOR, 32) | (RLE_TRUE_COLOR, 24) | (RLE_TRUE_COLOR, 32) => {},
+        (GRAYSCALE, 8) | (RLE_GRAYSCALE, 8) => {},
+        _ => return Err(invalid("unsupported TGA image type")),
+    }
+    if w == 0 || h == 0 {
+        return Err(invalid("invalid TGA dimensions"));
+    }
+
+    // Skip the image ID and any (unused) color map
+    let mut pos = HEADER_SIZE + id_length;
+    if colormap_type != 0 {
+        pos += colormap_len * colormap_bits.div_ceil(8);
+    }
+
+    let has_alpha = bpp == 32 && descriptor & ALPHA_BITS_MASK != 0;
+    let depth = bpp as usize / 8;
+
+    let unpack = |px: &[u8]| -> Pixel {
+        if grayscale {
+            Pixel::pack_rgb(px[0], px[0], px[0])
+        } else if has_alpha {
+            Pixel::pack_unpremul_argb(px[3], px[2], px[1], px[0])
+        } else {
+            Pixel::pack_rgb(px[2], px[1], px[0])
+        }
+    };
+
+    // Nothing is allocated for the image until the data is known to cover
+    // it. RLE packets can expand a lot, so that buffer grows as they decode.
+    let remaining = bytes.len().saturating_sub(pos);
+    if !rle && depth*w*h > remaining {
+        return Err(invalid("truncated TGA data"));
+    }
+
+    // Decode pixels in file order, then map them to bitmap coordinates
+    let mut decoded = Vec::with_capacity(if rle {min(w*h, remaining)} else {w*h});
+    while decoded.len() < w*h {
+        if rle {
+            let header = match bytes.get(pos) {
+                Some(header) => *header as usize,
+                None         => return Err(invalid("truncated TGA data")),
+            };
+            pos += 1;
+
+            let count = (header & 0x7f) + 1;
+            let len = if header & 0x80 != 0 {depth} else {depth*count};
+            if pos + len > bytes.len() || decoded.len() + count > w*h {
+                return Err(invalid("invalid TGA packet"));
+            }
+
+            if header & 0x80 != 0 {
+                let px = unpack(&bytes[pos..]);
+                decoded.resize(decoded.len() + count, px);
+            } else {
+                decoded.extend(bytes[pos..pos + len].chunks(depth).map(&unpack));
+            }
+            pos += len;
+        } else {
+            let len = depth*w*h;
+            decoded.extend(bytes[pos..pos + len].chunks(depth).map(&unpack));
+        }
+    }
+
+    let mut bitmap = Bitmap::new(w, h);
+    for row in 0..h {
+        let y = if descriptor & TOP_TO_BOTTOM != 0 {row} else {h - 1 - row};
+        for col in 0..w {
+            let x = if descriptor & RIGHT_TO_LEFT != 0 {w - 1 - col} else {col};
+            bitmap.set(x, y, &decoded[col + row*w]);
+        }
+    }
+
+    Ok(bitmap)
+}
+
+// Writes top-down 32-bit BGRA, run-length encoded per row if requested
+pub fn encode(bitmap: &Bitmap, rle: bool) -> io::Result<Vec<u8>> {
+    let (w, h) = (bitmap.width, bitmap.height);
+    if w > 0xffff || h > 0xffff {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "bitmap too large for TGA"));
+    }
+
+    let mut out = Vec::with_capacity(HEADER_SIZE + w*h*4 + FOOTER.len());
+
+    out.push(0);  // No image ID
+    out.push(0);  // No color map
+    out.push(if rle {RLE_TRUE_COLOR} else {TRUE_COLOR});
+    out.extend_from_slice(&[0; 5]);  // Color map spec
+    put_u16(&mut out, 0);
+    put_u16(&mut out, 0);
+    put_u16(&mut out, w as u16);
+    put_u16(&mut out, h as u16);
+    out.push(32);
+    out.push(TOP_TO_BOTTOM | 8);
+
+    let bgra = |px: &Pixel| -> [u8; 4] {
+        let (a, r, g, b) = px.unpremul_argb();
+        [b, g, r, a]
+    };
+
+    for y in 0..h {
+        let row = &bitmap.pixels[y*w..(y + 1)*w];
+
+        if !rle {
+            for px in row {
+                out.extend_from_slice(&bgra(px));
+            }
+            continue;
+        }
+
+        // Packets never cross rows, as recommended by the spec
+        let mut x = 0;
+        while x < w {
+            let px = bgra(&row[x]);
+
+            let mut run = 1;
+            while x + run < w && run < 128 && bgra(&row[x + run]) == px {
+                run += 1;
+            }
+
+            if run > 1 {
+                out.push(0x80 | (run - 1) as u8);
+                out.extend_from_slice(&px);
+                x += run;
+                continue;
+            }
+
+            // Raw packet up to the start of the next run
+            let mut count = 1;
+            while x + count < w && count < 128
+                  && (x + count + 1 >= w || bgra(&row[x + count]) != bgra(&row[x + count + 1])) {
+                count += 1;
+            }
+
+            out.push((count - 1) as u8);
+            for px in &row[x..x + count] {
+                out.extend_from_slice(&bgra(px));
+            }
+            x += count;
+        }
+    }
+
+    out.extend_from_slice(FOOTER);
+
+    Ok(out)
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+fn get_u16(bytes: &[u8], i: usize) -> u16 {
+    bytes[i] as u16 | (bytes[i+1] as u16) << 8
+}
+
+fn put_u16(out: &mut Vec<u8>, v: u16) {
+    out.extend_from_slice(&[v as u8, (v >> 8) as u8]);
+}
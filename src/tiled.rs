@@ -0,0 +1,53 @@
+use bitmap::Bitmap;
+use canvas::Canvas;
+use picture::Picture;
+use view::BitmapViewMut;
+
+use std::sync::Mutex;
+use std::thread;
+
+// Rows per tile. Tiles span the full width, so each is one contiguous run
+// of pixels that a canvas can draw into directly.
+pub const TILE_ROWS: usize = 64;
+
+// The number of threads worth using on this machine
+pub fn default_threads() -> usize {
+    thread::available_parallelism().map(|n| n.get()).unwrap_or(1)
+}
+
+// Plays the picture back onto bitmap like Picture::playback onto a Canvas,
+// but splits the bitmap into tiles drawn on up to `threads` threads. Every
+// tile replays all of the picture on a canvas bounded to its own pixels,
+// and the result is identical to drawing on a single canvas.
+pub fn playback(picture: &Picture, bitmap: &mut Bitmap, mat: [f32; 6], threads: usize) {
+    let width = bitmap.width;
+    if width == 0 || bitmap.height == 0 {
+        return;
+    }
+
+    let tiles = Mutex::new(bitmap.pixels.chunks_mut(TILE_ROWS * width).enumerate());
+    let threads = threads.clamp(1, bitmap.height.div_ceil(TILE_ROWS));
+
+    let draw_tiles = || {
+        loop {
+            // Hold the lock just long enough to take the next tile
+            let next = tiles.lock().unwrap().next();
+            let (index, pixels) = match next {
+                Some(tile) => tile,
+                None       => return,
+            };
+
+            let rows = pixels.len() / width;
+            let tile = BitmapViewMut::new(pixels, width, rows, width);
+            let mut canvas = Canvas::with_origin(tile, 0, index * TILE_ROWS);
+            picture.playback(&mut canvas, mat);
+        }
+    };
+
+    thread::scope(|scope| {
+        for _ in 1..threads {
+            scope.spawn(draw_tiles);
+        }
+        draw_tiles();
+    });
+}
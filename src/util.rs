@@ -1,6 +1,8 @@
 use pixel::Pixel;
 use rect::Rect;
 use matrix::Matrix;
+use point::Point;
+use simd;
 
 pub fn blend(src: &Pixel, dst: &Pixel) -> Pixel {
     if src.a == 255 {
@@ -24,14 +26,22 @@ pub fn blend(src: &Pixel, dst: &Pixel) -> Pixel {
     Pixel::pack_argb(final_a as u8, final_r as u8, final_g as u8, final_b as u8)
 }
 
-pub fn blend_row(src: &[Pixel], dst: &[Pixel]) -> Vec<Pixel> {
+// Blends src over dst in place, with SIMD where the CPU has it
+pub fn blend_row(src: &[Pixel], dst: &mut [Pixel]) {
     assert!(src.len() == dst.len(), "src and dst rows not the same size");
 
-    src.iter()
-       .zip(dst)
-       .map(|(srcpx, dstpx)| {
-            blend(srcpx, dstpx)
-       }).collect::<Vec<_>>()
+    let done = simd::blend_row(simd::level(), src, dst);
+    for (srcpx, dstpx) in src[done..].iter().zip(&mut dst[done..]) {
+        *dstpx = blend(srcpx, dstpx);
+    }
+}
+
+// Blends one pixel over all of dst
+pub fn blend_color_row(src: &Pixel, dst: &mut [Pixel]) {
+    let done = simd::blend_color(simd::level(), *src, dst);
+    for dstpx in dst[done..].iter_mut() {
+        *dstpx = blend(src, dstpx);
+    }
 }
 
 pub fn map_rect_to_rect_mat(src: &Rect, dst: &Rect) -> Matrix {
@@ -60,3 +70,117 @@ pub fn map_rect_to_rect_mat(src: &Rect, dst: &Rect) -> Matrix {
 pub fn clamp(min: f32, value: f32, max: f32) -> f32 {
     value.min(max).max(min)
 }
+
+// Sutherland-Hodgman against a convex clip polygon of either winding
+pub fn clip_convex_polygon(subject: &[Point], clip: &[Point]) -> Vec<Point> {
+    let cross = |o: &Point, a: &Point, b: &Point| (a.x - o.x)*(b.y - o.y) - (a.y - o.y)*(b.x - o.x);
+
+    let mut area = 0.0;
+    for i in 1..clip.len().saturating_sub(1) {
+        area += cross(&clip[0], &clip[i], &clip[i+1]);
+    }
+    if area == 0.0 {
+        return Vec::new();
+    }
+    let orient = area.signum();
+
+    let mut output = subject.to_vec();
+    for i in 0..clip.len() {
+        let (p, q) = (clip[i], clip[(i + 1) % clip.len()]);
+
+        let input = output;
+        output = Vec::with_capacity(input.len() + 1);
+
+        for j in 0..input.len() {
+            let (s, e) = (input[j], input[(j + 1) % input.len()]);
+            let ds = cross(&p, &q, &s) * orient;
+            let de = cross(&p, &q, &e) * orient;
+
+            if ds >= 0.0 {
+                output.push(s);
+            }
+            if (ds >= 0.0) != (de >= 0.0) {
+                let t = ds / (ds - de);
+                output.push(Point::new(s.x + t*(e.x - s.x), s.y + t*(e.y - s.y)));
+            }
+        }
+    }
+
+    output
+}
+
+// Smallest w kept when clipping against the w > 0 half-space
+const MIN_W: f32 = 1e-3;
+
+// How far device-space points may land from the origin. Within it the 28.4
+// edge products in raster_tri stay far from overflowing 64 bits.
+pub const GUARD_BAND: f32 = (1 << 22) as f32;
+
+pub fn in_guard_band(p: &Point) -> bool {
+    p.x.abs() <= GUARD_BAND && p.y.abs() <= GUARD_BAND
+}
+
+// Maps a convex polygon to device space, kept within the guard band. Under
+// perspective it is first clipped to w > 0, so nothing behind the eye wraps
+// around.
+pub fn map_polygon(mat: &Matrix, points: &[Point]) -> Vec<Point> {
+    if !mat.is_perspective() {
+        return clip_to_guard_band(points.iter().map(|p| mat.apply(p)).collect());
+    }
+
+    let mapped: Vec<[f32; 3]> = points.iter().map(|p| mat.apply_homogeneous(p)).collect();
+
+    let mut projected = Vec::with_capacity(mapped.len() + 1);
+    for i in 0..mapped.len() {
+        let (p, q) = (mapped[i], mapped[(i + 1) % mapped.len()]);
+
+        if p[2] >= MIN_W {
+            projected.push(Point::new(p[0] / p[2], p[1] / p[2]));
+        }
+        if (p[2] >= MIN_W) != (q[2] >= MIN_W) {
+            let t = (MIN_W - p[2]) / (q[2] - p[2]);
+            let x = p[0] + t*(q[0] - p[0]);
+            let y = p[1] + t*(q[1] - p[1]);
+            projected.push(Point::new(x / MIN_W, y / MIN_W));
+        }
+    }
+
+    clip_to_guard_band(projected)
+}
+
+// Sutherland-Hodgman against the sides of the guard band, in f64 so that
+// coordinates up to f32::MAX still intersect accurately. Polygons with
+// non-finite points have no sensible shape and are dropped.
+fn clip_to_guard_band(points: Vec<Point>) -> Vec<Point> {
+    if points.iter().all(in_guard_band) {
+        return points;
+    }
+    if points.iter().any(|p| !p.x.is_finite() || !p.y.is_finite()) {
+        return Vec::new();
+    }
+
+    let band = GUARD_BAND as f64;
+    let mut output: Vec<(f64, f64)> = points.iter().map(|p| (p.x as f64, p.y as f64)).collect();
+
+    for &(axis, sign) in [(0, 1.0), (0, -1.0), (1, 1.0), (1, -1.0)].iter() {
+        let inside = |p: &(f64, f64)| band - sign * if axis == 0 {p.0} else {p.1};
+
+        let input = output;
+        output = Vec::with_capacity(input.len() + 1);
+
+        for j in 0..input.len() {
+            let (s, e) = (input[j], input[(j + 1) % input.len()]);
+            let (ds, de) = (inside(&s), inside(&e));
+
+            if ds >= 0.0 {
+                output.push(s);
+            }
+            if (ds >= 0.0) != (de >= 0.0) {
+                let t = ds / (ds - de);
+                output.push((s.0 + t*(e.0 - s.0), s.1 + t*(e.1 - s.1)));
+            }
+        }
+    }
+
+    output.iter().map(|p| Point::new(p.0 as f32, p.1 as f32)).collect()
+}
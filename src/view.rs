@@ -0,0 +1,227 @@
+use bitmap::Bitmap;
+use pixel::Pixel;
+use point::Point;
+use rect::Rect;
+
+use std::cmp::{min, max};
+use std::mem;
+use std::slice;
+
+// Pixel memory a Canvas can draw into, a row at a time
+pub trait Surface {
+    fn width(&self) -> usize;
+    fn height(&self) -> usize;
+    fn row(&self, y: usize) -> &[Pixel];
+    fn row_mut(&mut self, y: usize) -> &mut [Pixel];
+}
+
+impl Surface for Bitmap {
+    fn width(&self) -> usize {
+        self.width
+    }
+
+    fn height(&self) -> usize {
+        self.height
+    }
+
+    fn row(&self, y: usize) -> &[Pixel] {
+        &self.pixels[y*self.width .. (y + 1)*self.width]
+    }
+
+    fn row_mut(&mut self, y: usize) -> &mut [Pixel] {
+        &mut self.pixels[y*self.width .. (y + 1)*self.width]
+    }
+}
+
+// Pixels borrowed from memory owned elsewhere, with rows stride pixels
+// apart. Whatever lies between rows is never touched.
+pub struct BitmapView<'a> {
+    pub width:  usize,
+    pub height: usize,
+    pub stride: usize,
+    pixels:     &'a [Pixel],
+}
+
+// The same, for drawing into. Canvas::new takes one of these to draw
+// straight into a framebuffer or part of a larger image.
+pub struct BitmapViewMut<'a> {
+    pub width:  usize,
+    pub height: usize,
+    pub stride: usize,
+    pixels:     &'a mut [Pixel],
+}
+
+// Pixels needed to hold the rows; the last one needs no padding after it
+fn span(width: usize, height: usize, stride: usize) -> usize {
+    assert!(width <= stride, "stride {} is less than width {}", stride, width);
+    if height == 0 { 0 } else { (height - 1)*stride + width }
+}
+
+// Reinterprets bytes as pixels. Pixel is four u8s with repr(C), so any four
+// bytes are a pixel, with no alignment needed.
+fn byte_stride(width: usize, height: usize, stride: usize, len: usize) -> (usize, usize) {
+    assert!(stride % mem::size_of::<Pixel>() == 0, "stride {} is not a whole number of pixels", stride);
+    let stride = stride / mem::size_of::<Pixel>();
+    let needed = span(width, height, stride);
+    assert!(len >= needed * mem::size_of::<Pixel>(),
+            "{} bytes is too few for {}x{} with a stride of {} pixels", len, width, height, stride);
+    (stride, needed)
+}
+
+// The whole pixels rect covers, after rounding, inside width x height
+fn pixel_bounds(rect: &Rect, width: usize, height: usize) -> (usize, usize, usize, usize) {
+    let rect = rect.round();
+    let clamp = |v: f32, hi: usize| v.max(0.0).min(hi as f32) as usize;
+    let (l, t) = (clamp(rect.left(), width), clamp(rect.top(), height));
+    let (r, b) = (clamp(rect.right(), width), clamp(rect.bottom(), height));
+    (l, t, max(l, r), max(t, b))
+}
+
+impl<'a> BitmapView<'a> {
+    pub fn new(pixels: &'a [Pixel], width: usize, height: usize, stride: usize) -> BitmapView<'a> {
+        let needed = span(width, height, stride);
+        assert!(pixels.len() >= needed, "{} pixels is too few for {}x{} with a stride of {}",
+                pixels.len(), width, height, stride);
+        BitmapView {
+            width:  width,
+            height: height,
+            stride: stride,
+            pixels: &pixels[..needed],
+        }
+    }
+
+    // Bytes in Pixel::ORDER, with rows stride bytes apart
+    pub fn from_bytes(bytes: &'a [u8], width: usize, height: usize, stride: usize) -> BitmapView<'a> {
+        let (stride, needed) = byte_stride(width, height, stride, bytes.len());
+        let pixels = unsafe { slice::from_raw_parts(bytes.as_ptr() as *const Pixel, needed) };
+        BitmapView::new(pixels, width, height, stride)
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> Pixel {
+        self.row(y)[x]
+    }
+
+    pub fn row(&self, y: usize) -> &'a [Pixel] {
+        assert!(y < self.height, "row {} of {}", y, self.height);
+        &self.pixels[y*self.stride .. y*self.stride + self.width]
+    }
+
+    // The pixels under rect, without copying. The rect is rounded like
+    // Rect::round and cut down to the view, so it may come back empty.
+    pub fn subset(&self, rect: &Rect) -> BitmapView<'a> {
+        let (l, t, r, b) = pixel_bounds(rect, self.width, self.height);
+        let start = min(t*self.stride + l, self.pixels.len());
+        BitmapView::new(&self.pixels[start..], r - l, b - t, self.stride)
+    }
+
+    // An owned, tightly packed copy
+    pub fn to_bitmap(&self) -> Bitmap {
+        let mut bitmap = Bitmap::new(self.width, self.height);
+        for y in 0..self.height {
+            bitmap.row_mut(y).copy_from_slice(self.row(y));
+        }
+        bitmap
+    }
+}
+
+impl<'a> BitmapViewMut<'a> {
+    pub fn new(pixels: &'a mut [Pixel], width: usize, height: usize, stride: usize) -> BitmapViewMut<'a> {
+        let needed = span(width, height, stride);
+        assert!(pixels.len() >= needed, "{} pixels is too few for {}x{} with a stride of {}",
+                pixels.len(), width, height, stride);
+        BitmapViewMut {
+            width:  width,
+            height: height,
+            stride: stride,
+            pixels: &mut pixels[..needed],
+        }
+    }
+
+    // Bytes in Pixel::ORDER, with rows stride bytes apart. Drawing assumes
+    // they are premultiplied.
+    pub fn from_bytes(bytes: &'a mut [u8], width: usize, height: usize, stride: usize) -> BitmapViewMut<'a> {
+        let (stride, needed) = byte_stride(width, height, stride, bytes.len());
+        let pixels = unsafe { slice::from_raw_parts_mut(bytes.as_mut_ptr() as *mut Pixel, needed) };
+        BitmapViewMut::new(pixels, width, height, stride)
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> Pixel {
+        self.row(y)[x]
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, p: &Pixel) {
+        self.row_mut(y)[x] = *p;
+    }
+
+    // Like BitmapView::subset, borrowing this view while the subset lives
+    pub fn subset_mut(&mut self, rect: &Rect) -> BitmapViewMut<'_> {
+        BitmapViewMut::new(self.pixels, self.width, self.height, self.stride).into_subset(rect)
+    }
+
+    // The same, giving up this view for the subset
+    pub fn into_subset(self, rect: &Rect) -> BitmapViewMut<'a> {
+        let (l, t, r, b) = pixel_bounds(rect, self.width, self.height);
+        let start = min(t*self.stride + l, self.pixels.len());
+        BitmapViewMut::new(&mut self.pixels[start..], r - l, b - t, self.stride)
+    }
+
+    // Copies src_rect of src so its top-left lands on dst, replacing what
+    // was there, with no blending. Parts falling outside either side are
+    // skipped, and the rest stays where it would have gone.
+    pub fn copy_from(&mut self, src: &BitmapView, src_rect: &Rect, dst: &Point) {
+        let rounded = src_rect.round();
+        let (l, t, r, b) = pixel_bounds(src_rect, src.width, src.height);
+
+        // How far src moves, worked out in f64 so far-off rects and points
+        // can't overflow. Past 2^31 either way, nothing lands on this view.
+        let far = (1u64 << 31) as f64;
+        let offset = |to: f32, from: f32| (to.floor() as f64 - from as f64).clamp(-far, far) as i64;
+
+        // Where the part inside src lands, which may start off this view
+        let x = l as i64 + offset(dst.x, rounded.left());
+        let y = t as i64 + offset(dst.y, rounded.top());
+        let (x0, y0) = (max(x, 0), max(y, 0));
+        let x1 = min(x + (r - l) as i64, self.width as i64);
+        let y1 = min(y + (b - t) as i64, self.height as i64);
+        if x0 >= x1 || y0 >= y1 {
+            return;
+        }
+
+        let sx = l + (x0 - x) as usize;
+        let count = (x1 - x0) as usize;
+        for dy in y0..y1 {
+            let sy = t + (dy - y) as usize;
+            self.row_mut(dy as usize)[x0 as usize .. x1 as usize].copy_from_slice(&src.row(sy)[sx .. sx + count]);
+        }
+    }
+
+    // A read-only view of the same pixels
+    pub fn as_view(&self) -> BitmapView<'_> {
+        BitmapView {
+            width:  self.width,
+            height: self.height,
+            stride: self.stride,
+            pixels: self.pixels,
+        }
+    }
+}
+
+impl<'a> Surface for BitmapViewMut<'a> {
+    fn width(&self) -> usize {
+        self.width
+    }
+
+    fn height(&self) -> usize {
+        self.height
+    }
+
+    fn row(&self, y: usize) -> &[Pixel] {
+        assert!(y < self.height, "row {} of {}", y, self.height);
+        &self.pixels[y*self.stride .. y*self.stride + self.width]
+    }
+
+    fn row_mut(&mut self, y: usize) -> &mut [Pixel] {
+        assert!(y < self.height, "row {} of {}", y, self.height);
+        &mut self.pixels[y*self.stride .. y*self.stride + self.width]
+    }
+}
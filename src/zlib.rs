@@ -0,0 +1,207 @@
+// Minimal zlib stream writer (RFC 1950/1951): LZ77 matching over a 32K window,
+// emitted as a single deflate block using the fixed Huffman codes.
+
+const WINDOW_SIZE: usize = 32768;
+const MIN_MATCH:   usize = 3;
+const MAX_MATCH:   usize = 258;
+const MAX_CHAIN:   usize = 64;
+
+const HASH_BITS: usize = 15;
+const HASH_SIZE: usize = 1 << HASH_BITS;
+
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31,
+    35, 43, 51, 59, 67, 83, 99, 115, 131, 163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2,
+    3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const DIST_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193,
+    257, 385, 513, 769, 1025, 1537, 2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DIST_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6,
+    7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13, 13,
+];
+
+pub fn compress(data: &[u8]) -> Vec<u8> {
+    let mut writer = BitWriter::new();
+
+    // CMF/FLG: deflate with a 32K window, default compression level
+    writer.bytes.extend_from_slice(&[0x78, 0x9c]);
+
+    // BFINAL = 1, BTYPE = 01 (fixed Huffman)
+    writer.write(1, 1);
+    writer.write(1, 2);
+
+    let mut matcher = Matcher::new(data);
+
+    let mut i = 0;
+    while i < data.len() {
+        let (len, dist) = matcher.longest_match(i);
+
+        if len >= MIN_MATCH {
+            write_length(&mut writer, len);
+            write_distance(&mut writer, dist);
+            for j in i..i + len {
+                matcher.insert(j);
+            }
+            i += len;
+        } else {
+            write_literal(&mut writer, data[i] as u16);
+            matcher.insert(i);
+            i += 1;
+        }
+    }
+
+    write_literal(&mut writer, 256);
+
+    let mut out = writer.finish();
+    out.extend_from_slice(&adler32(data).to_be_bytes());
+
+    out
+}
+
+pub fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for chunk in data.chunks(5552) {
+        for &byte in chunk {
+            a += byte as u32;
+            b += a;
+        }
+        a %= 65521;
+        b %= 65521;
+    }
+    b << 16 | a
+}
+
+// Hash chains over three-byte prefixes, most recent position first
+struct Matcher<'a> {
+    data: &'a [u8],
+    head: Vec<usize>,
+    prev: Vec<usize>,
+}
+
+impl<'a> Matcher<'a> {
+    fn new(data: &'a [u8]) -> Matcher<'a> {
+        Matcher {
+            data: data,
+            head: vec![usize::MAX; HASH_SIZE],
+            prev: vec![usize::MAX; WINDOW_SIZE],
+        }
+    }
+
+    fn hash(&self, i: usize) -> usize {
+        let d = self.data;
+        let v = (d[i] as usize) << 16 | (d[i+1] as usize) << 8 | d[i+2] as usize;
+        (v.wrapping_mul(2654435761) >> 8) & (HASH_SIZE - 1)
+    }
+
+    fn insert(&mut self, i: usize) {
+        if i + MIN_MATCH <= self.data.len() {
+            let h = self.hash(i);
+            self.prev[i % WINDOW_SIZE] = self.head[h];
+            self.head[h] = i;
+        }
+    }
+
+    fn longest_match(&self, i: usize) -> (usize, usize) {
+        let data = self.data;
+        if i + MIN_MATCH > data.len() {
+            return (0, 0);
+        }
+
+        let max_len = MAX_MATCH.min(data.len() - i);
+        let mut best = (0, 0);
+
+        let mut candidate = self.head[self.hash(i)];
+        let mut chain = 0;
+        while candidate != usize::MAX && i - candidate <= WINDOW_SIZE && chain < MAX_CHAIN {
+            let mut len = 0;
+            while len < max_len && data[candidate + len] == data[i + len] {
+                len += 1;
+            }
+            if len > best.0 {
+                best = (len, i - candidate);
+                if len == max_len {
+                    break;
+                }
+            }
+
+            // Stale entries from an older trip around the window end the chain
+            let next = self.prev[candidate % WINDOW_SIZE];
+            if next == usize::MAX || next >= candidate {
+                break;
+            }
+            candidate = next;
+            chain += 1;
+        }
+
+        best
+    }
+}
+
+fn write_literal(writer: &mut BitWriter, lit: u16) {
+    // Fixed literal/length code lengths from RFC 1951, section 3.2.6
+    let (code, bits) = match lit {
+        0..=143   => (0x30 + lit, 8),
+        144..=255 => (0x190 + lit - 144, 9),
+        256..=279 => (lit - 256, 7),
+        _         => (0xc0 + lit - 280, 8),
+    };
+    writer.write_huffman(code, bits);
+}
+
+fn write_length(writer: &mut BitWriter, len: usize) {
+    let index = LENGTH_BASE.iter().rposition(|&base| base as usize <= len).unwrap();
+    write_literal(writer, 257 + index as u16);
+    writer.write((len - LENGTH_BASE[index] as usize) as u32, LENGTH_EXTRA[index] as u32);
+}
+
+fn write_distance(writer: &mut BitWriter, dist: usize) {
+    let index = DIST_BASE.iter().rposition(|&base| base as usize <= dist).unwrap();
+    writer.write_huffman(index as u16, 5);
+    writer.write((dist - DIST_BASE[index] as usize) as u32, DIST_EXTRA[index] as u32);
+}
+
+// Packs bits LSB-first, as deflate requires
+struct BitWriter {
+    bytes: Vec<u8>,
+    acc:   u32,
+    bits:  u32,
+}
+
+impl BitWriter {
+    fn new() -> BitWriter {
+        BitWriter {
+            bytes: Vec::new(),
+            acc:   0,
+            bits:  0,
+        }
+    }
+
+    fn write(&mut self, value: u32, size: u32) {
+        self.acc |= value << self.bits;
+        self.bits += size;
+        while self.bits >= 8 {
+            self.bytes.push(self.acc as u8);
+            self.acc >>= 8;
+            self.bits -= 8;
+        }
+    }
+
+    // Huffman codes are stored starting from their most significant bit
+    fn write_huffman(&mut self, code: u16, size: u32) {
+        let reversed = (code.reverse_bits() >> (16 - size)) as u32;
+        self.write(reversed, size);
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.bits > 0 {
+            self.bytes.push(self.acc as u8);
+        }
+        self.bytes
+    }
+}
@@ -0,0 +1,171 @@
+extern crate dinky;
+
+mod common;
+
+use dinky::bitmap::Bitmap;
+use dinky::bmp;
+use dinky::pixel::Pixel;
+
+use common::argb;
+
+fn put_u32(out: &mut Vec<u8>, v: u32) {
+    out.extend_from_slice(&[v as u8, (v >> 8) as u8, (v >> 16) as u8, (v >> 24) as u8]);
+}
+
+// 24-bit BI_RGB file with a plain info header; rows are given top to bottom
+// and stored in the order the sign of the height says
+fn rgb24(rows: &[&[(u8, u8, u8)]], top_down: bool) -> Vec<u8> {
+    let (w, h) = (rows[0].len(), rows.len());
+    let stride = (w*3).div_ceil(4) * 4;
+
+    let mut out = Vec::new();
+    out.extend_from_slice(b"BM");
+    put_u32(&mut out, (54 + stride*h) as u32);
+    put_u32(&mut out, 0);
+    put_u32(&mut out, 54);
+
+    put_u32(&mut out, 40);
+    put_u32(&mut out, w as u32);
+    put_u32(&mut out, if top_down {(h as i32).wrapping_neg() as u32} else {h as u32});
+    out.extend_from_slice(&[1, 0, 24, 0]);
+    for _ in 0..6 {
+        put_u32(&mut out, 0);
+    }
+
+    let order: Vec<usize> = if top_down {(0..h).collect()} else {(0..h).rev().collect()};
+    for y in order {
+        for &(r, g, b) in rows[y] {
+            out.extend_from_slice(&[b, g, r]);
+        }
+        out.resize(out.len() + stride - w*3, 0);
+    }
+
+    out
+}
+
+#[test]
+fn round_trip() {
+    let mut bitmap = Bitmap::new(5, 3);
+    for y in 0..3 {
+        for x in 0..5 {
+            let v = (x*50 + y*20) as u8;
+            bitmap.set(x, y, &Pixel::pack_rgb(v, 255 - v, v / 2));
+        }
+    }
+    bitmap.set(0, 0, &Pixel::pack_argb(0, 0, 0, 0));
+    bitmap.set(4, 2, &Pixel::pack_unpremul_argb(128, 255, 0, 64));
+
+    let decoded = bmp::decode(&bmp::encode(&bitmap)).unwrap();
+
+    assert_eq!((decoded.width, decoded.height), (5, 3));
+    for y in 0..3 {
+        for x in 0..5 {
+            assert_eq!(argb(&decoded.get(x, y)), argb(&bitmap.get(x, y)), "at {},{}", x, y);
+        }
+    }
+}
+
+#[test]
+fn bottom_up_and_top_down_rows() {
+    let top:    &[(u8, u8, u8)] = &[(255, 0, 0), (0, 255, 0), (0, 0, 255)];
+    let bottom: &[(u8, u8, u8)] = &[(10, 20, 30), (40, 50, 60), (70, 80, 90)];
+
+    for &top_down in &[false, true] {
+        let bitmap = bmp::decode(&rgb24(&[top, bottom], top_down)).unwrap();
+
+        assert_eq!((bitmap.width, bitmap.height), (3, 2));
+        for x in 0..3 {
+            let (r, g, b) = top[x];
+            assert_eq!(argb(&bitmap.get(x, 0)), (255, r, g, b));
+            let (r, g, b) = bottom[x];
+            assert_eq!(argb(&bitmap.get(x, 1)), (255, r, g, b));
+        }
+    }
+}
+
+#[test]
+fn palette_images() {
+    // 3x2 with a three entry palette, stored bottom-up with rows padded to 4 bytes
+    let mut bytes = Vec::new();
+    bytes.extend_from_slice(b"BM");
+    put_u32(&mut bytes, 54 + 12 + 8);
+    put_u32(&mut bytes, 0);
+    put_u32(&mut bytes, 54 + 12);
+
+    put_u32(&mut bytes, 40);
+    put_u32(&mut bytes, 3);
+    put_u32(&mut bytes, 2);
+    bytes.extend_from_slice(&[1, 0, 8, 0]);
+    for &v in &[0, 8, 2835, 2835, 3, 0] {
+        put_u32(&mut bytes, v);
+    }
+    // BGRx entries
+    bytes.extend_from_slice(&[0, 0, 255, 0, 30, 20, 10, 0, 255, 255, 255, 0]);
+    bytes.extend_from_slice(&[2, 1, 0, 0, 0, 1, 2, 0]);
+
+    let bitmap = bmp::decode(&bytes).unwrap();
+    let palette = [(255, 255, 0, 0), (255, 10, 20, 30), (255, 255, 255, 255)];
+    assert_eq!((bitmap.width, bitmap.height), (3, 2));
+    for (x, &i) in [0, 1, 2].iter().enumerate() {
+        assert_eq!(argb(&bitmap.get(x, 0)), palette[i]);
+    }
+    for (x, &i) in [2, 1, 0].iter().enumerate() {
+        assert_eq!(argb(&bitmap.get(x, 1)), palette[i]);
+    }
+
+    // Indices past the palette are an error, not black
+    let last = bytes.len() - 2;
+    bytes[last] = 3;
+    assert!(bmp::decode(&bytes).is_err());
+}
+
+#[test]
+fn bitfield_alpha() {
+    // 2x1 top-down V5 file with RGBA masks, unlike the BGRA ones encode writes
+    let mut bytes = Vec::new();
+    bytes.extend_from_slice(b"BM");
+    put_u32(&mut bytes, 14 + 124 + 8);
+    put_u32(&mut bytes, 0);
+    put_u32(&mut bytes, 14 + 124);
+
+    put_u32(&mut bytes, 124);
+    put_u32(&mut bytes, 2);
+    put_u32(&mut bytes, (-1i32) as u32);
+    bytes.extend_from_slice(&[1, 0, 32, 0]);
+    for &v in &[3, 8, 2835, 2835, 0, 0, 0xff000000, 0x00ff0000, 0x0000ff00, 0x000000ff] {
+        put_u32(&mut bytes, v);
+    }
+    // Color space, endpoints, gamma, intent and profile are all left zero
+    bytes.resize(14 + 124, 0);
+
+    // Straight alpha, one half covered pixel and one transparent one
+    put_u32(&mut bytes, 0xff004080);
+    put_u32(&mut bytes, 0x0a141e00);
+
+    let bitmap = bmp::decode(&bytes).unwrap();
+    assert_eq!((bitmap.width, bitmap.height), (2, 1));
+    assert_eq!(argb(&bitmap.get(0, 0)), argb(&Pixel::pack_unpremul_argb(128, 255, 0, 64)));
+    assert_eq!(argb(&bitmap.get(1, 0)), (0, 0, 0, 0));
+}
+
+#[test]
+fn truncated_files_are_rejected() {
+    let bytes = bmp::encode(&Bitmap::new(4, 4));
+
+    // Inside the file header, the info header, and the pixel data
+    for &len in &[0, 2, 13, 17, 30, 121, bytes.len() - 1] {
+        assert!(bmp::decode(&bytes[..len]).is_err(), "accepted {} of {} bytes", len, bytes.len());
+    }
+    assert!(bmp::decode(&bytes).is_ok());
+}
+
+#[test]
+fn unsupported_headers_are_rejected() {
+    let mut bytes = rgb24(&[&[(1, 2, 3)]], false);
+    bytes[14] = 12; // OS/2 BITMAPCOREHEADER
+    assert!(bmp::decode(&bytes).is_err());
+
+    let mut bytes = rgb24(&[&[(1, 2, 3)]], false);
+    bytes[0] = b'X';
+    assert!(bmp::decode(&bytes).is_err());
+}
@@ -0,0 +1,95 @@
+extern crate dinky;
+
+use dinky::bitmap::Bitmap;
+use dinky::pixel::Pixel;
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+fn dinky(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_dinky")).args(args).output().unwrap()
+}
+
+fn code(args: &[&str]) -> i32 {
+    dinky(args).status.code().unwrap()
+}
+
+// A fresh directory per test, so they can run in parallel
+fn tmp_dir(name: &str) -> PathBuf {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("cli").join(name);
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn path(dir: &Path, name: &str) -> String {
+    dir.join(name).to_str().unwrap().to_string()
+}
+
+#[test]
+fn success_exits_zero() {
+    let dir = tmp_dir("success");
+    let (scene, out) = (path(&dir, "square.scene"), path(&dir, "square.qoi"));
+    fs::write(&scene, "size 8 6\nclear white\nrect 1 1 3 3 red\n").unwrap();
+
+    assert_eq!(code(&["render", &scene, "-o", &out]), 0);
+    let bitmap = Bitmap::load(Path::new(&out)).unwrap();
+    assert_eq!((bitmap.width, bitmap.height), (8, 6));
+
+    let copy = path(&dir, "copy.bmp");
+    assert_eq!(code(&["convert", &out, &copy]), 0);
+
+    let output = dinky(&["diff", &out, &copy]);
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "identical\n");
+
+    assert_eq!(code(&["help"]), 0);
+}
+
+#[test]
+fn differences_exit_one() {
+    let dir = tmp_dir("differences");
+    let (a, b, c) = (path(&dir, "a.qoi"), path(&dir, "b.qoi"), path(&dir, "c.qoi"));
+
+    let mut bitmap = Bitmap::new(4, 4);
+    bitmap.save(Path::new(&a)).unwrap();
+    bitmap.set(2, 1, &Pixel::pack_rgb(255, 0, 0));
+    bitmap.save(Path::new(&b)).unwrap();
+    Bitmap::new(5, 4).save(Path::new(&c)).unwrap();
+
+    let output = dinky(&["diff", &a, &b]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stdout).starts_with("1 of 16 pixels differ\n"));
+
+    assert_eq!(code(&["diff", &a, &c]), 1);
+}
+
+#[test]
+fn usage_and_failures_exit_two() {
+    let dir = tmp_dir("failures");
+    let bad = path(&dir, "bad.scene");
+    fs::write(&bad, "size 8 8\nrect 0 0 1 red\n").unwrap();
+
+    // No arguments prints the usage to stderr
+    let output = dinky(&[]);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(String::from_utf8_lossy(&output.stderr).starts_with("usage: dinky"));
+
+    assert_eq!(code(&["frobnicate"]), 2);
+    assert_eq!(code(&["render"]), 2);
+    assert_eq!(code(&["render", &bad, "--width", "0"]), 2);
+    assert_eq!(code(&["render", &bad, "--bogus", "1"]), 2);
+
+    // Sizes past the scene limit, given or kept from the aspect ratio
+    let thin = path(&dir, "thin.scene");
+    fs::write(&thin, "size 1 100\n").unwrap();
+    assert_eq!(code(&["render", &thin, "--width", "100000000"]), 2);
+    assert_eq!(code(&["render", &thin, "--height", "16385"]), 2);
+    assert_eq!(code(&["render", &thin, "--width", "200"]), 2);
+    assert_eq!(code(&["diff", &path(&dir, "missing.qoi"), &path(&dir, "missing.qoi")]), 2);
+
+    let output = dinky(&["render", &bad]);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("bad.scene:2:"));
+}
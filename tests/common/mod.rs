@@ -0,0 +1,22 @@
+// Helpers shared by the integration tests, pulled in with `mod common;`.
+// Each test crate uses only some of them.
+#![allow(dead_code)]
+
+use dinky::bitmap::Bitmap;
+use dinky::pixel::Pixel;
+
+// Every pixel different, so misplaced pixels show
+pub fn numbered(w: usize, h: usize) -> Bitmap {
+    let mut bitmap = Bitmap::new(w, h);
+    for y in 0..h {
+        for x in 0..w {
+            bitmap.set(x, y, &Pixel::pack_argb(255, x as u8, y as u8, 0));
+        }
+    }
+    bitmap
+}
+
+// Channels in a comparable, printable form
+pub fn argb(px: &Pixel) -> (u8, u8, u8, u8) {
+    (px.a, px.r, px.g, px.b)
+}
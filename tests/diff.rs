@@ -0,0 +1,66 @@
+extern crate dinky;
+
+use dinky::bitmap::Bitmap;
+use dinky::pixel::Pixel;
+
+fn gradient(w: usize, h: usize) -> Bitmap {
+    let mut bitmap = Bitmap::new(w, h);
+    for y in 0..h {
+        for x in 0..w {
+            let v = ((x + y) * 255 / (w + h)) as u8;
+            bitmap.set(x, y, &Pixel::pack_rgb(v, v / 2, 255 - v));
+        }
+    }
+    bitmap
+}
+
+#[test]
+fn identical_bitmaps() {
+    let a = gradient(20, 12);
+    let stats = a.diff(&a.clone());
+
+    assert_eq!(stats.max_delta, 0);
+    assert_eq!(stats.differing, 0);
+    assert!(stats.psnr.is_infinite());
+    assert!(stats.bounds.is_none());
+    assert!((a.ssim(&a) - 1.0).abs() < 1e-9);
+}
+
+#[test]
+fn changed_pixels() {
+    let a = gradient(20, 12);
+    let mut b = a.clone();
+    b.set(3, 4, &Pixel::pack_argb(0, 0, 0, 0));
+    b.set(9, 7, &Pixel::pack_argb(128, 0, 0, 0));
+
+    let stats = a.diff(&b);
+    assert_eq!(stats.differing, 2);
+    assert_eq!(stats.max_delta, 255);
+    assert!(stats.psnr.is_finite() && stats.psnr > 0.0);
+
+    let bounds = stats.bounds.unwrap();
+    assert_eq!((bounds.left(), bounds.top(), bounds.right(), bounds.bottom()), (3.0, 4.0, 10.0, 8.0));
+
+    assert!(a.ssim(&b) < 1.0);
+
+    let image = a.diff_image(&b, 0);
+    assert_eq!(image.get(3, 4).r, 255);
+    assert_eq!(image.get(3, 4).g, 0);
+    assert_eq!(image.get(0, 0).r, image.get(0, 0).g);
+}
+
+#[test]
+fn ssim_prefers_structure() {
+    let a = gradient(32, 32);
+
+    // A uniform brightness shift keeps the structure; noise does not
+    let mut shifted = a.clone();
+    let mut noisy   = a.clone();
+    for (i, (s, n)) in shifted.pixels.iter_mut().zip(noisy.pixels.iter_mut()).enumerate() {
+        *s = Pixel::pack_rgb(s.r.saturating_add(8), s.g.saturating_add(8), s.b.saturating_sub(8));
+        let d = if i % 2 == 0 { 8 } else { 0 };
+        *n = Pixel::pack_rgb(n.r.saturating_add(d), n.g.saturating_add(d), n.b.saturating_sub(d));
+    }
+
+    assert!(a.ssim(&shifted) > a.ssim(&noisy));
+}
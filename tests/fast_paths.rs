@@ -0,0 +1,82 @@
+extern crate dinky;
+
+use dinky::bitmap::Bitmap;
+use dinky::canvas::Canvas;
+use dinky::color::Color;
+use dinky::context::DrawContext;
+use dinky::point::Point;
+use dinky::rect::Rect;
+use dinky::shader::Shaders;
+use dinky::util::map_rect_to_rect_mat;
+
+// Checkerboard with a translucent half, so copies and blends both show up
+fn checker(w: usize, h: usize) -> Bitmap {
+    let mut bitmap = Bitmap::new(w, h);
+    for y in 0..h {
+        for x in 0..w {
+            let color = if (x + y) % 2 == 0 { Color::red() } else { Color::make_argb(0.5, 0.0, 0.25, 0.5) };
+            let color = if y < h/2 { Color::blue() } else { color };
+            bitmap.set(x, y, &color.to_pixel());
+        }
+    }
+    bitmap
+}
+
+// Rects drawn through shade_rect's fast paths must match the same rects
+// drawn as polygons, which always go through the rasterizer
+fn check(setup: &dyn Fn(&mut Canvas), rect: Rect, src: Option<&Bitmap>) {
+    let background = Color::make_argb(0.75, 0.0, 0.5, 0.25);
+    let corners = [
+        Point::new(rect.right(), rect.top()),
+        Point::new(rect.right(), rect.bottom()),
+        Point::new(rect.left(),  rect.bottom()),
+        Point::new(rect.left(),  rect.top()),
+    ];
+
+    let mut fast = Canvas::new(Bitmap::new(60, 40));
+    let mut slow = Canvas::new(Bitmap::new(60, 40));
+    for canvas in [&mut fast, &mut slow].iter_mut() {
+        canvas.clear(&background);
+        setup(canvas);
+    }
+
+    match src {
+        Some(src) => {
+            let local = map_rect_to_rect_mat(&Rect::make_wh(src.width as f32, src.height as f32), &rect);
+            fast.fill_bitmap_rect(src, &rect);
+            slow.shade_convex_polygon(&corners, &mut Shaders::from_bitmap(src, local.get_floats()));
+        },
+        None => {
+            for &color in [Color::green(), Color::make_argb(0.3, 0.1, 0.2, 0.3)].iter() {
+                fast.fill_rect(&rect, &color);
+                slow.fill_convex_polygon(&corners, &color);
+            }
+        },
+    }
+
+    assert_eq!(fast.bitmap().diff(slow.bitmap()).differing, 0);
+}
+
+#[test]
+fn colors_match_the_rasterizer() {
+    check(&|_| {}, Rect::make_xywh(5.0, 5.0, 30.0, 20.0), None);
+    check(&|c| c.translate(-3.25, 7.5), Rect::make_xywh(-10.0, 2.0, 50.0, 50.0), None);
+    check(&|c| c.scale(1.7, 0.6), Rect::make_xywh(3.0, 9.0, 21.0, 33.0), None);
+    check(&|c| { c.translate(60.0, 0.0); c.scale(-1.3, 1.0); }, Rect::make_xywh(4.0, 4.0, 20.0, 20.0), None);
+    check(&|c| c.clip_rect(&Rect::make_ltrb(10.5, 3.25, 41.75, 30.0)), Rect::make_wh(60.0, 40.0), None);
+}
+
+#[test]
+fn bitmaps_match_the_rasterizer() {
+    let src = checker(12, 10);
+
+    // Unscaled at whole-pixel offsets, partly off the canvas
+    check(&|_| {}, Rect::make_xywh(3.0, 4.0, 12.0, 10.0), Some(&src));
+    check(&|c| c.translate(52.0, -4.0), Rect::make_xywh(0.0, 0.0, 12.0, 10.0), Some(&src));
+
+    // Scaled, fractionally translated, mirrored and clipped
+    check(&|_| {}, Rect::make_xywh(2.0, 1.0, 40.0, 30.0), Some(&src));
+    check(&|c| c.translate(0.5, 0.25), Rect::make_xywh(3.0, 4.0, 12.0, 10.0), Some(&src));
+    check(&|c| { c.translate(0.0, 40.0); c.scale(1.0, -1.0); }, Rect::make_xywh(3.0, 4.0, 12.0, 10.0), Some(&src));
+    check(&|c| c.clip_rect(&Rect::make_ltrb(6.0, 6.0, 12.5, 11.0)), Rect::make_xywh(3.0, 4.0, 12.0, 10.0), Some(&src));
+}
@@ -0,0 +1,297 @@
+extern crate dinky;
+
+use dinky::bitmap::Bitmap;
+use dinky::gif::{self, Frame, Options};
+use dinky::pixel::Pixel;
+
+use std::io;
+
+// What a decoder sees of one frame
+struct Image {
+    dispose:  u8,
+    palette:  Vec<[u8; 3]>,
+    indices:  Vec<u8>,
+    max_code: u32,    // Widest LZW code read
+    clears:   usize,  // Clear codes past the leading one
+}
+
+impl Image {
+    fn rgb(&self, i: usize) -> [u8; 3] {
+        self.palette[self.indices[i] as usize]
+    }
+}
+
+fn decode(bytes: &[u8]) -> Vec<Image> {
+    assert_eq!(&bytes[..6], b"GIF89a");
+    let mut pos = 13;
+    let mut dispose = 0;
+    let mut images = Vec::new();
+
+    loop {
+        match bytes[pos] {
+            0x21 => {
+                if bytes[pos + 1] == 0xf9 {
+                    dispose = bytes[pos + 3] >> 2 & 7;
+                }
+                pos += 2;
+                while bytes[pos] != 0 {
+                    pos += bytes[pos] as usize + 1;
+                }
+                pos += 1;
+            },
+            0x2c => {
+                let flags = bytes[pos + 9];
+                let size = 1 << ((flags & 7) + 1);
+                pos += 10;
+                let palette = (0..size).map(|i| {
+                    let e = &bytes[pos + 3*i..];
+                    [e[0], e[1], e[2]]
+                }).collect();
+                pos += 3*size;
+
+                let min_code_size = bytes[pos] as u32;
+                pos += 1;
+                let mut data = Vec::new();
+                while bytes[pos] != 0 {
+                    let len = bytes[pos] as usize;
+                    data.extend_from_slice(&bytes[pos + 1..pos + 1 + len]);
+                    pos += len + 1;
+                }
+                pos += 1;
+
+                let (indices, max_code, clears) = lzw_decode(&data, min_code_size);
+                images.push(Image {
+                    dispose:  dispose,
+                    palette:  palette,
+                    indices:  indices,
+                    max_code: max_code,
+                    clears:   clears,
+                });
+            },
+            0x3b => return images,
+            b    => panic!("unexpected block {:#x}", b),
+        }
+    }
+}
+
+// The reference decoder: the code size grows once the table fills the
+// current width, and a clear code starts over
+fn lzw_decode(data: &[u8], min_code_size: u32) -> (Vec<u8>, u32, usize) {
+    let clear = 1usize << min_code_size;
+    let eoi   = clear + 1;
+
+    let reset = || -> Vec<Vec<u8>> {
+        let mut table: Vec<Vec<u8>> = (0..clear).map(|i| vec![i as u8]).collect();
+        table.push(Vec::new());
+        table.push(Vec::new());
+        table
+    };
+
+    let mut table = reset();
+    let mut code_size = min_code_size + 1;
+    let mut max_code = code_size;
+    let mut clears = 0;
+    let mut prev: Option<Vec<u8>> = None;
+    let mut out = Vec::new();
+    let mut bit = 0;
+
+    loop {
+        let mut code = 0;
+        for i in 0..code_size as usize {
+            let b = data[(bit + i) / 8] >> ((bit + i) % 8) & 1;
+            code |= (b as usize) << i;
+        }
+        bit += code_size as usize;
+
+        if code == clear {
+            if bit > code_size as usize {
+                clears += 1;
+            }
+            table = reset();
+            code_size = min_code_size + 1;
+            prev = None;
+            continue;
+        }
+        if code == eoi {
+            return (out, max_code, clears);
+        }
+
+        let entry = if code < table.len() {
+            table[code].clone()
+        } else {
+            let mut entry = prev.clone().expect("code before any literal");
+            assert_eq!(code, table.len(), "code out of range");
+            entry.push(entry[0]);
+            entry
+        };
+        out.extend_from_slice(&entry);
+
+        if let Some(mut p) = prev {
+            if table.len() < 4096 {
+                p.push(entry[0]);
+                table.push(p);
+            }
+        }
+        prev = Some(entry);
+
+        if table.len() == 1 << code_size && code_size < 12 {
+            code_size += 1;
+            max_code = max_code.max(code_size);
+        }
+    }
+}
+
+// xorshift, so the noise is the same every run
+fn noise(seed: &mut u32) -> u32 {
+    *seed ^= *seed << 13;
+    *seed ^= *seed >> 17;
+    *seed ^= *seed << 5;
+    *seed
+}
+
+fn encode_one(bitmap: Bitmap, dither: bool) -> Image {
+    let mut options = Options::new();
+    options.dither = dither;
+    let bytes = gif::encode(&[Frame::new(bitmap, 0)], &options).unwrap();
+    decode(&bytes).remove(0)
+}
+
+#[test]
+fn lzw_code_size_grows_and_resets() {
+    // 256 colors in noise fill the code table several times over
+    let mut colors = Vec::new();
+    for i in 0..256u32 {
+        colors.push(Pixel::pack_rgb(i as u8, (i*7) as u8, (i*13) as u8));
+    }
+
+    let mut seed = 1;
+    let mut bitmap = Bitmap::new(256, 64);
+    for px in bitmap.pixels.iter_mut() {
+        *px = colors[noise(&mut seed) as usize % 256];
+    }
+
+    let image = encode_one(bitmap.clone(), false);
+
+    assert_eq!(image.max_code, 12);
+    assert!(image.clears > 0);
+    for (i, px) in bitmap.pixels.iter().enumerate() {
+        assert_eq!(image.rgb(i), [px.r, px.g, px.b]);
+    }
+}
+
+#[test]
+fn lzw_small_code_size() {
+    // Two colors start at the minimum of 2 bits, so codes begin 3 wide
+    let mut seed = 7;
+    let mut bitmap = Bitmap::new(100, 40);
+    for px in bitmap.pixels.iter_mut() {
+        let v = if noise(&mut seed) & 1 == 0 {0} else {255};
+        *px = Pixel::pack_rgb(v, v, v);
+    }
+
+    let image = encode_one(bitmap.clone(), false);
+
+    assert_eq!(image.palette.len(), 2);
+    assert!(image.max_code > 3);
+    for (i, px) in bitmap.pixels.iter().enumerate() {
+        assert_eq!(image.rgb(i), [px.r, px.g, px.b]);
+    }
+}
+
+fn ramp() -> Bitmap {
+    // 768 distinct colors, constant down each column
+    let mut bitmap = Bitmap::new(768, 16);
+    for y in 0..16 {
+        for x in 0..768 {
+            let v = x / 3;
+            bitmap.set(x, y, &Pixel::pack_rgb(v as u8, (x % 3 * 40) as u8, 255 - v as u8));
+        }
+    }
+    bitmap
+}
+
+#[test]
+fn quantizer_stays_close() {
+    let bitmap = ramp();
+    let image = encode_one(bitmap.clone(), false);
+
+    assert_eq!(image.palette.len(), 256);
+    let mut total = 0;
+    for (i, px) in bitmap.pixels.iter().enumerate() {
+        let rgb = image.rgb(i);
+        let err = [px.r, px.g, px.b].iter().zip(&rgb)
+                                    .map(|(&a, &b)| (a as i32 - b as i32).abs())
+                                    .max().unwrap();
+        assert!(err <= 24, "pixel {} off by {}", i, err);
+        total += err;
+    }
+    assert!(total / (bitmap.pixels.len() as i32) < 8);
+}
+
+#[test]
+fn dithering_preserves_local_averages() {
+    // Far more colors than fit, so every pixel carries some error
+    let mut bitmap = Bitmap::new(256, 64);
+    for y in 0..64 {
+        for x in 0..256 {
+            bitmap.set(x, y, &Pixel::pack_rgb(x as u8, (y*4) as u8, 128));
+        }
+    }
+    let plain    = encode_one(bitmap.clone(), false);
+    let dithered = encode_one(bitmap.clone(), true);
+
+    // How far the average of each 8x8 block lands from the source, summed
+    let block_error = |image: &Image| -> i64 {
+        let mut error = 0;
+        for by in 0..8 {
+            for bx in 0..32 {
+                for c in 0..3 {
+                    let mut sum = 0;
+                    for y in by*8..by*8 + 8 {
+                        for x in bx*8..bx*8 + 8 {
+                            let px = bitmap.get(x, y);
+                            sum += image.rgb(x + y*256)[c] as i64 - [px.r, px.g, px.b][c] as i64;
+                        }
+                    }
+                    error += sum.abs();
+                }
+            }
+        }
+        error
+    };
+
+    assert!(block_error(&dithered) * 2 < block_error(&plain));
+    assert!(plain.indices != dithered.indices);
+}
+
+#[test]
+fn dispose_is_set_per_frame() {
+    let mut opaque = Bitmap::new(4, 4);
+    for px in opaque.pixels.iter_mut() {
+        *px = Pixel::pack_rgb(10, 20, 30);
+    }
+    let mut holes = opaque.clone();
+    holes.set(1, 1, &Pixel::pack_argb(0, 0, 0, 0));
+
+    // Each frame is cleared only when the next one has holes
+    let frames = [Frame::new(opaque.clone(), 5), Frame::new(holes, 5), Frame::new(opaque, 5)];
+    let images = decode(&gif::encode(&frames, &Options::new()).unwrap());
+
+    let dispose: Vec<u8> = images.iter().map(|image| image.dispose).collect();
+    assert_eq!(dispose, [2, 1, 1]);
+}
+
+#[test]
+fn bad_frames_are_errors() {
+    let options = Options::new();
+
+    let err = gif::encode(&[], &options).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+
+    let frames = [Frame::new(Bitmap::new(4, 4), 0), Frame::new(Bitmap::new(4, 5), 0)];
+    let err = gif::encode(&frames, &options).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+
+    let err = gif::encode(&[Frame::new(Bitmap::new(0x10000, 1), 0)], &options).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+}
@@ -0,0 +1,90 @@
+// Renders every built-in gallery image and compares it against the checked-in
+// reference in tests/golden. Run with DINKY_BLESS=1 to regenerate them.
+
+extern crate dinky;
+
+use dinky::gallery;
+use dinky::qoi;
+use dinky::png;
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// Largest per-channel difference a pixel may have and still match
+const TOLERANCE: u8 = 0;
+
+fn golden_path(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join("golden").join(name).with_extension("qoi")
+}
+
+fn failure_dir() -> PathBuf {
+    Path::new(env!("CARGO_TARGET_TMPDIR")).join("golden")
+}
+
+fn check(name: &str) {
+    let image = gallery::find(name).unwrap_or_else(|| panic!("no gallery image named {}", name));
+    let actual = image.render();
+    let path = golden_path(name);
+
+    if env::var_os("DINKY_BLESS").is_some() {
+        qoi::write(&actual, &path).unwrap();
+        return;
+    }
+
+    let expected = qoi::read(&path).unwrap_or_else(|err| {
+        panic!("{}: {} (run with DINKY_BLESS=1 to create it)", path.display(), err)
+    });
+    assert_eq!((actual.width, actual.height), (expected.width, expected.height),
+               "{} changed size", name);
+
+    let stats = actual.diff(&expected);
+    if stats.max_delta > TOLERANCE {
+        let dir = failure_dir();
+        fs::create_dir_all(&dir).unwrap();
+        let actual_path = dir.join(format!("{}.actual.png", name));
+        let diff_path   = dir.join(format!("{}.diff.png", name));
+        png::write(&actual, &actual_path).unwrap();
+        png::write(&expected.diff_image(&actual, TOLERANCE), &diff_path).unwrap();
+
+        panic!("{}: differs from {} by up to {} (tolerance {}) in {} pixels within {:?}\n  actual: {}\n  diff:   {}",
+               name, path.display(), stats.max_delta, TOLERANCE, stats.differing, stats.bounds.unwrap(),
+               actual_path.display(), diff_path.display());
+    }
+}
+
+macro_rules! golden_tests {
+    ($($name:ident),*) => {
+        const TESTED: &[&str] = &[$(stringify!($name)),*];
+
+        $(
+            #[test]
+            fn $name() {
+                check(stringify!($name));
+            }
+        )*
+    }
+}
+
+golden_tests!(
+    solid_ramp,
+    blend_black,
+    blend_white,
+    spocks_quad,
+    spocks_zoom,
+    circles_blend,
+    circles_fat,
+    tri,
+    tri_clipped,
+    poly,
+    poly_center,
+    poly_rotate,
+    translate_rect
+);
+
+#[test]
+fn every_gallery_image_is_tested() {
+    for image in gallery::IMAGES.iter() {
+        assert!(TESTED.contains(&image.name), "{} has no golden test", image.name);
+    }
+}
@@ -0,0 +1,71 @@
+extern crate dinky;
+
+use dinky::bitmap::Bitmap;
+use dinky::canvas::Canvas;
+use dinky::color::Color;
+use dinky::context::DrawContext;
+use dinky::matrix::{Matrix, MatrixType};
+use dinky::rect::Rect;
+use dinky::shader::Shaders;
+
+fn assert_close(a: &Matrix, b: &Matrix) {
+    for (x, y) in a.get_floats_3x3().iter().zip(b.get_floats_3x3().iter()) {
+        assert!((x - y).abs() < 1e-4, "{:?} != {:?}", a, b);
+    }
+}
+
+#[test]
+fn invert_rejects_singular_matrices() {
+    assert!(Matrix::new([0.0, 0.0, 5.0, 0.0, 1.0, 0.0]).invert().is_none());
+    assert!(Matrix::new([1.0, 2.0, 0.0, 2.0, 4.0, 0.0]).invert().is_none());
+    assert!(Matrix::from_3x3([1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 1.0, 0.0, 0.0]).invert().is_none());
+
+    let mat = Matrix::new([2.0, 1.0, 3.0, -1.0, 0.5, 7.0]);
+    assert_close(&mat.mul(&mat.invert().unwrap()), &Matrix::identity());
+}
+
+#[test]
+fn types_are_classified() {
+    assert_eq!(Matrix::identity().get_type(), MatrixType::Identity);
+    assert_eq!(Matrix::new([1.0, 0.0, 3.0, 0.0, 1.0, -2.0]).get_type(), MatrixType::Translate);
+    assert_eq!(Matrix::new([2.0, 0.0, 3.0, 0.0, -1.0, 0.0]).get_type(), MatrixType::ScaleTranslate);
+    assert_eq!(Matrix::new([1.0, 0.5, 0.0, 0.0, 1.0, 0.0]).get_type(), MatrixType::Affine);
+    assert_eq!(Matrix::from_3x3([1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.1, 0.0, 1.0]).get_type(), MatrixType::Perspective);
+
+    assert!(MatrixType::Translate < MatrixType::ScaleTranslate);
+}
+
+#[test]
+fn decomposition_round_trips() {
+    let mats = [
+        Matrix::identity(),
+        Matrix::new([2.0, 1.0, 3.0, -1.0, 0.5, 7.0]),
+        Matrix::new([0.0, -3.0, 1.0, 2.0, 0.0, -4.0]),
+        Matrix::new([1.0, 0.0, 0.0, 0.0, -1.0, 10.0]),
+        Matrix::new([-1.0, 0.0, 0.0, 0.0, 1.0, 0.0]),
+    ];
+    for mat in mats.iter() {
+        assert_close(&mat.decompose().unwrap().to_matrix(), mat);
+    }
+
+    // A plain rotation has no skew and unit scale
+    let (sin, cos) = 0.5f32.sin_cos();
+    let parts = Matrix::new([cos, -sin, 0.0, sin, cos, 0.0]).decompose().unwrap();
+    assert!((parts.rotate - 0.5).abs() < 1e-6 && parts.skew_x.abs() < 1e-6);
+    assert!((parts.scale_x - 1.0).abs() < 1e-6 && (parts.scale_y - 1.0).abs() < 1e-6);
+
+    assert!(Matrix::new([0.0, 0.0, 5.0, 0.0, 1.0, 0.0]).decompose().is_none());
+}
+
+#[test]
+fn singular_shader_matrices_skip_the_draw() {
+    let mut src = Bitmap::new(4, 4);
+    src.set(0, 0, &Color::red().to_pixel());
+
+    let mut canvas = Canvas::new(Bitmap::new(16, 16));
+    canvas.shade_rect(&Rect::make_wh(16.0, 16.0), &mut Shaders::from_bitmap(&src, [0.0; 6]));
+    canvas.scale(0.0, 1.0);
+    canvas.fill_bitmap_rect(&src, &Rect::make_wh(16.0, 16.0));
+
+    assert!(canvas.bitmap().pixels.iter().all(|px| px.a == 0));
+}
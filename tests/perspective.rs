@@ -0,0 +1,97 @@
+extern crate dinky;
+
+use dinky::bitmap::Bitmap;
+use dinky::canvas::Canvas;
+use dinky::color::Color;
+use dinky::context::DrawContext;
+use dinky::matrix::Matrix;
+use dinky::picture::PictureRecorder;
+use dinky::point::Point;
+use dinky::rect::Rect;
+use dinky::serialize;
+
+// w = 1 + x/200, so the square 0,0 - 100,100 becomes a trapezoid whose
+// right edge shrinks to x = y = 66.7
+fn keystone() -> Matrix {
+    Matrix::from_3x3([1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.005, 0.0, 1.0])
+}
+
+fn square() -> Rect {
+    Rect::make_wh(100.0, 100.0)
+}
+
+#[test]
+fn apply_divides_by_w() {
+    let mat = keystone();
+    let p = mat.apply(&Point::new(100.0, 100.0));
+    assert!((p.x - 66.666).abs() < 0.01 && (p.y - 66.666).abs() < 0.01);
+
+    let bounds = mat.map_rect(&square());
+    assert_eq!((bounds.left(), bounds.top()), (0.0, 0.0));
+    assert!((bounds.right() - 66.666).abs() < 0.01 && bounds.bottom() == 100.0);
+
+    // And the inverse undoes it
+    let back = mat.invert().unwrap().apply(&p);
+    assert!((back.x - 100.0).abs() < 0.01 && (back.y - 100.0).abs() < 0.01);
+}
+
+#[test]
+fn fills_are_projected() {
+    let mut canvas = Canvas::new(Bitmap::new(100, 100));
+    canvas.concat_matrix(&keystone());
+    canvas.fill_rect(&square(), &Color::black());
+    let bitmap = canvas.bitmap();
+
+    assert_eq!(bitmap.get(5, 95).a, 255);
+    assert_eq!(bitmap.get(60, 5).a, 255);
+    assert_eq!(bitmap.get(60, 80).a, 0);
+    assert_eq!(bitmap.get(80, 10).a, 0);
+}
+
+#[test]
+fn bitmap_sampling_is_perspective_correct() {
+    // Red on the left half, blue on the right
+    let mut src = Canvas::new(Bitmap::new(2, 1));
+    src.fill_rect(&Rect::make_wh(1.0, 1.0), &Color::red());
+    src.fill_rect(&Rect::make_xywh(1.0, 0.0, 1.0, 1.0), &Color::blue());
+
+    let mut canvas = Canvas::new(Bitmap::new(100, 100));
+    canvas.concat_matrix(&keystone());
+    canvas.fill_bitmap_rect(src.bitmap(), &square());
+    let bitmap = canvas.bitmap();
+
+    // The source midline lands at x = 50/1.25 = 40, not at the affine 33.3
+    assert_eq!(bitmap.get(37, 10).r, 255);
+    assert_eq!(bitmap.get(43, 10).b, 255);
+}
+
+#[test]
+fn geometry_behind_the_eye_is_clipped() {
+    // For x < 50, w > 0 and everything maps left of the canvas. For x > 50,
+    // w < 0 and dividing anyway would land points inside the canvas.
+    let mat = Matrix::from_3x3([1.0, 0.0, -100.0, 0.0, -1.0, 0.0, -0.02, 0.0, 1.0]);
+
+    let mut canvas = Canvas::new(Bitmap::new(100, 100));
+    canvas.concat_matrix(&mat);
+    canvas.fill_rect(&square(), &Color::black());
+    canvas.fill_convex_polygon(&[Point::new(0.0, 0.0), Point::new(100.0, 50.0), Point::new(0.0, 100.0)], &Color::black());
+
+    assert!(canvas.bitmap().pixels.iter().all(|px| px.a == 0));
+}
+
+#[test]
+fn pictures_keep_perspective() {
+    let mut recorder = PictureRecorder::new(square());
+    recorder.concat_matrix(&keystone());
+    recorder.fill_rect(&Rect::make_ltrb(10.0, 10.0, 90.0, 90.0), &Color::green());
+    let picture = serialize::decode(&serialize::encode(&recorder.finish())).unwrap();
+
+    let mut direct = Canvas::new(Bitmap::new(100, 100));
+    direct.concat_matrix(&keystone());
+    direct.fill_rect(&Rect::make_ltrb(10.0, 10.0, 90.0, 90.0), &Color::green());
+
+    let mut replayed = Canvas::new(Bitmap::new(100, 100));
+    picture.playback(&mut replayed, [1.0, 0.0, 0.0, 0.0, 1.0, 0.0]);
+
+    assert_eq!(direct.bitmap().diff(replayed.bitmap()).differing, 0);
+}
@@ -0,0 +1,93 @@
+extern crate dinky;
+
+mod common;
+
+use dinky::bitmap::Bitmap;
+use dinky::canvas::Canvas;
+use dinky::color::Color;
+use dinky::context::DrawContext;
+use dinky::picture::PictureRecorder;
+use dinky::rect::Rect;
+
+use common::argb;
+
+fn filled(w: usize, h: usize, color: &Color) -> Canvas {
+    let mut canvas = Canvas::new(Bitmap::new(w, h));
+    canvas.clear(color);
+    canvas
+}
+
+#[test]
+fn playback_clears_only_the_cull_rect() {
+    let red  = Color::make_argb(1.0, 1.0, 0.0, 0.0);
+    let blue = Color::make_argb(1.0, 0.0, 0.0, 1.0);
+
+    let mut recorder = PictureRecorder::new(Rect::make_wh(10.0, 8.0));
+    // Clears ignore the CTM they were recorded under
+    recorder.save();
+    recorder.rotate(0.3);
+    recorder.clear(&red);
+    recorder.restore();
+    recorder.fill_rect(&Rect::make_xywh(2.0, 2.0, 3.0, 3.0), &blue);
+    let picture = recorder.finish();
+    let cull = picture.cull_rect();
+    assert_eq!((cull.left(), cull.top(), cull.right(), cull.bottom()), (0.0, 0.0, 10.0, 8.0));
+
+    // Scaled by 2 and moved, the clear covers 20x16 pixels at (5, 7)
+    let mut canvas = filled(40, 40, &Color::white());
+    picture.playback(&mut canvas, [2.0, 0.0, 5.0, 0.0, 2.0, 7.0]);
+
+    for y in 0..40 {
+        for x in 0..40 {
+            let inside = |l, t, w, h| x >= l && x < l + w && y >= t && y < t + h;
+            let expected = if inside(9, 11, 6, 6) {
+                blue.to_pixel()
+            } else if inside(5, 7, 20, 16) {
+                red.to_pixel()
+            } else {
+                Color::white().to_pixel()
+            };
+            assert_eq!(argb(&canvas.bitmap().get(x, y)), argb(&expected), "({}, {})", x, y);
+        }
+    }
+}
+
+#[test]
+fn clear_replaces_pixels_inside_the_clip() {
+    let clear = Color::make_argb(0.5, 0.0, 1.0, 0.0);
+
+    let mut canvas = filled(20, 20, &Color::black());
+    canvas.save();
+    canvas.translate(3.0, 4.0);
+    canvas.clip_rect(&Rect::make_wh(10.0, 5.0));
+    canvas.clear(&clear);
+    canvas.restore();
+
+    // Translucent colors are stored as they are, not blended
+    for y in 0..20 {
+        for x in 0..20 {
+            let inside = (3..13).contains(&x) && (4..9).contains(&y);
+            let expected = if inside {clear.to_pixel()} else {Color::black().to_pixel()};
+            assert_eq!(argb(&canvas.bitmap().get(x, y)), argb(&expected), "({}, {})", x, y);
+        }
+    }
+}
+
+#[test]
+fn mirrored_clips_clear_the_same_pixels() {
+    let red = Color::make_argb(1.0, 1.0, 0.0, 0.0);
+
+    let mut plain = filled(16, 16, &Color::white());
+    plain.clip_rect(&Rect::make_xywh(2.0, 5.0, 9.0, 7.0));
+    plain.clear(&red);
+
+    let mut mirrored = filled(16, 16, &Color::white());
+    mirrored.concat([-1.0, 0.0, 16.0, 0.0, 1.0, 0.0]);
+    mirrored.clip_rect(&Rect::make_xywh(5.0, 5.0, 9.0, 7.0));
+    mirrored.clear(&red);
+
+    for (p, q) in plain.bitmap().pixels.iter().zip(&mirrored.bitmap().pixels) {
+        assert_eq!(argb(p), argb(q));
+    }
+    assert_eq!(plain.bitmap().pixels.iter().filter(|px| px.g == 0).count(), 9*7);
+}
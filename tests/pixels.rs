@@ -0,0 +1,48 @@
+extern crate dinky;
+
+mod common;
+
+use dinky::bitmap::Bitmap;
+use dinky::pixel::{Pixel, PixelOrder};
+
+use common::argb;
+
+#[test]
+fn bytes_follow_pixel_order() {
+    let px = Pixel::pack_argb(200, 10, 20, 30);
+    let expected = match Pixel::ORDER {
+        PixelOrder::Bgra => [30, 20, 10, 200],
+        PixelOrder::Rgba => [10, 20, 30, 200],
+    };
+    assert_eq!(px.to_bytes(), expected);
+    assert_eq!(argb(&Pixel::from_bytes(expected)), argb(&px));
+    assert_eq!(argb(&Pixel::from_u32(px.to_u32())), argb(&px));
+
+    if cfg!(target_endian = "little") && Pixel::ORDER == PixelOrder::Bgra {
+        assert_eq!(px.to_u32(), 0xc80a141e);
+    }
+
+    let mut bitmap = Bitmap::new(3, 2);
+    bitmap.set(1, 1, &px);
+    assert_eq!(bitmap.as_bytes().len(), 24);
+    assert_eq!(&bitmap.as_bytes()[16..20], &expected);
+}
+
+#[test]
+fn byte_views_write_through() {
+    let mut bitmap = Bitmap::new(2, 2);
+    let px = Pixel::pack_argb(255, 1, 2, 3);
+    bitmap.as_bytes_mut()[12..16].copy_from_slice(&px.to_bytes());
+    assert_eq!(argb(&bitmap.get(1, 1)), (255, 1, 2, 3));
+    assert_eq!(argb(&bitmap.get(0, 1)), (0, 0, 0, 0));
+}
+
+#[test]
+fn converts_to_either_order() {
+    let mut bitmap = Bitmap::new(2, 1);
+    bitmap.set(0, 0, &Pixel::pack_argb(255, 1, 2, 3));
+    bitmap.set(1, 0, &Pixel::pack_argb(128, 4, 5, 6));
+
+    assert_eq!(bitmap.to_bytes(PixelOrder::Bgra), vec![3, 2, 1, 255, 6, 5, 4, 128]);
+    assert_eq!(bitmap.to_bytes(PixelOrder::Rgba), vec![1, 2, 3, 255, 4, 5, 6, 128]);
+}
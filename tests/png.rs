@@ -0,0 +1,277 @@
+extern crate dinky;
+
+use dinky::bitmap::Bitmap;
+use dinky::pixel::Pixel;
+use dinky::png::{self, BlendOp, DisposeOp, Frame};
+use dinky::zlib;
+
+use std::io;
+
+fn be32(bytes: &[u8]) -> u32 {
+    (bytes[0] as u32) << 24 | (bytes[1] as u32) << 16 | (bytes[2] as u32) << 8 | bytes[3] as u32
+}
+
+// Splits a PNG into (type, data) chunks, checking every CRC on the way
+fn chunks(bytes: &[u8]) -> Vec<([u8; 4], Vec<u8>)> {
+    assert_eq!(&bytes[..8], &[0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n']);
+
+    let mut pos = 8;
+    let mut chunks = Vec::new();
+    while pos < bytes.len() {
+        let len = be32(&bytes[pos..]) as usize;
+        let body = &bytes[pos + 4..pos + 8 + len];
+        assert_eq!(png::crc32(body), be32(&bytes[pos + 8 + len..]), "bad CRC");
+
+        chunks.push(([body[0], body[1], body[2], body[3]], body[4..].to_vec()));
+        pos += 12 + len;
+    }
+    chunks
+}
+
+struct BitReader<'a> {
+    bytes: &'a [u8],
+    bit:   usize,
+}
+
+impl<'a> BitReader<'a> {
+    fn bits(&mut self, n: usize) -> u32 {
+        let mut v = 0;
+        for i in 0..n {
+            v |= ((self.bytes[self.bit / 8] >> (self.bit % 8)) as u32 & 1) << i;
+            self.bit += 1;
+        }
+        v
+    }
+
+    // Huffman codes come most significant bit first
+    fn code(&mut self, n: usize) -> u32 {
+        let mut v = 0;
+        for _ in 0..n {
+            v = v << 1 | self.bits(1);
+        }
+        v
+    }
+}
+
+// Enough of RFC 1951 to read what zlib::compress writes: fixed-Huffman
+// blocks, checked against the zlib header and Adler-32 trailer
+fn inflate(stream: &[u8]) -> Vec<u8> {
+    const LENGTH_BASE:  [usize; 29] = [3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31,
+                                       35, 43, 51, 59, 67, 83, 99, 115, 131, 163, 195, 227, 258];
+    const LENGTH_EXTRA: [usize; 29] = [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2,
+                                       3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0];
+
+    assert_eq!((stream[0] as u32 * 256 + stream[1] as u32) % 31, 0, "bad zlib header");
+    assert_eq!(stream[0] & 0x0f, 8);
+
+    let mut reader = BitReader { bytes: &stream[2..], bit: 0 };
+    let mut out: Vec<u8> = Vec::new();
+
+    loop {
+        let last = reader.bits(1);
+        assert_eq!(reader.bits(2), 1, "only fixed Huffman blocks are expected");
+
+        loop {
+            // Fixed literal/length codes are 7 to 9 bits long
+            let mut code = reader.code(7);
+            let symbol = if code < 0x18 {
+                code + 256
+            } else {
+                code = code << 1 | reader.code(1);
+                if code < 0xc0 {
+                    code - 0x30
+                } else if code < 0xc8 {
+                    code - 0xc0 + 280
+                } else {
+                    (code << 1 | reader.code(1)) - 0x190 + 144
+                }
+            } as usize;
+
+            if symbol < 256 {
+                out.push(symbol as u8);
+                continue;
+            }
+            if symbol == 256 {
+                break;
+            }
+
+            let i = symbol - 257;
+            let len = LENGTH_BASE[i] + reader.bits(LENGTH_EXTRA[i]) as usize;
+            let d = reader.code(5) as usize;
+            let extra = if d < 4 {0} else {d/2 - 1};
+            let base = if d < 4 {d + 1} else {((2 + d % 2) << extra) + 1};
+            let dist = base + reader.bits(extra) as usize;
+
+            for _ in 0..len {
+                let byte = out[out.len() - dist];
+                out.push(byte);
+            }
+        }
+
+        if last == 1 {
+            break;
+        }
+    }
+
+    let end = 2 + reader.bit.div_ceil(8);
+    assert_eq!(be32(&stream[end..]), zlib::adler32(&out), "bad Adler-32");
+    assert_eq!(end + 4, stream.len());
+    out
+}
+
+// Undoes the per-row filters, returning straight RGBA
+fn unfilter(raw: &[u8], w: usize, h: usize) -> Vec<u8> {
+    let stride = w*4;
+    assert_eq!(raw.len(), (stride + 1)*h);
+
+    let mut out = vec![0u8; stride*h];
+    for y in 0..h {
+        let filter = raw[y*(stride + 1)];
+        for i in 0..stride {
+            let a = if i >= 4 {out[y*stride + i - 4] as i16} else {0};
+            let b = if y > 0 {out[(y - 1)*stride + i] as i16} else {0};
+            let c = if i >= 4 && y > 0 {out[(y - 1)*stride + i - 4] as i16} else {0};
+            let predictor = match filter {
+                0 => 0,
+                1 => a,
+                2 => b,
+                3 => (a + b) / 2,
+                4 => {
+                    let p = a + b - c;
+                    let (pa, pb, pc) = ((p - a).abs(), (p - b).abs(), (p - c).abs());
+                    if pa <= pb && pa <= pc {a} else if pb <= pc {b} else {c}
+                },
+                _ => panic!("bad filter {}", filter),
+            };
+            out[y*stride + i] = raw[y*(stride + 1) + 1 + i].wrapping_add(predictor as u8);
+        }
+    }
+    out
+}
+
+fn assert_pixels(rgba: &[u8], bitmap: &Bitmap) {
+    for (px, bytes) in bitmap.pixels.iter().zip(rgba.chunks(4)) {
+        let (a, r, g, b) = px.unpremul_argb();
+        assert_eq!(bytes, &[r, g, b, a]);
+    }
+}
+
+fn sample(w: usize, h: usize, seed: usize) -> Bitmap {
+    let mut bitmap = Bitmap::new(w, h);
+    for y in 0..h {
+        for x in 0..w {
+            let v = (x*x + y*7 + seed*31) as u8;
+            bitmap.set(x, y, &Pixel::pack_unpremul_argb(255 - (y*10) as u8, v, (x*16) as u8, 200));
+        }
+    }
+    bitmap
+}
+
+#[test]
+fn checksum_known_answers() {
+    assert_eq!(png::crc32(b""), 0);
+    assert_eq!(png::crc32(b"123456789"), 0xcbf43926);
+    assert_eq!(png::crc32(b"IEND"), 0xae426082);
+
+    assert_eq!(zlib::adler32(b""), 1);
+    assert_eq!(zlib::adler32(b"Wikipedia"), 0x11e60398);
+    // Long enough to need the periodic modulo
+    assert_eq!(zlib::adler32(&[0xff; 100000]), 0x149a302c);
+}
+
+#[test]
+fn deflate_round_trip() {
+    let mut text = Vec::new();
+    for i in 0..5000u32 {
+        text.extend_from_slice(format!("{} ", i % 97 * i).as_bytes());
+    }
+
+    // Empty, incompressible, long runs, matches far back in the window
+    let runs = vec![7u8; 70000];
+    let noise: Vec<u8> = (0..4000u32).map(|i| (i.wrapping_mul(2654435761) >> 13) as u8).collect();
+    let mut far = noise.clone();
+    far.extend_from_slice(&vec![0; 30000]);
+    far.extend_from_slice(&noise);
+
+    for data in &[Vec::new(), noise.clone(), runs, text, far] {
+        assert_eq!(&inflate(&zlib::compress(data)), data);
+    }
+}
+
+#[test]
+fn still_image() {
+    let bitmap = sample(13, 9, 0);
+    let chunks = chunks(&png::encode(&bitmap).unwrap());
+
+    let kinds: Vec<&[u8]> = chunks.iter().map(|c| &c.0[..]).collect();
+    assert_eq!(kinds, [&b"IHDR"[..], b"IDAT", b"IEND"]);
+
+    let ihdr = &chunks[0].1;
+    assert_eq!((be32(ihdr), be32(&ihdr[4..])), (13, 9));
+    assert_eq!(&ihdr[8..], &[8, 6, 0, 0, 0]);
+
+    assert_pixels(&unfilter(&inflate(&chunks[1].1), 13, 9), &bitmap);
+}
+
+#[test]
+fn animation() {
+    let mut second = Frame::new(sample(4, 3, 1), 1, 10);
+    second.x = 5;
+    second.y = 2;
+    second.dispose = DisposeOp::Background;
+    second.blend = BlendOp::Over;
+
+    let frames = [Frame::new(sample(10, 6, 0), 1, 10), second, Frame::new(sample(10, 6, 2), 3, 0)];
+    let chunks = chunks(&png::encode_animation(&frames, 2).unwrap());
+
+    let kinds: Vec<&[u8]> = chunks.iter().map(|c| &c.0[..]).collect();
+    assert_eq!(kinds, [&b"IHDR"[..], b"acTL", b"fcTL", b"IDAT", b"fcTL", b"fdAT", b"fcTL", b"fdAT", b"IEND"]);
+
+    let actl = &chunks[1].1;
+    assert_eq!((be32(actl), be32(&actl[4..])), (3, 2));
+
+    // fcTL and fdAT share one sequence, starting from zero
+    let sequence: Vec<u32> = chunks.iter()
+                                   .filter(|c| &c.0 == b"fcTL" || &c.0 == b"fdAT")
+                                   .map(|c| be32(&c.1))
+                                   .collect();
+    assert_eq!(sequence, [0, 1, 2, 3, 4]);
+
+    let fctl = &chunks[4].1;
+    assert_eq!(fctl.len(), 26);
+    assert_eq!((be32(&fctl[4..]), be32(&fctl[8..]), be32(&fctl[12..]), be32(&fctl[16..])), (4, 3, 5, 2));
+    assert_eq!(&fctl[20..], &[0, 1, 0, 10, 1, 1]);
+
+    assert_pixels(&unfilter(&inflate(&chunks[3].1), 10, 6), &frames[0].bitmap);
+    assert_pixels(&unfilter(&inflate(&chunks[5].1[4..]), 4, 3), &frames[1].bitmap);
+    assert_pixels(&unfilter(&inflate(&chunks[7].1[4..]), 10, 6), &frames[2].bitmap);
+}
+
+#[test]
+fn bad_animations_are_errors() {
+    let canvas = || Frame::new(sample(8, 8, 0), 1, 10);
+    let check = |frames: &[Frame]| {
+        let err = png::encode_animation(frames, 0).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    };
+
+    check(&[]);
+
+    let mut offset = canvas();
+    offset.x = 1;
+    check(&[offset]);
+
+    let mut outside = Frame::new(sample(4, 4, 0), 1, 10);
+    outside.y = 5;
+    check(&[canvas(), outside]);
+
+    check(&[canvas(), Frame::new(Bitmap::new(0, 3), 1, 10)]);
+}
+
+#[test]
+fn empty_images_are_errors() {
+    for &(w, h) in &[(0, 0), (0, 4), (4, 0)] {
+        let err = png::encode(&Bitmap::new(w, h)).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    }
+}
@@ -0,0 +1,73 @@
+extern crate dinky;
+
+mod common;
+
+use dinky::bitmap::Bitmap;
+use dinky::pixel::Pixel;
+use dinky::qoi;
+
+use common::argb;
+
+#[test]
+fn round_trip() {
+    // Small steps, bigger steps, repeats and alpha changes hit every op
+    let mut bitmap = Bitmap::new(16, 9);
+    for y in 0..9 {
+        for x in 0..16 {
+            let px = match y % 3 {
+                0 => Pixel::pack_rgb(x as u8, 100, 200 - x as u8),
+                1 => Pixel::pack_rgb((x*29) as u8, (x*13) as u8, 7),
+                _ => Pixel::pack_unpremul_argb((x*16) as u8, 255, (y*20) as u8, 9),
+            };
+            bitmap.set(x, y, &px);
+        }
+    }
+    bitmap.set(3, 4, &bitmap.get(3, 1));
+
+    let decoded = qoi::decode(&qoi::encode(&bitmap)).unwrap();
+
+    assert_eq!((decoded.width, decoded.height), (16, 9));
+    for (p, q) in decoded.pixels.iter().zip(&bitmap.pixels) {
+        assert_eq!(argb(p), argb(q));
+    }
+}
+
+#[test]
+fn runs() {
+    let mut bitmap = Bitmap::new(10, 10);
+    for px in bitmap.pixels.iter_mut() {
+        *px = Pixel::pack_rgb(200, 0, 0);
+    }
+
+    // One RGB op, then runs of 62 and 37 that end with the image
+    let bytes = qoi::encode(&bitmap);
+    assert_eq!(&bytes[14..bytes.len() - 8], &[0xfe, 200, 0, 0, 0xc0 | 61, 0xc0 | 36][..]);
+
+    let decoded = qoi::decode(&bytes).unwrap();
+    assert!(decoded.pixels.iter().all(|px| argb(px) == (255, 200, 0, 0)));
+}
+
+#[test]
+fn bad_input_is_rejected() {
+    let bytes = qoi::encode(&Bitmap::new(4, 4));
+
+    assert!(qoi::decode(&bytes[..10]).is_err());
+
+    let mut wrong = bytes.clone();
+    wrong[0] = b'Q';
+    assert!(qoi::decode(&wrong).is_err());
+
+    // Dimensions far past what the data could hold
+    let mut huge = bytes.clone();
+    huge[4] = 0x7f;
+    assert!(qoi::decode(&huge).is_err());
+
+    // A run that claims pixels past the end marker
+    let mut short = bytes[..14].to_vec();
+    short.push(0xc0 | 3);
+    short.extend_from_slice(&[0, 0, 0, 0, 0, 0, 0, 1]);
+    let mut one_row = short.clone();
+    one_row[11] = 1;
+    assert!(qoi::decode(&short).is_err());
+    assert!(qoi::decode(&one_row).is_ok());
+}
@@ -0,0 +1,98 @@
+extern crate dinky;
+
+use dinky::bitmap::Bitmap;
+use dinky::canvas::Canvas;
+use dinky::color::Color;
+use dinky::context::DrawContext;
+use dinky::point::Point;
+
+// A disk cut into slivers around its center, each one a thin triangle with
+// mostly empty bounding box rows. Every pixel must be drawn by exactly one
+// sliver, so a translucent fill shows no gaps and no double blends.
+#[test]
+fn slivers_tile_exactly() {
+    let color = Color::make_argb(0.5, 0.5, 0.0, 0.0);
+    let center = Point::new(50.3, 49.7);
+    let n = 200;
+
+    let mut canvas = Canvas::new(Bitmap::new(100, 100));
+    for i in 0..n {
+        let angle = |i: usize| i as f32 / n as f32 * 2.0 * std::f32::consts::PI;
+        let rim = |i: usize| Point::new(center.x + 45.0*angle(i).cos(), center.y + 45.0*angle(i).sin());
+        canvas.fill_convex_polygon(&[center, rim(i), rim(i + 1)], &color);
+    }
+
+    let once = color.to_pixel();
+    let mut covered = 0;
+    for y in 0..100 {
+        for x in 0..100 {
+            let px = canvas.bitmap().get(x, y);
+            let inside = (x as f32 - center.x).hypot(y as f32 - center.y) < 44.0;
+            if inside {
+                assert_eq!((px.a, px.r), (once.a, once.r), "({}, {})", x, y);
+            }
+            if px.a != 0 {
+                assert_eq!((px.a, px.r), (once.a, once.r), "({}, {})", x, y);
+                covered += 1;
+            }
+        }
+    }
+
+    // Roughly the area of the disk, and nothing outside it
+    let area = std::f32::consts::PI * 45.0 * 45.0;
+    assert!((covered as f32 - area).abs() < area * 0.01, "{} pixels", covered);
+}
+
+// Huge device coordinates are clipped to a guard band before the fixed-point
+// edge math, so they neither overflow nor lose the part on the canvas
+#[test]
+fn huge_coordinates_are_clipped() {
+    let color = Color::make_argb(1.0, 0.0, 0.5, 1.0);
+    let pixel = color.to_pixel();
+
+    let cover = |far: f32| -> Bitmap {
+        let mut canvas = Canvas::new(Bitmap::new(40, 30));
+        canvas.rotate_about(0.3, &Point::new(20.0, 15.0));
+        canvas.fill_convex_polygon(&[Point::new(-far, -far), Point::new(far, -far),
+                                     Point::new(far, far), Point::new(-far, far)], &color);
+        canvas.bitmap().clone()
+    };
+
+    let corner = |far: f32| -> Bitmap {
+        let mut canvas = Canvas::new(Bitmap::new(40, 30));
+        canvas.scale(2.0, 1.0);
+        canvas.fill_convex_polygon(&[Point::new(10.0, 10.0), Point::new(far, 10.0), Point::new(10.0, far)], &color);
+        canvas.bitmap().clone()
+    };
+
+    for &far in &[1e7, 1e12] {
+        // Covers the canvas, however it is rotated
+        assert!(cover(far).pixels.iter().all(|px| px.a == pixel.a && px.b == pixel.b), "far {}", far);
+
+        // Only its near corner is visible
+        let bitmap = corner(far);
+        for y in 0..30 {
+            for x in 0..40 {
+                assert_eq!(bitmap.get(x, y).a != 0, x >= 20 && y >= 10, "far {} at ({}, {})", far, x, y);
+            }
+        }
+    }
+
+    // Past what even f64 intersects to the pixel, but still no overflow
+    for &far in &[1e30, 1e38, f32::MAX] {
+        cover(far);
+        corner(far);
+    }
+}
+
+#[test]
+fn non_finite_coordinates_draw_nothing() {
+    let mut canvas = Canvas::new(Bitmap::new(20, 20));
+    let color = Color::black();
+
+    for &bad in &[f32::NAN, f32::INFINITY, f32::NEG_INFINITY] {
+        canvas.fill_convex_polygon(&[Point::new(0.0, 0.0), Point::new(bad, 5.0), Point::new(0.0, 10.0)], &color);
+        canvas.fill_rect(&dinky::rect::Rect::make_ltrb(0.0, 0.0, bad, 10.0), &color);
+    }
+    assert!(canvas.bitmap().pixels.iter().all(|px| px.a == 0));
+}
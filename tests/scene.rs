@@ -0,0 +1,79 @@
+extern crate dinky;
+
+use dinky::scene::{Scene, MAX_SIZE};
+
+use std::path::Path;
+
+// The line, column and message of the error the scene fails with
+fn error(text: &str) -> (usize, usize, String) {
+    match Scene::parse(text, Path::new("")) {
+        Ok(_)    => panic!("scene should not parse:\n{}", text),
+        Err(err) => (err.line, err.column, err.message),
+    }
+}
+
+fn at(text: &str) -> (usize, usize) {
+    let (line, column, _) = error(text);
+    (line, column)
+}
+
+#[test]
+fn valid_scene() {
+    let text = "# A comment\n\
+                size 40 30\n\
+                format png\n\
+                clear #ff000080  # Trailing comments too\n\
+                save\n\
+                rotate 45 about 20 15\n\
+                polygon 0.5,0,0,1 0 0 10 0 0 10\n\
+                restore\n\
+                concat 1 0 0 0 1 0 0 0.01 1\n\
+                rect 1 2 3 4 yellow\n";
+    let scene = Scene::parse(text, Path::new("")).unwrap();
+
+    assert_eq!((scene.width, scene.height), (40, 30));
+    assert_eq!(scene.render().pixels.len(), 40*30);
+}
+
+#[test]
+fn errors_point_at_the_token() {
+    assert_eq!(at("size 10 10\nrect 0 0 five 5 red"), (2, 10));
+    assert_eq!(at("size 10 10\n  bogus 1 2"), (2, 3));
+    assert_eq!(at("size 10 10\nrect 0 0 5 5 #12345"), (2, 14));
+    assert_eq!(at("size 10 10\nclear red extra"), (2, 11));
+    assert_eq!(at("size 10 10\nrotate 10 around 5 5"), (2, 11));
+    assert_eq!(at("size 10 10\n\n\nrestore"), (4, 1));
+    assert_eq!(at("size 10 10\npolygon red 0 0 1 1"), (2, 1));
+    assert_eq!(at("size 10 10\nformat tiff"), (2, 8));
+    assert_eq!(at("size 10 10\nbitmap missing.ppm 0 0 1 1"), (2, 8));
+
+    // Columns count characters, not bytes
+    assert_eq!(at("size 10 10\nrect 0 0 é 5 red"), (2, 10));
+}
+
+#[test]
+fn missing_arguments_point_past_the_line() {
+    assert_eq!(error("size 10 10\nrect 0 0 5"), (2, 11, "expected a number".to_string()));
+    assert_eq!(error("size 10 10\ntranslate 5  # no y"), (2, 20, "expected a number".to_string()));
+    assert_eq!(at("size 10 10\npolygon red 0 0 1 1 2"), (2, 22));
+}
+
+#[test]
+fn size_comes_first_and_once() {
+    assert_eq!(at("clear red\nsize 10 10"), (1, 1));
+    assert_eq!(at("size 10 10\nsize 10 10"), (2, 1));
+    assert_eq!(at("\n# Nothing but comments\n"), (2, 1));
+    assert_eq!(at(""), (1, 1));
+}
+
+#[test]
+fn size_is_bounded() {
+    let largest = format!("size {} 1", MAX_SIZE);
+    assert!(Scene::parse(&largest, Path::new("")).is_ok());
+
+    for text in &["size 0 10", "size 10 -1", "size 10 1.5", "size 10 99999999999999999999999"] {
+        assert_eq!(at(text).0, 1);
+    }
+    assert_eq!(at(&format!("size 10 {}", MAX_SIZE + 1)), (1, 9));
+    assert_eq!(at(&format!("size {} 10", MAX_SIZE + 1)), (1, 6));
+}
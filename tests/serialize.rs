@@ -0,0 +1,136 @@
+extern crate dinky;
+
+use dinky::bitmap::Bitmap;
+use dinky::color::Color;
+use dinky::matrix::Matrix;
+use dinky::picture::{DrawOp, Picture};
+use dinky::pixel::Pixel;
+use dinky::point::Point;
+use dinky::rect::Rect;
+use dinky::serialize;
+use dinky::shader::ShaderDesc;
+
+use std::io;
+
+// Every kind of op, with enough nesting to matter
+fn sample() -> Picture {
+    let mut bitmap = Bitmap::new(3, 2);
+    bitmap.set(1, 1, &Pixel::pack_argb(128, 100, 50, 0));
+    let triangle = vec![Point::new(1.0, 2.0), Point::new(30.5, 4.0), Point::new(8.0, 20.25)];
+
+    Picture::new(vec![
+        DrawOp::Clear(Color::white()),
+        DrawOp::Save,
+        DrawOp::Concat(Matrix::new([2.0, 0.5, 10.0, -0.25, 1.5, 3.0])),
+        DrawOp::ClipRect(Rect::make_xywh(0.0, 0.0, 40.0, 30.0)),
+        DrawOp::FillRect(Rect::make_xywh(1.5, 2.0, 10.0, 12.0), Color::make_argb(0.5, 1.0, 0.0, 0.25)),
+        DrawOp::FillConvexPolygon(triangle.clone(), Color::black()),
+        DrawOp::Restore,
+        DrawOp::Concat(Matrix::from_3x3([1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.001, 0.002, 1.0])),
+        DrawOp::FillBitmapRect(bitmap.clone(), Rect::make_wh(6.0, 4.0)),
+        DrawOp::ShadeRect(Rect::make_wh(5.0, 5.0), ShaderDesc::Color(Color::green())),
+        DrawOp::ShadeConvexPolygon(triangle, ShaderDesc::Bitmap(bitmap, Matrix::new([1.0, 0.0, 2.0, 0.0, 1.0, 3.0]))),
+    ], Rect::make_wh(64.0, 48.0))
+}
+
+fn check_invalid(bytes: &[u8]) {
+    let err = serialize::decode(bytes).err().expect("picture should be rejected");
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+}
+
+// The op count is a single byte varint for these pictures
+const OPS_START: usize = 4 + 2 + 16 + 1;
+
+#[test]
+fn round_trip() {
+    let picture = sample();
+    let bytes = serialize::encode(&picture);
+    let decoded = serialize::decode(&bytes).unwrap();
+
+    assert_eq!(serialize::encode(&decoded), bytes);
+    assert_eq!(serialize::dump(&decoded), serialize::dump(&picture));
+}
+
+#[test]
+fn truncated_input_is_rejected() {
+    let bytes = serialize::encode(&sample());
+    for len in 0..bytes.len() {
+        check_invalid(&bytes[..len]);
+    }
+
+    let mut trailing = bytes.clone();
+    trailing.push(0);
+    check_invalid(&trailing);
+}
+
+#[test]
+fn bad_headers_and_tags_are_rejected() {
+    let bytes = serialize::encode(&sample());
+
+    let mut magic = bytes.clone();
+    magic[0] = b'X';
+    check_invalid(&magic);
+
+    for &version in &[0u16, 2, 0xffff] {
+        let mut versioned = bytes.clone();
+        versioned[4..6].copy_from_slice(&version.to_le_bytes());
+        check_invalid(&versioned);
+    }
+
+    let mut tag = bytes.clone();
+    tag[OPS_START] = 99;
+    check_invalid(&tag);
+}
+
+#[test]
+fn unbalanced_restore_is_rejected() {
+    let picture = Picture::new(vec![DrawOp::Save, DrawOp::Restore, DrawOp::Restore], Rect::make_wh(1.0, 1.0));
+    check_invalid(&serialize::encode(&picture));
+}
+
+#[test]
+fn unclosed_save_is_rejected() {
+    let picture = Picture::new(vec![DrawOp::Save, DrawOp::Save, DrawOp::Restore], Rect::make_wh(1.0, 1.0));
+    check_invalid(&serialize::encode(&picture));
+}
+
+#[test]
+fn empty_bitmaps_are_rejected() {
+    let identity = Matrix::identity();
+    for &(w, h) in &[(0, 0), (0, 3), (3, 0)] {
+        let shade = DrawOp::ShadeRect(Rect::make_wh(4.0, 4.0), ShaderDesc::Bitmap(Bitmap::new(w, h), identity));
+        check_invalid(&serialize::encode(&Picture::new(vec![shade], Rect::make_wh(4.0, 4.0))));
+
+        let fill = DrawOp::FillBitmapRect(Bitmap::new(w, h), Rect::make_wh(4.0, 4.0));
+        check_invalid(&serialize::encode(&Picture::new(vec![fill], Rect::make_wh(4.0, 4.0))));
+    }
+}
+
+// Only decode checks the nesting; dump copes with whatever it is given
+#[test]
+fn dump_survives_extra_restores() {
+    let picture = Picture::new(vec![DrawOp::Restore, DrawOp::Save, DrawOp::Restore, DrawOp::Restore],
+                               Rect::make_wh(1.0, 1.0));
+    let dump = serialize::dump(&picture);
+    assert_eq!(dump.lines().skip(1).collect::<Vec<_>>(),
+               ["   0: restore", "   1: save", "   2: restore", "   3: restore"]);
+}
+
+#[test]
+fn out_of_range_values_are_rejected() {
+    let bad = [f32::NAN, f32::INFINITY, f32::NEG_INFINITY, 1.0e20, -3.0e9];
+    let encode = |op: DrawOp, cull: Rect| serialize::encode(&Picture::new(vec![op], cull));
+    let unit = Rect::make_wh(1.0, 1.0);
+
+    for &v in &bad {
+        check_invalid(&encode(DrawOp::Clear(Color::black()), Rect::make_ltrb(0.0, 0.0, v, 1.0)));
+        check_invalid(&encode(DrawOp::ClipRect(Rect::make_ltrb(v, 0.0, 1.0, 1.0)), unit));
+        check_invalid(&encode(DrawOp::FillConvexPolygon(vec![Point::new(0.0, v)], Color::black()), unit));
+        check_invalid(&encode(DrawOp::Concat(Matrix::new([1.0, 0.0, v, 0.0, 1.0, 0.0])), unit));
+        check_invalid(&encode(DrawOp::Concat(Matrix::from_3x3([1.0, 0.0, 0.0, 0.0, 1.0, 0.0, v, 0.0, 1.0])), unit));
+    }
+
+    // Large but sane values still load
+    let far = encode(DrawOp::FillRect(Rect::make_ltrb(-1.0e8, 0.0, 1.0e8, 1.0), Color::black()), unit);
+    assert!(serialize::decode(&far).is_ok());
+}
@@ -0,0 +1,122 @@
+extern crate dinky;
+
+use dinky::bitmap::Bitmap;
+use dinky::canvas::Canvas;
+use dinky::context::DrawContext;
+use dinky::matrix::Matrix;
+use dinky::pixel::Pixel;
+use dinky::point::Point;
+use dinky::rect::Rect;
+use dinky::shader::Shaders;
+use dinky::simd::{self, Level};
+use dinky::util::{blend, clamp};
+
+// Small xorshift, so failures reproduce
+struct Rng(u32);
+
+impl Rng {
+    fn next(&mut self) -> u32 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 17;
+        self.0 ^= self.0 << 5;
+        self.0
+    }
+
+    fn pixel(&mut self) -> Pixel {
+        let v = self.next();
+        let a = match v % 4 {
+            0 => 0,
+            1 => 255,
+            _ => (v >> 8) as u8,
+        };
+        let channel = |n: u32| (n % (a as u32 + 1)) as u8;
+        Pixel::pack_argb(a, channel(v >> 16), channel(self.next()), channel(self.next() >> 8))
+    }
+
+    fn float(&mut self, range: f32) -> f32 {
+        (self.next() % 20001) as f32 / 10000.0 * range - range
+    }
+}
+
+fn levels() -> Vec<Level> {
+    [Level::Sse2, Level::Avx2].iter().cloned().filter(|&level| level <= simd::level()).collect()
+}
+
+fn same(a: &[Pixel], b: &[Pixel]) -> bool {
+    a.iter().zip(b).all(|(p, q)| (p.a, p.r, p.g, p.b) == (q.a, q.r, q.g, q.b))
+}
+
+#[test]
+fn blending_matches_scalar() {
+    let mut rng = Rng(0x9e3779b9);
+
+    for level in levels() {
+        for len in 0..40 {
+            let src: Vec<Pixel> = (0..len).map(|_| rng.pixel()).collect();
+            let dst: Vec<Pixel> = (0..len).map(|_| rng.pixel()).collect();
+            let expected: Vec<Pixel> = src.iter().zip(&dst).map(|(s, d)| blend(s, d)).collect();
+
+            let mut row = dst.clone();
+            let done = simd::blend_row(level, &src, &mut row);
+            assert!(done + 4 > len, "{:?} only did {} of {}", level, done, len);
+            assert!(same(&row[..done], &expected[..done]), "{:?} blend_row", level);
+
+            let color = rng.pixel();
+            let mut row = dst.clone();
+            let done = simd::blend_color(level, color, &mut row);
+            let expected: Vec<Pixel> = dst.iter().map(|d| blend(&color, d)).collect();
+            assert!(same(&row[..done], &expected[..done]), "{:?} blend_color", level);
+        }
+    }
+}
+
+#[test]
+fn sampling_matches_scalar() {
+    let mut rng = Rng(0x2545f491);
+
+    let mut src = Bitmap::new(13, 7);
+    for px in src.pixels.iter_mut() {
+        *px = rng.pixel();
+    }
+
+    for level in levels() {
+        for _ in 0..500 {
+            // Includes scales big enough to send lookups far off the edges
+            let range = if rng.next() % 8 == 0 { 1e6 } else { 4.0 };
+            let xform = Matrix::new([rng.float(range), rng.float(range), rng.float(20.0),
+                                     rng.float(range), rng.float(range), rng.float(20.0)]);
+            let (x, y) = ((rng.next() % 50) as usize, (rng.next() % 50) as usize);
+
+            let mut row = vec![Pixel::pack_argb(0, 0, 0, 0); 37];
+            let done = simd::sample_row(level, &xform, &src, x, y, &mut row);
+            assert!(done >= 32, "{:?} only did {}", level, done);
+
+            for (n, px) in row[..done].iter().enumerate() {
+                let lookup = xform.apply(&Point::new((x + n) as f32 + 0.5, y as f32 + 0.5));
+                let expected = src.get(clamp(0.0, lookup.x, 12.0) as usize, clamp(0.0, lookup.y, 6.0) as usize);
+                assert!(same(&[*px], &[expected]), "{:?} at {} of {:?}", level, n, xform);
+            }
+        }
+    }
+}
+
+// Nothing to gather from, at any level; the draw is skipped before that
+#[test]
+fn empty_bitmaps_sample_nothing() {
+    let marker = Pixel::pack_argb(255, 1, 2, 3);
+
+    for &(w, h) in &[(0, 0), (0, 5), (5, 0)] {
+        let src = Bitmap::new(w, h);
+        for &level in &[Level::Scalar, Level::Sse2, Level::Avx2] {
+            let mut row = vec![marker; 37];
+            assert_eq!(simd::sample_row(level, &Matrix::identity(), &src, 0, 0, &mut row), 0, "{:?}", level);
+            assert!(same(&row, &[marker; 37]), "{:?}", level);
+        }
+
+        let mut canvas = Canvas::new(Bitmap::new(16, 16));
+        canvas.shade_rect(&Rect::make_wh(16.0, 16.0), &mut Shaders::from_bitmap(&src, [1.0, 0.0, 0.0, 0.0, 1.0, 0.0]));
+        canvas.shade_convex_polygon(&[Point::new(0.0, 0.0), Point::new(9.0, 0.5), Point::new(3.0, 14.0)],
+                                    &mut Shaders::from_bitmap(&src, [2.0, 0.0, 1.0, 0.0, 2.0, 1.0]));
+        assert!(canvas.bitmap().pixels.iter().all(|px| px.a == 0));
+    }
+}
@@ -0,0 +1,119 @@
+extern crate dinky;
+
+use dinky::color::Color;
+use dinky::context::DrawContext;
+use dinky::matrix::Matrix;
+use dinky::point::Point;
+use dinky::rect::Rect;
+use dinky::svg::SvgCanvas;
+
+// The document between the opening <svg> and the closing </svg>
+fn body(canvas: &SvgCanvas) -> Vec<String> {
+    let doc = canvas.document();
+    let lines: Vec<String> = doc.lines().map(|l| l.to_string()).collect();
+    assert!(lines[1].starts_with("<svg "));
+    assert_eq!(lines[lines.len() - 1], "</svg>");
+    lines[2..lines.len() - 1].to_vec()
+}
+
+#[test]
+fn untransformed_shapes() {
+    let mut canvas = SvgCanvas::new(100, 50);
+    canvas.fill_rect(&Rect::make_xywh(10.0, 5.0, 20.0, 30.0), &Color::make_argb(1.0, 1.0, 0.0, 0.0));
+    canvas.fill_convex_polygon(&[Point::new(0.0, 0.0), Point::new(8.0, 0.0), Point::new(4.0, 6.0)],
+                               &Color::make_argb(0.5, 0.0, 0.0, 1.0));
+
+    assert_eq!(body(&canvas), [
+        "<rect x=\"10\" y=\"5\" width=\"20\" height=\"30\" fill=\"rgb(255,0,0)\"/>",
+        "<polygon points=\"0,0 8,0 4,6\" fill=\"rgb(0,0,255)\" fill-opacity=\"0.5\"/>",
+    ]);
+}
+
+#[test]
+fn transforms_are_column_major() {
+    let mut canvas = SvgCanvas::new(100, 100);
+    canvas.translate(10.0, 20.0);
+    canvas.scale(2.0, 3.0);
+    canvas.fill_rect(&Rect::make_wh(5.0, 5.0), &Color::black());
+
+    canvas.concat([1.0, 0.5, 0.0, 0.25, 1.0, 0.0]);
+    canvas.fill_convex_polygon(&[Point::new(0.0, 0.0), Point::new(1.0, 0.0), Point::new(0.0, 1.0)],
+                               &Color::black());
+
+    assert_eq!(body(&canvas), [
+        "<rect x=\"0\" y=\"0\" width=\"5\" height=\"5\" fill=\"rgb(0,0,0)\" transform=\"matrix(2 0 0 3 10 20)\"/>",
+        "<polygon points=\"0,0 1,0 0,1\" fill=\"rgb(0,0,0)\" transform=\"matrix(2 0.75 1 3 10 20)\"/>",
+    ]);
+}
+
+#[test]
+fn clips_open_and_close_groups() {
+    let mut canvas = SvgCanvas::new(100, 100);
+    let black = Color::black();
+
+    canvas.save();
+    canvas.translate(5.0, 0.0);
+    canvas.clip_rect(&Rect::make_xywh(0.0, 0.0, 40.0, 40.0));
+    canvas.save();
+    canvas.clip_rect(&Rect::make_xywh(10.0, 10.0, 60.0, 60.0));
+    canvas.fill_rect(&Rect::make_wh(1.0, 1.0), &black);
+    canvas.restore();
+    canvas.fill_rect(&Rect::make_wh(2.0, 2.0), &black);
+    canvas.restore();
+    canvas.fill_rect(&Rect::make_wh(3.0, 3.0), &black);
+
+    // Clip shapes live in the user space current when they were set
+    assert_eq!(body(&canvas), [
+        "<defs>",
+        "<clipPath id=\"clip0\"><rect x=\"0\" y=\"0\" width=\"40\" height=\"40\" transform=\"matrix(1 0 0 1 5 0)\"/></clipPath>",
+        "<clipPath id=\"clip1\"><rect x=\"10\" y=\"10\" width=\"60\" height=\"60\" transform=\"matrix(1 0 0 1 5 0)\"/></clipPath>",
+        "</defs>",
+        "<g clip-path=\"url(#clip0)\">",
+        "<g clip-path=\"url(#clip1)\">",
+        "<rect x=\"0\" y=\"0\" width=\"1\" height=\"1\" fill=\"rgb(0,0,0)\" transform=\"matrix(1 0 0 1 5 0)\"/>",
+        "</g>",
+        "<rect x=\"0\" y=\"0\" width=\"2\" height=\"2\" fill=\"rgb(0,0,0)\" transform=\"matrix(1 0 0 1 5 0)\"/>",
+        "</g>",
+        "<rect x=\"0\" y=\"0\" width=\"3\" height=\"3\" fill=\"rgb(0,0,0)\"/>",
+    ]);
+}
+
+#[test]
+fn clear_respects_clips() {
+    let mut canvas = SvgCanvas::new(20, 20);
+    canvas.fill_rect(&Rect::make_wh(4.0, 4.0), &Color::black());
+    canvas.clear(&Color::white());
+    canvas.fill_rect(&Rect::make_wh(6.0, 6.0), &Color::black());
+
+    // Open groups are closed by document()
+    canvas.clip_rect(&Rect::make_wh(10.0, 10.0));
+    canvas.clear(&Color::white());
+    canvas.fill_rect(&Rect::make_wh(5.0, 5.0), &Color::black());
+
+    assert_eq!(body(&canvas), [
+        "<defs>",
+        "<clipPath id=\"clip0\"><rect x=\"0\" y=\"0\" width=\"10\" height=\"10\"/></clipPath>",
+        "</defs>",
+        "<rect width=\"20\" height=\"20\" fill=\"rgb(255,255,255)\"/>",
+        "<rect x=\"0\" y=\"0\" width=\"6\" height=\"6\" fill=\"rgb(0,0,0)\"/>",
+        "<g clip-path=\"url(#clip0)\">",
+        "<rect width=\"20\" height=\"20\" fill=\"rgb(255,255,255)\"/>",
+        "<rect x=\"0\" y=\"0\" width=\"5\" height=\"5\" fill=\"rgb(0,0,0)\"/>",
+        "</g>",
+    ]);
+}
+
+#[test]
+fn perspective_is_mapped_to_device_space() {
+    let mut canvas = SvgCanvas::new(100, 100);
+    canvas.concat_matrix(&Matrix::from_3x3([1.0, 0.0, 0.0,
+                                            0.0, 1.0, 0.0,
+                                            0.0, 0.01, 1.0]));
+    canvas.clip_rect(&Rect::make_wh(50.0, 100.0));
+    canvas.fill_rect(&Rect::make_wh(100.0, 100.0), &Color::black());
+
+    let lines = body(&canvas);
+    assert_eq!(lines[1], "<clipPath id=\"clip0\"><polygon points=\"0,0 50,0 25,50 0,50\"/></clipPath>");
+    assert_eq!(lines[4], "<polygon points=\"0,0 100,0 50,50 0,50\" fill=\"rgb(0,0,0)\"/>");
+    assert!(lines.iter().all(|l| !l.contains("transform")));
+}
@@ -0,0 +1,105 @@
+extern crate dinky;
+
+mod common;
+
+use dinky::bitmap::Bitmap;
+use dinky::pixel::Pixel;
+use dinky::tga;
+
+use std::io;
+
+use common::argb;
+
+fn sample() -> Bitmap {
+    let mut bitmap = Bitmap::new(7, 4);
+    for y in 0..4 {
+        for x in 0..7 {
+            // Runs along the left half, noise along the right
+            let v = if x < 4 {y as u8 * 60} else {(x*37 + y*91) as u8};
+            bitmap.set(x, y, &Pixel::pack_rgb(v, 255 - v, v / 3));
+        }
+    }
+    bitmap.set(6, 3, &Pixel::pack_unpremul_argb(100, 200, 40, 0));
+    bitmap
+}
+
+#[test]
+fn round_trip() {
+    let bitmap = sample();
+
+    for &rle in &[false, true] {
+        let decoded = tga::decode(&tga::encode(&bitmap, rle).unwrap()).unwrap();
+
+        assert_eq!((decoded.width, decoded.height), (7, 4));
+        for (p, q) in decoded.pixels.iter().zip(&bitmap.pixels) {
+            assert_eq!(argb(p), argb(q), "rle {}", rle);
+        }
+    }
+}
+
+#[test]
+fn rle_packets() {
+    let red   = Pixel::pack_rgb(255, 0, 0);
+    let green = Pixel::pack_rgb(0, 255, 0);
+    let blue  = Pixel::pack_rgb(0, 0, 255);
+
+    // Five reds, then blue green red, which can only go raw
+    let mut bitmap = Bitmap::new(8, 1);
+    for x in 0..8 {
+        bitmap.set(x, 0, match x {5 => &blue, 6 => &green, _ => &red});
+    }
+
+    let bytes = tga::encode(&bitmap, true).unwrap();
+    let data = &bytes[18..bytes.len() - 26];
+    assert_eq!(data, &[0x84, 0, 0, 255, 255,
+                       0x02, 255, 0, 0, 255, 0, 255, 0, 255, 0, 0, 255, 255][..]);
+
+    let decoded = tga::decode(&bytes).unwrap();
+    assert_eq!(argb(&decoded.get(4, 0)), argb(&red));
+    assert_eq!(argb(&decoded.get(5, 0)), argb(&blue));
+    assert_eq!(argb(&decoded.get(6, 0)), argb(&green));
+}
+
+#[test]
+fn long_runs_split_into_packets() {
+    let mut bitmap = Bitmap::new(300, 1);
+    for px in bitmap.pixels.iter_mut() {
+        *px = Pixel::pack_rgb(1, 2, 3);
+    }
+
+    // 128 + 128 + 44
+    let bytes = tga::encode(&bitmap, true).unwrap();
+    assert_eq!(bytes.len(), 18 + 3*5 + 26);
+    assert_eq!((bytes[18], bytes[23], bytes[28]), (0xff, 0xff, 0x80 | 43));
+
+    let decoded = tga::decode(&bytes).unwrap();
+    assert!(decoded.pixels.iter().all(|px| argb(px) == (255, 1, 2, 3)));
+}
+
+#[test]
+fn bad_input_is_rejected() {
+    let err = tga::encode(&Bitmap::new(0x10000, 1), false).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+
+    let bytes = tga::encode(&sample(), true).unwrap();
+    assert!(tga::decode(&bytes[..17]).is_err());
+    assert!(tga::decode(&bytes[..40]).is_err());
+
+    // A run longer than what is left of the image
+    let mut bytes = tga::encode(&Bitmap::new(2, 1), true).unwrap();
+    bytes[18] = 0x85;
+    assert!(tga::decode(&bytes).is_err());
+}
+
+// Headers are checked against the data before the image is allocated
+#[test]
+fn huge_headers_fail_without_allocating() {
+    for &rle in &[false, true] {
+        let mut bytes = tga::encode(&Bitmap::new(1, 1), rle).unwrap();
+        bytes[12..16].copy_from_slice(&[0xff, 0xff, 0xff, 0xff]);
+
+        // Just the header, then also the one packet or pixel of the original
+        assert!(tga::decode(&bytes[..18]).is_err(), "rle {}", rle);
+        assert!(tga::decode(&bytes[..18 + 4 + rle as usize]).is_err(), "rle {}", rle);
+    }
+}
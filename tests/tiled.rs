@@ -0,0 +1,67 @@
+extern crate dinky;
+
+use dinky::bitmap::Bitmap;
+use dinky::canvas::Canvas;
+use dinky::gallery;
+use dinky::picture::{Picture, PictureRecorder};
+use dinky::rect::Rect;
+use dinky::tiled;
+
+fn record(image: &gallery::Image) -> Picture {
+    let mut recorder = PictureRecorder::new(Rect::make_wh(image.width as f32, image.height as f32));
+    (image.draw)(&mut recorder);
+    recorder.finish()
+}
+
+// Every gallery image, split into tiles over various thread counts, must
+// match drawing it on a single canvas pixel for pixel
+#[test]
+fn tiles_match_single_canvas() {
+    for image in gallery::IMAGES.iter() {
+        let expected = image.render();
+        let picture = record(image);
+
+        for &threads in &[1, 3, 8] {
+            let mut bitmap = Bitmap::new(image.width, image.height);
+            tiled::playback(&picture, &mut bitmap, [1.0, 0.0, 0.0, 0.0, 1.0, 0.0], threads);
+            assert_eq!(expected.diff(&bitmap).differing, 0, "{} on {} threads", image.name, threads);
+        }
+    }
+}
+
+// Heights that don't divide into whole tiles, under a transform that puts
+// edges at fractional positions across tile boundaries
+#[test]
+fn partial_tiles_under_transform() {
+    let mat = [0.7, 0.2, 3.5, -0.15, 0.9, 17.25];
+
+    for image in gallery::IMAGES.iter() {
+        let picture = record(image);
+        let height = tiled::TILE_ROWS * 2 + 7;
+
+        let mut canvas = Canvas::new(Bitmap::new(image.width, height));
+        picture.playback(&mut canvas, mat);
+
+        let mut bitmap = Bitmap::new(image.width, height);
+        tiled::playback(&picture, &mut bitmap, mat, 4);
+        assert_eq!(canvas.bitmap().diff(&bitmap).differing, 0, "{}", image.name);
+    }
+}
+
+// A canvas placed partway into the device draws exactly that part of it
+#[test]
+fn origin_offsets_device() {
+    let image = gallery::find("circles_blend").unwrap();
+    let full = image.render();
+    let (left, top, width, height) = (37, 101, 150, 90);
+
+    let mut canvas = Canvas::with_origin(Bitmap::new(width, height), left, top);
+    (image.draw)(&mut canvas);
+
+    for y in 0..height {
+        for x in 0..width {
+            let (p, q) = (canvas.bitmap().get(x, y), full.get(left + x, top + y));
+            assert_eq!((p.a, p.r, p.g, p.b), (q.a, q.r, q.g, q.b), "({}, {})", x, y);
+        }
+    }
+}
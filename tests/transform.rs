@@ -0,0 +1,151 @@
+extern crate dinky;
+
+mod common;
+
+use dinky::bitmap::Bitmap;
+use dinky::gallery;
+use dinky::pixel::Pixel;
+use dinky::resize::Filter;
+
+use common::numbered;
+
+const FILTERS: [Filter; 4] = [Filter::Box, Filter::Triangle, Filter::Lanczos3, Filter::Mitchell];
+
+fn xy(bitmap: &Bitmap, x: usize, y: usize) -> (u8, u8) {
+    let px = bitmap.get(x, y);
+    (px.r, px.g)
+}
+
+fn same(a: &Bitmap, b: &Bitmap) -> bool {
+    a.width == b.width && a.height == b.height && a.diff(b).differing == 0
+}
+
+#[test]
+fn flips_and_turns_move_corners() {
+    let src = numbered(5, 3);
+
+    let mut b = src.clone();
+    b.flip_horizontal();
+    assert_eq!(xy(&b, 0, 0), (4, 0));
+    assert_eq!(xy(&b, 4, 2), (0, 2));
+
+    let mut b = src.clone();
+    b.flip_vertical();
+    assert_eq!(xy(&b, 0, 0), (0, 2));
+    assert_eq!(xy(&b, 3, 1), (3, 1));
+
+    // Clockwise: the top-left corner ends up top-right
+    let mut b = src.clone();
+    b.rotate_90();
+    assert_eq!((b.width, b.height), (3, 5));
+    assert_eq!(xy(&b, 2, 0), (0, 0));
+    assert_eq!(xy(&b, 0, 0), (0, 2));
+    assert_eq!(xy(&b, 0, 4), (4, 2));
+
+    let mut b = src.clone();
+    b.rotate_270();
+    assert_eq!((b.width, b.height), (3, 5));
+    assert_eq!(xy(&b, 0, 4), (0, 0));
+    assert_eq!(xy(&b, 0, 0), (4, 0));
+
+    let mut b = src.clone();
+    b.transpose();
+    assert_eq!((b.width, b.height), (3, 5));
+    assert_eq!(xy(&b, 2, 4), (4, 2));
+}
+
+// Sizes past the 32 pixel blocks, and not multiples of them
+#[test]
+fn turns_compose() {
+    let src = gallery::find("circles_fat").unwrap().render();
+
+    let mut four = src.clone();
+    for _ in 0..4 {
+        four.rotate_90();
+    }
+    assert!(same(&four, &src));
+
+    let (mut twice, mut half) = (src.clone(), src.clone());
+    twice.rotate_90();
+    twice.rotate_90();
+    half.rotate_180();
+    assert!(same(&twice, &half));
+
+    let (mut flips, mut both) = (src.clone(), src.clone());
+    flips.flip_horizontal();
+    flips.flip_vertical();
+    both.rotate_180();
+    assert!(same(&flips, &both));
+
+    let (mut back, mut turned) = (src.clone(), src.clone());
+    back.rotate_90();
+    back.rotate_270();
+    assert!(same(&back, &src));
+
+    // Transposing is a clockwise turn then a horizontal flip
+    let mut transposed = src.clone();
+    transposed.transpose();
+    turned.rotate_90();
+    turned.flip_horizontal();
+    assert!(same(&transposed, &turned));
+}
+
+#[test]
+fn resize_keeps_flat_colors() {
+    let mut src = Bitmap::new(37, 23);
+    for px in src.pixels.iter_mut() {
+        *px = Pixel::pack_argb(128, 100, 50, 10);
+    }
+
+    for &filter in FILTERS.iter() {
+        for &(w, h) in &[(10, 7), (37, 23), (80, 61), (1, 1)] {
+            let out = src.resize(w, h, filter);
+            assert_eq!((out.width, out.height), (w, h));
+            for px in &out.pixels {
+                assert_eq!((px.a, px.r, px.g, px.b), (128, 100, 50, 10), "{:?} to {}x{}", filter, w, h);
+            }
+        }
+    }
+}
+
+// Box, triangle and Lanczos all pass pixel centers through untouched
+#[test]
+fn resize_to_same_size_is_exact() {
+    let src = gallery::find("spocks_quad").unwrap().render();
+    for &filter in &[Filter::Box, Filter::Triangle, Filter::Lanczos3] {
+        assert!(same(&src.resize(src.width, src.height, filter), &src), "{:?}", filter);
+    }
+}
+
+#[test]
+fn box_halving_averages_blocks() {
+    let mut src = Bitmap::new(4, 2);
+    let grey = |v: u8| Pixel::pack_rgb(v, v, v);
+    for (x, &v) in [0, 100, 200, 50].iter().enumerate() {
+        src.set(x, 0, &grey(v));
+        src.set(x, 1, &grey(v / 2));
+    }
+
+    let out = src.resize(2, 1, Filter::Box);
+    assert_eq!(out.get(0, 0).r, 38);   // (0 + 100 + 0 + 50) / 4 = 37.5
+    assert_eq!(out.get(1, 0).r, 94);   // (200 + 50 + 100 + 25) / 4 = 93.75
+}
+
+// Ringing at a hard transparent edge must not leave color above alpha,
+// which pack_argb would catch
+#[test]
+fn resize_stays_premultiplied() {
+    let mut src = Bitmap::new(16, 16);
+    for y in 0..16 {
+        for x in 0..8 {
+            src.set(x, y, &Pixel::pack_rgb(255, 255, 255));
+        }
+    }
+
+    for &filter in FILTERS.iter() {
+        for &(w, h) in &[(7, 7), (29, 13), (64, 64)] {
+            let out = src.resize(w, h, filter);
+            assert!(out.pixels.iter().all(|px| px.r <= px.a && px.g <= px.a && px.b <= px.a));
+        }
+    }
+}
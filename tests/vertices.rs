@@ -0,0 +1,179 @@
+extern crate dinky;
+
+use dinky::bitmap::Bitmap;
+use dinky::canvas::Canvas;
+use dinky::color::Color;
+use dinky::context::DrawContext;
+use dinky::point::Point;
+use dinky::rect::Rect;
+
+fn quad() -> [Point; 4] {
+    [
+        Point::new(10.0, 10.0),
+        Point::new(90.0, 10.0),
+        Point::new(90.0, 70.0),
+        Point::new(10.0, 70.0),
+    ]
+}
+
+#[test]
+fn uniform_colors_match_fill() {
+    let color = Color::make_argb(0.5, 0.2, 0.6, 1.0);
+
+    let mut filled = Canvas::new(Bitmap::new(100, 80));
+    filled.fill_rect(&Rect::make_ltrb(10.0, 10.0, 90.0, 70.0), &color);
+
+    // Both windings, sharing an edge, must not leave gaps or double-blend
+    let mut mesh = Canvas::new(Bitmap::new(100, 80));
+    mesh.draw_vertices(&quad(), &[color; 4], &[0, 1, 2, 0, 3, 2]);
+
+    let stats = filled.bitmap().diff(mesh.bitmap());
+    assert_eq!(stats.differing, 0);
+}
+
+#[test]
+fn colors_are_interpolated() {
+    let colors = [Color::red(), Color::green(), Color::blue(), Color::white()];
+
+    let mut canvas = Canvas::new(Bitmap::new(100, 80));
+    canvas.draw_vertices(&quad(), &colors, &[0, 1, 2, 0, 2, 3]);
+    let bitmap = canvas.bitmap();
+
+    // Near each corner the color is that vertex's
+    let near = |x: usize, y: usize, r: i32, g: i32, b: i32| {
+        let px = bitmap.get(x, y);
+        assert_eq!(px.a, 255);
+        for &(got, want) in &[(px.r, r), (px.g, g), (px.b, b)] {
+            assert!((got as i32 - want).abs() <= 8, "({}, {}) = {:?}", x, y, (px.r, px.g, px.b));
+        }
+    };
+    near(10, 10, 255,   0,   0);
+    near(89, 10,   0, 255,   0);
+    near(89, 69,   0,   0, 255);
+    near(10, 69, 255, 255, 255);
+
+    // Halfway along the top edge is half red, half green
+    let mid = bitmap.get(50, 10);
+    assert!((mid.r as i32 - 128).abs() <= 4 && (mid.g as i32 - 128).abs() <= 4);
+
+    // And nothing outside the mesh
+    assert_eq!(bitmap.get(5, 5).a, 0);
+    assert_eq!(bitmap.get(95, 75).a, 0);
+}
+
+#[test]
+fn respects_ctm_and_clip() {
+    let red = [Color::red(); 4];
+
+    let mut canvas = Canvas::new(Bitmap::new(100, 80));
+    canvas.translate(5.0, 0.0);
+    canvas.clip_rect(&Rect::make_ltrb(0.0, 0.0, 50.0, 80.0));
+    canvas.draw_vertices(&quad(), &red, &[0, 1, 2, 0, 2, 3]);
+
+    let bounds = canvas.bitmap().diff(&Bitmap::new(100, 80)).bounds.unwrap();
+    assert_eq!((bounds.left(), bounds.top(), bounds.right(), bounds.bottom()), (15.0, 10.0, 55.0, 70.0));
+}
+
+fn checker(w: usize, h: usize) -> Bitmap {
+    let mut bitmap = Bitmap::new(w, h);
+    for y in 0..h {
+        for x in 0..w {
+            let v = ((x * 37 + y * 91) % 256) as u8;
+            bitmap.set(x, y, &Color::make_rgb(v as f32 / 255.0, 1.0 - v as f32 / 255.0, 0.5).to_pixel());
+        }
+    }
+    bitmap
+}
+
+#[test]
+fn texture_matches_bitmap_rect() {
+    let bitmap = checker(10, 8);
+    let dst = Rect::make_ltrb(10.0, 10.0, 90.0, 74.0);
+
+    let mut expected = Canvas::new(Bitmap::new(100, 80));
+    expected.fill_bitmap_rect(&bitmap, &dst);
+
+    let points = [
+        Point::new(10.0, 10.0),
+        Point::new(90.0, 10.0),
+        Point::new(90.0, 74.0),
+        Point::new(10.0, 74.0),
+    ];
+    let uvs = [
+        Point::new( 0.0, 0.0),
+        Point::new(10.0, 0.0),
+        Point::new(10.0, 8.0),
+        Point::new( 0.0, 8.0),
+    ];
+    let mut mesh = Canvas::new(Bitmap::new(100, 80));
+    mesh.draw_textured_vertices(&points, &uvs, &bitmap, None, &[0, 1, 2, 0, 2, 3]);
+
+    assert_eq!(expected.bitmap().diff(mesh.bitmap()).differing, 0);
+}
+
+#[test]
+fn colors_modulate_texture() {
+    let mut white = Bitmap::new(4, 4);
+    for px in white.pixels.iter_mut() {
+        *px = Color::white().to_pixel();
+    }
+
+    let uvs = [Point::new(0.0, 0.0), Point::new(4.0, 0.0), Point::new(4.0, 4.0), Point::new(0.0, 4.0)];
+    let half_red = [Color::make_argb(0.5, 1.0, 0.0, 0.0); 4];
+
+    let mut modulated = Canvas::new(Bitmap::new(100, 80));
+    modulated.draw_textured_vertices(&quad(), &uvs, &white, Some(&half_red), &[0, 1, 2, 0, 2, 3]);
+
+    let mut filled = Canvas::new(Bitmap::new(100, 80));
+    filled.fill_rect(&Rect::make_ltrb(10.0, 10.0, 90.0, 70.0), &half_red[0]);
+
+    assert!(filled.bitmap().diff(modulated.bitmap()).max_delta <= 1);
+}
+
+#[test]
+fn adjacent_triangles_leave_no_gaps() {
+    // A wobbly grid, so shared edges end at fractional coordinates
+    let n = 8;
+    let mut points = Vec::new();
+    for j in 0..n + 1 {
+        for i in 0..n + 1 {
+            let (u, v) = (i as f32 / n as f32, j as f32 / n as f32);
+            points.push(Point::new(10.0 + u*80.0 + 4.0*(v*6.0).sin(), 10.0 + v*60.0 + 4.0*(u*6.0).cos()));
+        }
+    }
+    let mut indices = Vec::new();
+    for j in 0..n {
+        for i in 0..n {
+            let a = j*(n + 1) + i;
+            indices.extend_from_slice(&[a, a + 1, a + n + 2, a, a + n + 2, a + n + 1]);
+        }
+    }
+
+    let colors = vec![Color::make_argb(0.5, 1.0, 0.0, 0.0); points.len()];
+    let mut canvas = Canvas::new(Bitmap::new(100, 80));
+    canvas.draw_vertices(&points, &colors, &indices);
+
+    // Every pixel well inside is covered exactly once
+    let expected = Color::make_argb(0.5, 1.0, 0.0, 0.0).to_pixel();
+    for y in 20..60 {
+        for x in 20..80 {
+            assert_eq!(canvas.bitmap().get(x, y).a, expected.a, "at ({}, {})", x, y);
+        }
+    }
+}
+
+#[test]
+fn triangles_past_the_guard_band_are_skipped() {
+    let color = Color::make_argb(1.0, 0.0, 1.0, 0.0);
+    let mut points = quad().to_vec();
+    points.push(Point::new(1e30, 40.0));
+    points.push(Point::new(f32::NAN, 40.0));
+
+    // Only the first triangle stays, whole
+    let mut canvas = Canvas::new(Bitmap::new(100, 80));
+    canvas.draw_vertices(&points, &[color; 6], &[0, 1, 2, 1, 2, 4, 0, 3, 5]);
+
+    let mut expected = Canvas::new(Bitmap::new(100, 80));
+    expected.draw_vertices(&quad(), &[color; 4], &[0, 1, 2]);
+    assert_eq!(canvas.bitmap().diff(expected.bitmap()).differing, 0);
+}
@@ -0,0 +1,185 @@
+extern crate dinky;
+
+mod common;
+
+use dinky::bitmap::Bitmap;
+use dinky::canvas::Canvas;
+use dinky::gallery;
+use dinky::pixel::Pixel;
+use dinky::point::Point;
+use dinky::rect::Rect;
+use dinky::view::{BitmapView, BitmapViewMut};
+
+use common::numbered;
+
+// A framebuffer with padded rows, holding a gallery image at an offset.
+// Drawing through a view must match drawing into a Bitmap, and leave every
+// byte outside the view alone.
+#[test]
+fn canvas_draws_into_borrowed_bytes() {
+    let image = gallery::find("circles_blend").unwrap();
+    let background = Pixel::from_bytes([0x5a; 4]);
+
+    let mut expected = Bitmap::new(image.width, image.height);
+    for px in expected.pixels.iter_mut() {
+        *px = background;
+    }
+    let mut canvas = Canvas::new(expected);
+    (image.draw)(&mut canvas);
+    let expected = canvas.bitmap();
+
+    let (left, top) = (3, 5);
+    let stride = (image.width + 10) * 4;
+    let mut framebuffer = vec![0x5au8; stride * (image.height + 8)];
+
+    {
+        let offset = top*stride + left*4;
+        let view = BitmapViewMut::from_bytes(&mut framebuffer[offset..], image.width, image.height, stride);
+        let mut canvas = Canvas::new(view);
+        (image.draw)(&mut canvas);
+    }
+
+    let view = BitmapView::from_bytes(&framebuffer, image.width + 10, image.height + 8, stride);
+    for y in 0..view.height {
+        for x in 0..view.width {
+            let px = view.get(x, y);
+            let inside = x >= left && x < left + image.width && y >= top && y < top + image.height;
+            let want = if inside {
+                expected.get(x - left, y - top).to_bytes()
+            } else {
+                [0x5a; 4]
+            };
+            assert_eq!(px.to_bytes(), want, "({}, {})", x, y);
+        }
+    }
+}
+
+#[test]
+fn views_share_the_bitmap() {
+    let mut bitmap = Bitmap::new(4, 3);
+    let px = Pixel::pack_argb(255, 9, 8, 7);
+    bitmap.view_mut().set(2, 1, &px);
+    assert_eq!(bitmap.get(2, 1).to_bytes(), px.to_bytes());
+    assert_eq!(bitmap.view().row(1)[2].to_bytes(), px.to_bytes());
+}
+
+// The last row needs no padding after it
+#[test]
+fn last_row_can_be_short() {
+    let mut pixels = vec![Pixel::pack_argb(0, 0, 0, 0); 2*5 + 3];
+    let view = BitmapViewMut::new(&mut pixels, 3, 3, 5);
+    assert_eq!(view.as_view().row(2).len(), 3);
+}
+
+#[test]
+#[should_panic(expected = "too few")]
+fn rejects_short_memory() {
+    let mut bytes = vec![0u8; 4*(2*5 + 2)];
+    BitmapViewMut::from_bytes(&mut bytes, 3, 3, 20);
+}
+
+#[test]
+#[should_panic(expected = "whole number of pixels")]
+fn rejects_partial_pixel_stride() {
+    let bytes = vec![0u8; 64];
+    BitmapView::from_bytes(&bytes, 2, 2, 10);
+}
+
+fn rg(px: Pixel) -> (u8, u8, u8) {
+    (px.a, px.r, px.g)
+}
+
+#[test]
+fn subsets_are_clipped_regions() {
+    let bitmap = numbered(10, 8);
+
+    let sub = bitmap.subset(&Rect::make_ltrb(2.0, 3.0, 6.0, 5.0));
+    assert_eq!((sub.width, sub.height), (4, 2));
+    assert_eq!(rg(sub.get(0, 0)), (255, 2, 3));
+    assert_eq!(rg(sub.get(3, 1)), (255, 5, 4));
+
+    // Subsets of subsets are relative to the view
+    let inner = sub.subset(&Rect::make_xywh(1.0, 1.0, 2.0, 1.0));
+    assert_eq!(rg(inner.get(0, 0)), (255, 3, 4));
+
+    let edge = bitmap.subset(&Rect::make_ltrb(-5.0, 6.0, 3.0, 20.0));
+    assert_eq!((edge.width, edge.height), (3, 2));
+    assert_eq!(rg(edge.get(0, 0)), (255, 0, 6));
+
+    let outside = bitmap.subset(&Rect::make_ltrb(12.0, 9.0, 20.0, 20.0));
+    assert_eq!((outside.width, outside.height), (0, 0));
+}
+
+#[test]
+fn extract_copies_out() {
+    let bitmap = numbered(10, 8);
+    let sprite = bitmap.extract(&Rect::make_xywh(4.0, 2.0, 3.0, 3.0));
+    assert_eq!((sprite.width, sprite.height, sprite.pixels.len()), (3, 3, 9));
+    assert_eq!(rg(sprite.get(2, 2)), (255, 6, 4));
+}
+
+#[test]
+fn copy_replaces_without_blending() {
+    let mut dst = Bitmap::new(6, 6);
+    for px in dst.pixels.iter_mut() {
+        *px = Pixel::pack_rgb(0, 0, 255);
+    }
+    let mut src = numbered(4, 4);
+    src.set(1, 1, &Pixel::pack_argb(0, 0, 0, 0));
+
+    dst.copy_from(&src.view(), &Rect::make_wh(4.0, 4.0), &Point::new(1.0, 2.0));
+    assert_eq!(rg(dst.get(2, 3)), (0, 0, 0));
+    assert_eq!(rg(dst.get(4, 5)), (255, 3, 3));
+    assert_eq!(rg(dst.get(0, 0)), (255, 0, 0));
+    assert_eq!(dst.get(0, 0).b, 255);
+}
+
+// Both rects clipped, with the surviving part landing where it would have
+#[test]
+fn copy_clips_both_sides() {
+    let src = numbered(5, 5);
+    let mut dst = Bitmap::new(4, 4);
+
+    dst.copy_from(&src.view(), &Rect::make_ltrb(-1.0, 2.0, 4.0, 9.0), &Point::new(-2.0, 1.0));
+    // src (0, 2) lands at (-2 + 1, 1) = (-1, 1), so only src x >= 1 shows
+    assert_eq!(rg(dst.get(0, 1)), (255, 1, 2));
+    assert_eq!(rg(dst.get(2, 3)), (255, 3, 4));
+    assert_eq!(rg(dst.get(3, 1)), (0, 0, 0));
+    assert_eq!(rg(dst.get(0, 0)), (0, 0, 0));
+}
+
+// Far-off rects and points copy nothing, unless they cancel out
+#[test]
+fn copy_handles_far_coordinates() {
+    let src = numbered(5, 5);
+    let mut dst = Bitmap::new(4, 4);
+
+    dst.copy_from(&src.view(), &Rect::make_ltrb(-1e30, 0.0, 3.0, 3.0), &Point::new(0.0, 0.0));
+    dst.copy_from(&src.view(), &Rect::make_wh(3.0, 3.0), &Point::new(1e30, -1e30));
+    dst.copy_from(&src.view(), &Rect::make_wh(3.0, 3.0), &Point::new(-3e9, 0.0));
+    dst.copy_from(&src.view(), &Rect::make_ltrb(-1e30, -1e30, 1e30, 1e30), &Point::new(-1e30, -1e29));
+    assert!(dst.pixels.iter().all(|px| px.a == 0));
+
+    // The rect's left is far out, but the point moves it straight back
+    dst.copy_from(&src.view(), &Rect::make_ltrb(-1e30, 1.0, 3.0, 3.0), &Point::new(-1e30, 0.0));
+    assert_eq!(rg(dst.get(0, 0)), (255, 0, 1));
+    assert_eq!(rg(dst.get(2, 1)), (255, 2, 2));
+}
+
+// A dirty region redrawn through a subset matches a full redraw there
+#[test]
+fn canvas_draws_into_subset() {
+    let image = gallery::find("tri").unwrap();
+    let full = image.render();
+
+    let mut bitmap = Bitmap::new(image.width, image.height);
+    let dirty = Rect::make_xywh(40.0, 60.0, 100.0, 50.0);
+    {
+        let mut canvas = Canvas::with_origin(bitmap.subset_mut(&dirty), 40, 60);
+        (image.draw)(&mut canvas);
+    }
+
+    assert!(full.extract(&dirty).pixels.iter().any(|px| px.a != 0));
+    assert_eq!(bitmap.extract(&dirty).diff(&full.extract(&dirty)).differing, 0);
+    assert_eq!(rg(bitmap.get(39, 60)), (0, 0, 0));
+}